@@ -4,6 +4,7 @@ use dmktp::wrapper::Wrapper;
 use dmktp::address::Address;
 use dmktp::auction::{ Auction, Bid };
 use dmktp::sealedbid::{ SealedBid, UnsealedBid, SealedAuction };
+use dmktp::instrument::default_instrument;
 use utils::addr_from_keyfile;
 use swth_cli_libv2::errors::CliError;
 
@@ -110,10 +111,9 @@ pub fn all_seeded_wrapped() -> Vec<Wrapper> {
 
 pub fn gensell1() -> Vec<Wrapper> {
     let mut buffer: Vec<Wrapper> = Vec::new();
-    let all_addrs = all_addrs_vec();
 
     for i in 1..21 {
-        buffer.push(Wrapper::So(SellOrder::from_vals(all_addrs[1].clone(), i, i, i)))
+        buffer.push(Wrapper::So(SellOrder::from_vals(Address::empty(), i, i, i)))
     }
 
     buffer
@@ -121,10 +121,9 @@ pub fn gensell1() -> Vec<Wrapper> {
 
 pub fn gensell2() -> Vec<Wrapper> {
     let mut buffer: Vec<Wrapper> = Vec::new();
-    let all_addrs = all_addrs_vec();
 
     for i in 21..41 {
-        buffer.push(Wrapper::So(SellOrder::from_vals(all_addrs[1].clone(), i, i, i)))
+        buffer.push(Wrapper::So(SellOrder::from_vals(Address::empty(), i, i, i)))
     }
 
     buffer
@@ -132,10 +131,9 @@ pub fn gensell2() -> Vec<Wrapper> {
 
 pub fn gensell3() -> Vec<Wrapper> {
     let mut buffer: Vec<Wrapper> = Vec::new();
-    let all_addrs = all_addrs_vec();
 
     for i in 41..61 {
-        buffer.push(Wrapper::So(SellOrder::from_vals(all_addrs[1].clone(), i, i, i)))
+        buffer.push(Wrapper::So(SellOrder::from_vals(Address::empty(), i, i, i)))
     }
 
     buffer
@@ -143,10 +141,9 @@ pub fn gensell3() -> Vec<Wrapper> {
 
 pub fn genbuy1() -> Vec<Wrapper> {
     let mut buffer: Vec<Wrapper> = Vec::new();
-    let all_addrs = all_addrs_vec();
 
     for i in 1..21 {
-        buffer.push(Wrapper::Bo(BuyOrder::from_vals(all_addrs[2].clone(), i, i, i)))
+        buffer.push(Wrapper::Bo(BuyOrder::from_vals(Address::empty(), i, i, i)))
     }
 
     buffer
@@ -154,10 +151,9 @@ pub fn genbuy1() -> Vec<Wrapper> {
 
 pub fn genbuy2() -> Vec<Wrapper> {
     let mut buffer: Vec<Wrapper> = Vec::new();
-    let all_addrs = all_addrs_vec();
 
     for i in 21..41 {
-        buffer.push(Wrapper::Bo(BuyOrder::from_vals(all_addrs[2].clone(), i, i, i)))
+        buffer.push(Wrapper::Bo(BuyOrder::from_vals(Address::empty(), i, i, i)))
     }
 
     buffer
@@ -166,10 +162,9 @@ pub fn genbuy2() -> Vec<Wrapper> {
 
 pub fn genbuy3() -> Vec<Wrapper> {
     let mut buffer: Vec<Wrapper> = Vec::new();
-    let all_addrs = all_addrs_vec();
 
     for i in 41..61 {
-        buffer.push(Wrapper::Bo(BuyOrder::from_vals(all_addrs[2].clone(), i, i, i)))
+        buffer.push(Wrapper::Bo(BuyOrder::from_vals(Address::empty(), i, i, i)))
     }
 
     buffer
@@ -177,10 +172,9 @@ pub fn genbuy3() -> Vec<Wrapper> {
 
 pub fn genoddbuy1() -> Vec<Wrapper> {
     let mut buffer: Vec<Wrapper> = Vec::new();
-    let all_addrs = all_addrs_vec();
 
     for i in 30..50 {
-        buffer.push(Wrapper::Bo(BuyOrder::from_vals(all_addrs[2].clone(), i, i, i)))
+        buffer.push(Wrapper::Bo(BuyOrder::from_vals(Address::empty(), i, i, i)))
     }
 
     buffer
@@ -188,63 +182,49 @@ pub fn genoddbuy1() -> Vec<Wrapper> {
 
 pub fn genoddsell1() -> Vec<Wrapper> {
     let mut buffer: Vec<Wrapper> = Vec::new();
-    let all_addrs = all_addrs_vec();
 
     for i in 12..32 {
-        buffer.push(Wrapper::So(SellOrder::from_vals(all_addrs[1].clone(), i, i, i)))
+        buffer.push(Wrapper::So(SellOrder::from_vals(Address::empty(), i, i, i)))
     }
 
     buffer
 }
 
 pub fn gen_auction() -> Vec<Wrapper> {
-    let auction: Auction = Auction::new(1, format!("Initial auction"), true, 1_000_000, 3_000_000_000);
+    let auction: Auction = Auction::new(1, format!("Initial auction"), true, 1_000_000, 3_000_000_000, default_instrument());
     vec![Wrapper::Na(auction)]
 }
 
 pub fn gen_sealed_auction1() -> Wrapper {
-    let auction: SealedAuction = SealedAuction::new(1, String::from("demo sealed price 2nd bid auction #01"), true, 1_000_000, 1533453523);
+    let auction: SealedAuction = SealedAuction::new(1, String::from("demo sealed price 2nd bid auction #01"), true, 1_000_000, 1533453523, default_instrument());
     Wrapper::Ns(auction)
 }
 
 pub fn gen_sealed_auction2() -> Wrapper {
-    let auction: SealedAuction = SealedAuction::new(2, String::from("demo sealed price 2nd bid auction #02"), true, 2_000_000, 1533453523);
+    let auction: SealedAuction = SealedAuction::new(2, String::from("demo sealed price 2nd bid auction #02"), true, 2_000_000, 1533453523, default_instrument());
     Wrapper::Ns(auction)
 }
 
-pub fn gen_sealed_auction_ng(amt: u64) -> Wrapper {
-    let auction: SealedAuction = SealedAuction::new(3, String::from("demo sealed price 2nd bid auction #03"), true, amt, 1533453523);
+pub fn gen_sealed_auction_ng(amt: u64, instrument: String) -> Wrapper {
+    let auction: SealedAuction = SealedAuction::new(3, String::from("demo sealed price 2nd bid auction #03"), true, amt, 1533453523, instrument);
     Wrapper::Ns(auction)
 }
 
 
 pub fn gen_unsealed_bid1(_keyfile: Option<&str>) -> Result<Wrapper, CliError> {
-    let addr = match addr_from_keyfile(_keyfile) {
-        Ok(v) => v,
-        Err(e) => return Err(CliError::CustomError { contents: format!("error creating unsealed bid due to keyfile management issues: {}\n", e)})
-    };
-
-    let ubid: UnsealedBid = UnsealedBid::from_vals(addr, 1, 1_000, format!("21fa2f9a1856a7bc5f8938616e358edc"));
+    // The reveal is bound to the signer by the processor, which fills in the address
+    // before checking it against the sealed digest.
+    let ubid: UnsealedBid = UnsealedBid::from_vals(Address::empty(), 1, 1_000, format!("21fa2f9a1856a7bc5f8938616e358edc"));
     Ok(Wrapper::Ub(ubid))
 }
 
 pub fn gen_unsealed_bid2(_keyfile: Option<&str>) -> Result<Wrapper, CliError> {
-    let addr = match addr_from_keyfile(_keyfile) {
-        Ok(v) => v,
-        Err(e) => return Err(CliError::CustomError { contents: format!("error creating unsealed bid due to keyfile management issues: {}\n", e)})
-    };
-
-    let ubid: UnsealedBid = UnsealedBid::from_vals(addr, 1, 2_000, format!("21fa2f9a1856a7bc5f8938616e358edc"));
+    let ubid: UnsealedBid = UnsealedBid::from_vals(Address::empty(), 1, 2_000, format!("21fa2f9a1856a7bc5f8938616e358edc"));
     Ok(Wrapper::Ub(ubid))
 }
 
 pub fn gen_unsealed_bid3(_keyfile: Option<&str>) -> Result<Wrapper, CliError> {
-    let addr = match addr_from_keyfile(_keyfile) {
-        Ok(v) => v,
-        Err(e) => return Err(CliError::CustomError { contents: format!("error creating unsealed bid due to keyfile management issues: {}\n", e)})
-    };
-
-    let ubid: UnsealedBid = UnsealedBid::from_vals(addr, 1, 3_000, format!("21fa2f9a1856a7bc5f8938616e358edc"));
+    let ubid: UnsealedBid = UnsealedBid::from_vals(Address::empty(), 1, 3_000, format!("21fa2f9a1856a7bc5f8938616e358edc"));
     Ok(Wrapper::Ub(ubid))
 }
 
@@ -6,15 +6,29 @@ use futures::Stream;
 use tokio_core;
 use base64::decode;
 
+use serde::de::DeserializeOwned;
 use serde_cbor::{ from_slice };
 use serde_json::{ self, Value as JsonValue };
 
 
 
 use dmktp::orderbook::OrderBook;
+use dmktp::order::{ BuyOrder, SellOrder };
 use dmktp::balancebook::BalanceBook;
+use dmktp::decimal::Precision;
+use dmktp::useracct::UserAccount;
 use dmktp::auction::{ AuctionList, Auction };
-use dmktp::addressing::{ BALANCEBOOK, ORDERBOOK, AUCTIONLIST, SEALEDAUCTIONLIST, CBALLOTBOXRESULT };
+use dmktp::addressing::{ self, BALANCEBOOK, AUCTIONLIST, SEALEDAUCTIONLIST, CBALLOTBOXRESULT, PERMISSIONS, MARKETSTATUS, ORDERSTATUS, TRADELEDGER, INSTRUMENTS, FEESCHEDULE,
+                         ACCOUNT_PREFIX, AUCTION_PREFIX, SEALED_AUCTION_PREFIX, TRADE_PREFIX, ORDERSTATUS_PREFIX, BLOCKINFO_CONFIG };
+use dmktp::address::Address;
+use dmktp::blockinfo;
+use dmktp::fees::FeeSchedule;
+use dmktp::instrument::InstrumentRegistry;
+use dmktp::marketstatus::MarketStatus;
+use dmktp::orderstatus::{ OrderStatusBook, OrderRecord };
+use dmktp::tradeledger::{ TradeLedger, Trade };
+use dmktp::permissions::Permissions;
+use dmktp::rules::TradingRules;
 use dmktp::sealedbid::{ SealedAuctionList, SealedAuction, SealedBid, UnsealedBid };
 use swth_cli_libv2::errors::CliError;
 
@@ -24,14 +38,16 @@ const LOCALHOST: &'static str = "http://127.0.0.1:8008";
 const STATE_QUERY_ROUTE: &'static str = "/state?address=";
 
 
-pub fn decode_and_fmt_orderbook(x: &JsonValue) -> Result<(), CliError> {
+// Decodes the value stored at one entry of a state query.
+fn decode_entry<T: DeserializeOwned>(x: &JsonValue) -> Result<T, CliError> {
     let base64_decoded = x.get(String::from("data"))?
                                            .as_str()?;
     let cbor_string = decode(base64_decoded)?;
-    let as_value: OrderBook = from_slice(&cbor_string[0..])?;
-    println!("Order book state: {}\n", as_value);
+    Ok(from_slice(&cbor_string[0..])?)
+}
 
-    Ok(())
+fn entry_address(x: &JsonValue) -> Result<String, CliError> {
+    Ok(String::from(x.get(String::from("address"))?.as_str()?))
 }
 
 pub fn decode_and_fmt_cbresult(x: &JsonValue) -> Result<(), CliError> {
@@ -45,72 +61,189 @@ pub fn decode_and_fmt_cbresult(x: &JsonValue) -> Result<(), CliError> {
     Ok(())
 }
 
-pub fn decode_and_fmt_balancebook(x: &JsonValue) -> Result<(), CliError> {
+pub fn decode_and_fmt_permissions(x: &JsonValue) -> Result<(), CliError> {
     let base64_decoded = x.get(String::from("data"))?
                                            .as_str()?;
 
     let cbor_string = decode(base64_decoded)?;
-    let as_value: BalanceBook = from_slice(&cbor_string[0..])?;
-    println!("balancebook state: {}", as_value);
+    let as_value: Permissions = from_slice(&cbor_string[0..])?;
+    println!("{}", as_value);
     Ok(())
 }
-
-pub fn decode_and_fmt_auctionlist(x: &JsonValue) -> Result<(), CliError> {
+pub fn decode_and_fmt_marketstatus(x: &JsonValue) -> Result<(), CliError> {
     let base64_decoded = x.get(String::from("data"))?
                                            .as_str()?;
 
     let cbor_string = decode(base64_decoded)?;
-    let as_value: AuctionList = from_slice(&cbor_string[0..])?;
-    println!("all auctions state: {:?}", as_value);
+    let as_value: MarketStatus = from_slice(&cbor_string[0..])?;
+    println!("{}", as_value);
     Ok(())
 }
 
-pub fn decode_and_fmt_sealedauctionlist(x: &JsonValue) -> Result<(), CliError> {
+pub fn decode_and_fmt_instruments(x: &JsonValue) -> Result<(), CliError> {
     let base64_decoded = x.get(String::from("data"))?
                                            .as_str()?;
 
     let cbor_string = decode(base64_decoded)?;
-    let as_value: SealedAuctionList = from_slice(&cbor_string[0..])?;
-    println!("all sealed bid auctions state: {}", as_value);
+    let as_value: InstrumentRegistry = from_slice(&cbor_string[0..])?;
+    println!("{}", as_value);
     Ok(())
 }
 
+// Which orders `get_order`, `my_orders` and `get_orders` print out of the status book.
+pub enum OrderQuery {
+    All,
+    Id(String),
+    Owner(Address),
+}
 
+// Each order's status record is stored at its own address, under a prefix per owner; those
+// of a market that predates that may still be in the old status book, and are only current
+// there until first written back.
+pub fn fetch_orderstatus(_url: Option<&str>, _owner: Option<&Address>) -> Result<OrderStatusBook, CliError> {
+    let prefix = match _owner {
+        Some(addr) => addressing::order_status_prefix(addr),
+        None => String::from(ORDERSTATUS_PREFIX),
+    };
+    let (legacy_url, records_url): (String, String) = match _url {
+        Some(non_local) => (format!("{}{}{}", non_local, STATE_QUERY_ROUTE, ORDERSTATUS), format!("{}{}{}", non_local, STATE_QUERY_ROUTE, prefix)),
+        None => (format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, ORDERSTATUS), format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, prefix)),
+    };
 
-pub fn init_get_state_orderbook(_url: Option<&str>) -> Result<(), CliError> {
-    let req_url_string: String = match _url {
-        Some(non_local) => format!("{}{}{}", non_local, STATE_QUERY_ROUTE, ORDERBOOK),
-        None => format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, ORDERBOOK),
+    let mut status: OrderStatusBook = match fetch_state(legacy_url)?.first() {
+        Some(x) => decode_entry(x)?,
+        None => OrderStatusBook::new(),
+    };
+    for x in fetch_state(records_url)?.iter() {
+        let record: OrderRecord = decode_entry(x)?;
+        status.records.insert(record.id.clone(), record);
+    }
+    Ok(status)
+}
+
+// The book's own address holds everything but its orders, which are stored a price level
+// per address under the instrument's levels prefix.
+pub fn fetch_orderbook(_url: Option<&str>, _instrument: &str) -> Result<Option<OrderBook>, CliError> {
+    let book_addr = addressing::orderbook_addr(_instrument);
+    let prefix = addressing::levels_prefix(_instrument);
+    let (book_url, levels_url): (String, String) = match _url {
+        Some(non_local) => (format!("{}{}{}", non_local, STATE_QUERY_ROUTE, book_addr), format!("{}{}{}", non_local, STATE_QUERY_ROUTE, prefix)),
+        None => (format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, book_addr), format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, prefix)),
+    };
+
+    let mut ob: OrderBook = match fetch_state(book_url)?.first() {
+        Some(x) => decode_entry(x)?,
+        None => return Ok(None),
+    };
+    for x in fetch_state(levels_url)?.iter() {
+        let addr = entry_address(x)?;
+        if addr == book_addr {
+            continue;
+        }
+        match &addr[prefix.len()..prefix.len() + 1] {
+            "0" => decode_entry::<Vec<BuyOrder>>(x)?.into_iter().for_each(|o| ob.buy_orders.push(o)),
+            _ => decode_entry::<Vec<SellOrder>>(x)?.into_iter().for_each(|o| ob.sell_orders.push(o)),
+        }
+    }
+    let instruments = fetch_instruments(_url)?;
+    ob.precision = instruments.precision(_instrument);
+    ob.matching = instruments.get(_instrument).map(|x| x.matching).unwrap_or_default();
+    ob.mode = instruments.get(_instrument).map(|x| x.mode).unwrap_or_default();
+    Ok(Some(ob))
+}
+
+// Other participants' iceberg orders are shown without their reserve; _viewer sees all of
+// their own. The reserve is still in the state read, which anyone can fetch.
+pub fn init_get_state_orderbook(_url: Option<&str>, _instrument: &str, _viewer: Option<&Address>) -> Result<(), CliError> {
+    match fetch_orderbook(_url, _instrument)? {
+        Some(ob) => println!("Order book state: {}\n", ob.view_for(_viewer)),
+        None => return Err(CliError::CustomError { contents: String::from("No orderbook exists yet!")}),
     };
 
-    get_state(req_url_string, 0)
+    Ok(())
+}
+
+// Accounts are stored one per address; those of a market that predates that may still be
+// in the old balance book, and are only current there until first written back.
+pub fn fetch_balancebook(_url: Option<&str>) -> Result<BalanceBook, CliError> {
+    let (legacy_url, accounts_url): (String, String) = match _url {
+        Some(non_local) => (format!("{}{}{}", non_local, STATE_QUERY_ROUTE, BALANCEBOOK), format!("{}{}{}", non_local, STATE_QUERY_ROUTE, ACCOUNT_PREFIX)),
+        None => (format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, BALANCEBOOK), format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, ACCOUNT_PREFIX)),
+    };
+
+    let mut bb: BalanceBook = match fetch_state(legacy_url)?.first() {
+        Some(x) => decode_entry(x)?,
+        None => BalanceBook::new(),
+    };
+    for x in fetch_state(accounts_url)?.iter() {
+        let acct: UserAccount = decode_entry(x)?;
+        bb.balance_book.insert(acct.addr.to_string(), acct);
+    }
+    Ok(bb)
 }
 
 pub fn init_get_state_balancebook(_url: Option<&str>) -> Result<(), CliError> {
-    let req_url_string: String = match _url {
-        Some(non_local) => format!("{}{}{}", non_local, STATE_QUERY_ROUTE, BALANCEBOOK),
-        None => format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, BALANCEBOOK),
+    let bb = fetch_balancebook(_url)?;
+    match bb.balance_book.len() {
+        0 => return Err(CliError::CustomError { contents: String::from("No balancebook exists yet!")}),
+        _ => println!("balancebook state: {}", bb.show(&fetch_instruments(_url)?)),
+    };
+
+    Ok(())
+}
+
+// The list's own address has the total auctioned, and the auctions of a list that predates
+// each auction having its own address.
+pub fn fetch_auctionlist(_url: Option<&str>) -> Result<Option<AuctionList>, CliError> {
+    let (list_url, auctions_url): (String, String) = match _url {
+        Some(non_local) => (format!("{}{}{}", non_local, STATE_QUERY_ROUTE, AUCTIONLIST), format!("{}{}{}", non_local, STATE_QUERY_ROUTE, AUCTION_PREFIX)),
+        None => (format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, AUCTIONLIST), format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, AUCTION_PREFIX)),
     };
 
-    get_state(req_url_string, 1)
+    let mut alist: AuctionList = match fetch_state(list_url)?.first() {
+        Some(x) => decode_entry(x)?,
+        None => return Ok(None),
+    };
+    for x in fetch_state(auctions_url)?.iter() {
+        let auction: Auction = decode_entry(x)?;
+        alist.add_auction(auction);
+    }
+    Ok(Some(alist))
 }
 
 pub fn init_get_state_auctionlist(_url: Option<&str>) -> Result<(), CliError> {
-    let req_url_string: String = match _url {
-        Some(non_local) => format!("{}{}{}", non_local, STATE_QUERY_ROUTE, AUCTIONLIST),
-        None => format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, AUCTIONLIST),
+    match fetch_auctionlist(_url)? {
+        Some(ref alist) if !alist.contents.is_empty() => println!("all auctions state: {:?}", alist),
+        _ => return Err(CliError::CustomError { contents: String::from("No auctions exist yet!")}),
     };
 
-    get_state(req_url_string, 2)
+    Ok(())
+}
+
+pub fn fetch_sealedauctionlist(_url: Option<&str>) -> Result<Option<SealedAuctionList>, CliError> {
+    let (list_url, auctions_url): (String, String) = match _url {
+        Some(non_local) => (format!("{}{}{}", non_local, STATE_QUERY_ROUTE, SEALEDAUCTIONLIST), format!("{}{}{}", non_local, STATE_QUERY_ROUTE, SEALED_AUCTION_PREFIX)),
+        None => (format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, SEALEDAUCTIONLIST), format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, SEALED_AUCTION_PREFIX)),
+    };
+
+    let mut slist: SealedAuctionList = match fetch_state(list_url)?.first() {
+        Some(x) => decode_entry(x)?,
+        None => return Ok(None),
+    };
+    for x in fetch_state(auctions_url)?.iter() {
+        let auction: SealedAuction = decode_entry(x)?;
+        slist.add_sealed_auction(auction);
+    }
+    Ok(Some(slist))
 }
 
 pub fn init_get_state_sealedauctionlist(_url: Option<&str>) -> Result<(), CliError> {
-    let req_url_string: String = match _url {
-        Some(non_local) => format!("{}{}{}", non_local, STATE_QUERY_ROUTE, SEALEDAUCTIONLIST),
-        None => format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, SEALEDAUCTIONLIST),
+    match fetch_sealedauctionlist(_url)? {
+        Some(ref slist) if !slist.contents.is_empty() => println!("all sealed bid auctions state: {}", slist),
+        _ => return Err(CliError::CustomError { contents: String::from("No sealed bid auctions exist yet!")}),
     };
 
-    get_state(req_url_string, 3)
+    Ok(())
 }
 
 pub fn init_get_cbresult_state(_url: Option<&str>) -> Result<(), CliError> {
@@ -124,8 +257,179 @@ pub fn init_get_cbresult_state(_url: Option<&str>) -> Result<(), CliError> {
     get_state(req_url_string, 4)
 }
 
+pub fn init_get_state_permissions(_url: Option<&str>) -> Result<(), CliError> {
+    let req_url_string: String = match _url {
+        Some(non_local) => format!("{}{}{}", non_local, STATE_QUERY_ROUTE, PERMISSIONS),
+        None => format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, PERMISSIONS),
+    };
+
+    get_state(req_url_string, 5)
+}
+
+pub fn init_get_state_marketstatus(_url: Option<&str>) -> Result<(), CliError> {
+    let req_url_string: String = match _url {
+        Some(non_local) => format!("{}{}{}", non_local, STATE_QUERY_ROUTE, MARKETSTATUS),
+        None => format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, MARKETSTATUS),
+    };
+
+    get_state(req_url_string, 6)
+}
+
+pub fn init_get_state_instruments(_url: Option<&str>) -> Result<(), CliError> {
+    let req_url_string: String = match _url {
+        Some(non_local) => format!("{}{}{}", non_local, STATE_QUERY_ROUTE, INSTRUMENTS),
+        None => format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, INSTRUMENTS),
+    };
+
+    get_state(req_url_string, 7)
+}
+
+
+pub fn init_get_state_orderstatus(_url: Option<&str>, _query: OrderQuery) -> Result<(), CliError> {
+    let owner = match _query {
+        OrderQuery::Owner(ref addr) => Some(addr),
+        _ => None,
+    };
+    let status = fetch_orderstatus(_url, owner)?;
+    if status.records.is_empty() {
+        return Err(CliError::CustomError { contents: String::from("No orders have been submitted yet!")});
+    }
+    match _query {
+        OrderQuery::All => println!("order status: {}", status),
+        OrderQuery::Id(ref id) => match status.get(id) {
+            Some(record) => println!("{}", record),
+            None => return Err(CliError::CustomError { contents: format!("No order with ID {} exists!", id)}),
+        },
+        OrderQuery::Owner(ref addr) => status.for_owner(addr)
+                                             .iter()
+                                             .for_each(|x| println!("{}", x)),
+    };
+
+    Ok(())
+}
+
+
+// The trade tape is returned rather than printed so it can also be exported. Trades are
+// stored one per address, by instrument; those of a market that predates that come first.
+pub fn fetch_tradeledger(_url: Option<&str>) -> Result<TradeLedger, CliError> {
+    let (legacy_url, trades_url): (String, String) = match _url {
+        Some(non_local) => (format!("{}{}{}", non_local, STATE_QUERY_ROUTE, TRADELEDGER), format!("{}{}{}", non_local, STATE_QUERY_ROUTE, TRADE_PREFIX)),
+        None => (format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, TRADELEDGER), format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, TRADE_PREFIX)),
+    };
+
+    let mut ledger: TradeLedger = match fetch_state(legacy_url)?.first() {
+        Some(x) => decode_entry(x)?,
+        None => TradeLedger::new(),
+    };
+    for x in fetch_state(trades_url)?.iter() {
+        let trade: Trade = decode_entry(x)?;
+        ledger.trades.push(trade);
+    }
+    Ok(ledger)
+}
+
+
+pub fn fetch_fees(_url: Option<&str>) -> Result<FeeSchedule, CliError> {
+    let req_url_string: String = match _url {
+        Some(non_local) => format!("{}{}{}", non_local, STATE_QUERY_ROUTE, FEESCHEDULE),
+        None => format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, FEESCHEDULE),
+    };
+
+    let data_vec = fetch_state(req_url_string)?;
+    match data_vec.first() {
+        Some(x) => {
+            let base64_decoded = x.get(String::from("data"))?
+                                                   .as_str()?;
+            let cbor_string = decode(base64_decoded)?;
+            Ok(from_slice(&cbor_string[0..])?)
+        }
+        None => Ok(FeeSchedule::new()),
+    }
+}
+
+// Until something is listed the registry holds only the default instrument.
+pub fn fetch_instruments(_url: Option<&str>) -> Result<InstrumentRegistry, CliError> {
+    let req_url_string: String = match _url {
+        Some(non_local) => format!("{}{}{}", non_local, STATE_QUERY_ROUTE, INSTRUMENTS),
+        None => format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, INSTRUMENTS),
+    };
+
+    let data_vec = fetch_state(req_url_string)?;
+    match data_vec.first() {
+        Some(x) => {
+            let base64_decoded = x.get(String::from("data"))?
+                                                   .as_str()?;
+            let cbor_string = decode(base64_decoded)?;
+            Ok(from_slice(&cbor_string[0..])?)
+        }
+        None => Ok(InstrumentRegistry::new()),
+    }
+}
+
+pub fn fetch_rules(_url: Option<&str>, _instrument: &str) -> Result<TradingRules, CliError> {
+    match fetch_instruments(_url)?.get(_instrument) {
+        Some(v) => Ok(v.rules),
+        None => Err(CliError::CustomError { contents: format!("Instrument {} is not listed", _instrument)}),
+    }
+}
+
+
+// The block the next transaction will be processed in, at the earliest.
+pub fn fetch_block_num(_url: Option<&str>) -> Result<u64, CliError> {
+    let req_url_string: String = match _url {
+        Some(non_local) => format!("{}{}{}", non_local, STATE_QUERY_ROUTE, BLOCKINFO_CONFIG),
+        None => format!("{}{}{}", LOCALHOST, STATE_QUERY_ROUTE, BLOCKINFO_CONFIG),
+    };
+
+    match fetch_state(req_url_string)?.first() {
+        Some(x) => {
+            let base64_decoded = x.get(String::from("data"))?
+                                                   .as_str()?;
+            match blockinfo::latest_block(&decode(base64_decoded)?) {
+                Ok(latest) => Ok(latest.saturating_add(1)),
+                Err(e) => Err(CliError::CustomError { contents: format!("{}", e)}),
+            }
+        }
+        None => Err(CliError::CustomError { contents: String::from("No block number is recorded; the market needs the Block Info family running")}),
+    }
+}
+
+// Unlisted instruments are read as whole numbers.
+pub fn fetch_precision(_url: Option<&str>, _instrument: &str) -> Result<Precision, CliError> {
+    Ok(fetch_instruments(_url)?.precision(_instrument))
+}
+
 
 pub fn get_state(_url: String, type_code: usize) -> Result<(), CliError> {
+    let data_vec = fetch_state(_url)?;
+    let mut data_vec_iter = data_vec.iter();
+    
+    match type_code {
+        4 => match data_vec_iter.len() {
+            0 => return Err(CliError::CustomError { contents: String::from("No CRange voting result has been posted yet!")}),
+            _ => data_vec_iter.try_for_each(|x| decode_and_fmt_cbresult(x))?
+        }
+        5 => match data_vec_iter.len() {
+            0 => println!("No permission list on-chain yet; only the bootstrap admin key is authorized."),
+            _ => data_vec_iter.try_for_each(|x| decode_and_fmt_permissions(x))?
+        }
+        6 => match data_vec_iter.len() {
+            0 => println!("Market step: 0 (no step has been taken yet)"),
+            _ => data_vec_iter.try_for_each(|x| decode_and_fmt_marketstatus(x))?
+        }
+        7 => match data_vec_iter.len() {
+            0 => println!("{}", InstrumentRegistry::new()),
+            _ => data_vec_iter.try_for_each(|x| decode_and_fmt_instruments(x))?
+        }
+        _ => unreachable!()
+    };
+
+    
+    Ok(())
+}
+
+// Returns the "data" entries of a state query; empty when nothing is stored at the address.
+pub fn fetch_state(_url: String) -> Result<Vec<JsonValue>, CliError> {
 
 
     let hyper_uri = match _url.parse::<hyper::Uri>() {
@@ -176,35 +480,9 @@ pub fn get_state(_url: String, type_code: usize) -> Result<(), CliError> {
     };
 
 
-    let mut data_vec_iter = response_as_serde_value.get(String::from("data"))?
-                                                       .as_array()?
-                                                       .iter();
-    
-    match type_code {
-        0 => match data_vec_iter.len() {
-            0 => return Err(CliError::CustomError { contents: String::from("No orderbook exists yet!")}),
-            _ => data_vec_iter.try_for_each(|x| decode_and_fmt_orderbook(x))?
-        },
-        1 => match data_vec_iter.len() {
-            0 => return Err(CliError::CustomError { contents: String::from("No balancebook exists yet!")}),
-            _ => data_vec_iter.try_for_each(|x| decode_and_fmt_balancebook(x))?
-        },
-        2 => match data_vec_iter.len() {
-            0 => return Err(CliError::CustomError { contents: String::from("No auctions exist yet!")}),
-            _ => data_vec_iter.try_for_each(|x| decode_and_fmt_auctionlist(x))?
+    let data_vec = response_as_serde_value.get(String::from("data"))?
+                                          .as_array()?
+                                          .clone();
 
-        }
-        3 => match data_vec_iter.len() {
-            0 => return Err(CliError::CustomError { contents: String::from("No sealed bid auctions exist yet!")}),
-            _ => data_vec_iter.try_for_each(|x| decode_and_fmt_sealedauctionlist(x))?
-        },
-        4 => match data_vec_iter.len() {
-            0 => return Err(CliError::CustomError { contents: String::from("No CRange voting result has been posted yet!")}),
-            _ => data_vec_iter.try_for_each(|x| decode_and_fmt_cbresult(x))?
-        }
-        _ => unreachable!()
-    };
-
-    
-    Ok(())
-}
\ No newline at end of file
+    Ok(data_vec)
+}
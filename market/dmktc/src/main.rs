@@ -14,21 +14,41 @@ extern crate hyper;
 extern crate base64;
 extern crate tokio_core;
 extern crate rand;
+extern crate protobuf;
 extern crate dmktp;
 
 use clap::{ App, Arg };
 use swth_cli_libv2::clireq::{ CliRequest, ClientRequest };
 use failure::Error;
-use dmktp::order::{ BuyOrder, SellOrder, OrderT };
-use dmktp::wrapper::Wrapper;
+use dmktp::order::{ BuyOrder, SellOrder, OrderT, OrderType, SelfTradePrevention };
+use dmktp::orderbook::OrderBook;
+use dmktp::wrapper::{ Wrapper, Touches };
+use dmktp::address::Address;
 use dmktp::auction::{ Auction, Bid };
+use dmktp::permissions::PermissionMsg;
+use dmktp::adminmsg::{ AdminMsg, BalanceChange };
+use dmktp::useracct::UserAccount;
+use dmktp::amend::Amend;
+use dmktp::cancel::Cancel;
+use dmktp::fees::{ FeeSchedule, FeeTier };
+use dmktp::stoporder::StopOrder;
+use dmktp::rules::TradingRules;
+use dmktp::pricelimits::{ CircuitBreaker, PriceBand, ReferencePrice };
+use dmktp::instrument::{ Instrument, MarketMode, DEFAULT_INSTRUMENT };
+use dmktp::decimal::Decimal;
+use dmktp::matching::MatchingAlgorithm;
 use swth_cli_libv2::errors::CliError;
 use swth_cli_libv2::mkbatch::exec_wo_deps;
+use std::fs::File;
+use std::io::Write;
 
 //pub mod pubkeys;
 pub mod generators;
 pub mod getstate;
 pub mod utils;
+pub mod watch;
+
+use getstate::OrderQuery;
 
 fn run() -> Result<(), Error>  {
     let matches = App::new("Intkey CLI using swth_cli_lib")
@@ -52,6 +72,163 @@ fn run() -> Result<(), Error>  {
                             .help("Specify name of signing key. Defaults to your environment username. Enter as -k <name> for <name>.priv.")
                             .takes_value(true))
 
+                        .arg(Arg::with_name("account")
+                            .short("a")
+                            .long("account")
+                            .value_name("account")
+                            .help("Target account (address or public key) for admin commands.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("name")
+                            .long("name")
+                            .value_name("name")
+                            .help("Account holder's name, for new_user, or the instrument's description, for list_instrument.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("instrument")
+                            .short("i")
+                            .long("instrument")
+                            .value_name("instrument")
+                            .help("Instrument symbol for orders, cancels, order book queries and admin balance changes. Defaults to COMMODITY.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("order_type")
+                            .short("t")
+                            .long("type")
+                            .value_name("order_type")
+                            .help("Order type for buy/sell: limit, market, ioc or fok. Defaults to limit. Market orders have no price; give just the quantity, as in buy <quantity> -t market. A market buy spends no more cash than you have.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("expires")
+                            .long("expires")
+                            .value_name("block")
+                            .help("Last block number a buy/sell order is good for. Orders are good till cancelled by default.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("trigger")
+                            .long("trigger")
+                            .value_name("trigger")
+                            .help("Last trade price that sets off a stop_buy/stop_sell order. Use --type market for a stop (stop_buy <quantity> -t market), limit (the default) for a stop-limit.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("peak")
+                            .long("peak")
+                            .value_name("qty")
+                            .help("Makes a buy/sell order an iceberg order showing at most this much of its quantity on the book at a time. The rest is only left out of the book as dmktc shows it; it is stored in public state like any order.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("stp")
+                            .long("stp")
+                            .value_name("mode")
+                            .help("What a buy/sell order does on meeting one of your own resting orders: cancel-newest (the default), cancel-oldest, cancel-both or decrement.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("mode")
+                            .long("mode")
+                            .value_name("mode")
+                            .help("How a new listing is traded, for list_instrument: continuous (the default) or call.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("matching")
+                            .long("matching")
+                            .value_name("algorithm")
+                            .help("How a new listing shares incoming orders among those at the best price, for list_instrument: fifo (the default), pro-rata or top-order.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("price_places")
+                            .long("price-places")
+                            .value_name("places")
+                            .help("Decimal places of a new listing's prices, for list_instrument. Defaults to 0.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("qty_places")
+                            .long("qty-places")
+                            .value_name("places")
+                            .help("Decimal places of a new listing's quantities, for list_instrument. Defaults to 0.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("tick")
+                            .long("tick")
+                            .value_name("tick")
+                            .help("Tick size for set_rules: prices must be a multiple of it.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("lot")
+                            .long("lot")
+                            .value_name("lot")
+                            .help("Lot size for set_rules: quantities must be a multiple of it.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("min_qty")
+                            .long("min-qty")
+                            .value_name("qty")
+                            .help("Smallest order quantity, for set_rules.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("max_qty")
+                            .long("max-qty")
+                            .value_name("qty")
+                            .help("Largest order quantity, for set_rules. 0 removes the limit.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("max_notional")
+                            .long("max-notional")
+                            .value_name("amount")
+                            .help("Largest cash value of an order, price * quantity, for set_rules. 0 removes the limit.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("reference")
+                            .long("reference")
+                            .value_name("reference")
+                            .help("Price a set_band band is measured from: last (the last trade, the default) or close (the last trade as of the previous market step).")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("window")
+                            .long("window")
+                            .value_name("blocks")
+                            .help("Number of blocks, the current one included, over which set_breaker measures price moves. Defaults to 1; at most 1000.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("new_price")
+                            .long("new-price")
+                            .value_name("price")
+                            .help("New price for amend. Leave out to keep the order's price.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("tier")
+                            .long("tier")
+                            .value_name("account=maker:taker")
+                            .help("Fee rates in basis points for one account, for set_fees. Can be repeated.")
+                            .takes_value(true)
+                            .multiple(true)
+                            .number_of_values(1))
+
+                        .arg(Arg::with_name("filter")
+                            .long("filter")
+                            .value_name("key=value")
+                            .help("Only show events whose attribute matches, for watch. Can be repeated.")
+                            .takes_value(true)
+                            .multiple(true)
+                            .number_of_values(1))
+
+                        .arg(Arg::with_name("validator")
+                            .long("validator")
+                            .value_name("validator")
+                            .help("Validator component endpoint to receive events from, for watch. Defaults to tcp://localhost:4004.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("events_file")
+                            .long("events-file")
+                            .value_name("events_file")
+                            .help("Replay events recorded with --record instead of watching a validator.")
+                            .takes_value(true))
+
+                        .arg(Arg::with_name("record")
+                            .long("record")
+                            .value_name("record")
+                            .help("Append every event received by watch to this file.")
+                            .takes_value(true))
+
                         .arg(Arg::with_name("url")
                             .short("u")
                             .long("url")
@@ -64,9 +241,32 @@ fn run() -> Result<(), Error>  {
 
 
 
+    let instrument: String = String::from(matches.value_of("instrument").unwrap_or(DEFAULT_INSTRUMENT));
+    let order_type: OrderType = matches.value_of("order_type").unwrap_or("limit").parse()?;
+    let expires: Option<u64> = match matches.value_of("expires") {
+        Some(v) => Some(v.parse::<u64>()?),
+        None => None,
+    };
+    // Quantities are read at the places of the instrument they are in.
+    let peak = |_places: u32| -> Result<Option<u64>, Error> {
+        match matches.value_of("peak") {
+            Some(v) => Ok(Some(Decimal::parse(v, _places)?.units)),
+            None => Ok(None),
+        }
+    };
+    let stp: SelfTradePrevention = matches.value_of("stp").unwrap_or("cancel-newest").parse()?;
+    // Market orders have no price, so their one argument is the quantity.
+    let market = order_type == OrderType::Market;
+    let (price_arg, qty_arg) = match market {
+        true => (None, matches.value_of("price")),
+        false => (matches.value_of("price"), matches.value_of("quantity")),
+    };
+
     match matches.value_of("verb") {
         Some("get_orderbook") => {
-            getstate::init_get_state_orderbook(matches.value_of("url"))?;
+            // Without a usable key every iceberg order is shown as others see it.
+            let viewer = utils::addr_from_keyfile(matches.value_of("keyfile")).ok();
+            getstate::init_get_state_orderbook(matches.value_of("url"), &instrument, viewer.as_ref())?;
         },
         Some("get_balancebook") => {
             getstate::init_get_state_balancebook(matches.value_of("url"))?;
@@ -85,32 +285,153 @@ fn run() -> Result<(), Error>  {
             exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
         },
         Some("buy") => {
-            let price: u64 = match matches.value_of("price") {
-                Some(v) => v.parse::<u64>()?,
+            let precision = getstate::fetch_precision(matches.value_of("url"), &instrument)?;
+            let price: u64 = match price_arg {
+                Some(v) => Decimal::parse(v, precision.price)?.units,
+                None if market => 0,
                 None => return Err(Error::from(CliError::CustomError { contents: format!("Price cannot be left blank!")}))
             };
-            let qty: u64 = match matches.value_of("quantity") {
-                Some(v) => v.parse::<u64>()?,
+            let qty: u64 = match qty_arg {
+                Some(v) => Decimal::parse(v, precision.qty)?.units,
                 None => return Err(Error::from(CliError::CustomError { contents: format!("Quantity cannot be left blank!")}))
             };
-            let addr = utils::addr_from_keyfile(matches.value_of("keyfile"))?;
-
-            let clireqs = vec![Wrapper::Bo(BuyOrder::from_vals(addr, price, qty, 0)).to_cli_request().unwrap()];
+            // Sent with our address, which the processor checks against the signing key, so
+            // that the accounts and records it touches can be worked out here.
+            let mut order = BuyOrder::from_vals(utils::addr_from_keyfile(matches.value_of("keyfile"))?, price, qty, 0);
+            order.set_instrument(instrument);
+            order.set_order_type(order_type);
+            order.set_expires(expires);
+            order.set_peak(peak(precision.qty)?);
+            order.set_stp(stp);
+            getstate::fetch_rules(matches.value_of("url"), order.get_instrument())?.check_order(&order, &precision)?;
+            let touches = order_touches(matches.value_of("url"), &order)?;
+            let clireqs = vec![Wrapper::Bo(order).to_cli_request_touching(&touches).unwrap()];
             exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
 
         }
         Some("sell") => {
-            let price: u64 = match matches.value_of("price") {
-                Some(v) => v.parse::<u64>()?,
+            let precision = getstate::fetch_precision(matches.value_of("url"), &instrument)?;
+            let price: u64 = match price_arg {
+                Some(v) => Decimal::parse(v, precision.price)?.units,
+                None if market => 0,
                 None => return Err(Error::from(CliError::CustomError { contents: format!("Price cannot be left blank!")}))
             };
-            let qty: u64 = match matches.value_of("quantity") {
-                Some(v) => v.parse::<u64>()?,
+            let qty: u64 = match qty_arg {
+                Some(v) => Decimal::parse(v, precision.qty)?.units,
                 None => return Err(Error::from(CliError::CustomError { contents: format!("Quantity cannot be left blank!")}))
             };
-            let addr = utils::addr_from_keyfile(matches.value_of("keyfile"))?;
+            let mut order = SellOrder::from_vals(utils::addr_from_keyfile(matches.value_of("keyfile"))?, price, qty, 0);
+            order.set_instrument(instrument);
+            order.set_order_type(order_type);
+            order.set_expires(expires);
+            order.set_peak(peak(precision.qty)?);
+            order.set_stp(stp);
+            getstate::fetch_rules(matches.value_of("url"), order.get_instrument())?.check_order(&order, &precision)?;
+            let touches = order_touches(matches.value_of("url"), &order)?;
+            let clireqs = vec![Wrapper::So(order).to_cli_request_touching(&touches).unwrap()];
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("stop_buy") | Some("stop_sell") => {
+            let precision = getstate::fetch_precision(matches.value_of("url"), &instrument)?;
+            let price: u64 = match price_arg {
+                Some(v) => Decimal::parse(v, precision.price)?.units,
+                None if market => 0,
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Price cannot be left blank!")}))
+            };
+            let qty: u64 = match qty_arg {
+                Some(v) => Decimal::parse(v, precision.qty)?.units,
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Quantity cannot be left blank!")}))
+            };
+            let trigger: u64 = match matches.value_of("trigger") {
+                Some(v) => Decimal::parse(v, precision.price)?.units,
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Trigger price cannot be left blank! Pass it with --trigger <price>")}))
+            };
+            let rules = getstate::fetch_rules(matches.value_of("url"), &instrument)?;
+            rules.check_price(trigger)?;
+            let stop = match matches.value_of("verb") {
+                Some("stop_buy") => {
+                    let mut order = BuyOrder::from_vals(Address::empty(), price, qty, 0);
+                    order.set_instrument(instrument);
+                    order.set_order_type(order_type);
+                    order.set_expires(expires);
+                    order.set_peak(peak(precision.qty)?);
+                    order.set_stp(stp);
+                    rules.check_order(&order, &precision)?;
+                    StopOrder::Buy(trigger, order)
+                }
+                _ => {
+                    let mut order = SellOrder::from_vals(Address::empty(), price, qty, 0);
+                    order.set_instrument(instrument);
+                    order.set_order_type(order_type);
+                    order.set_expires(expires);
+                    order.set_peak(peak(precision.qty)?);
+                    order.set_stp(stp);
+                    rules.check_order(&order, &precision)?;
+                    StopOrder::Sell(trigger, order)
+                }
+            };
+            let clireqs = vec![Wrapper::St(stop).to_cli_request().unwrap()];
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("cancel") => {
+            // dmktc cancel <order id> | cancel buy <nonce> [-i instrument] | cancel sell <nonce> [-i instrument] | cancel all
+            let nonce = || -> Result<u64, Error> {
+                match matches.value_of("quantity") {
+                    Some(v) => Ok(v.parse::<u64>()?),
+                    None => Err(Error::from(CliError::CustomError { contents: format!("Order nonce cannot be left blank!")}))
+                }
+            };
+            let cancel: Cancel = match matches.value_of("price") {
+                Some("buy") => Cancel::Buy(instrument, nonce()?),
+                Some("sell") => Cancel::Sell(instrument, nonce()?),
+                Some("all") => Cancel::All,
+                Some(id) => Cancel::Order(String::from(id)),
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Usage: cancel <order id> | cancel buy <nonce> | cancel sell <nonce> | cancel all")}))
+            };
 
-            let clireqs = vec![Wrapper::So(SellOrder::from_vals(addr, price, qty, 0)).to_cli_request().unwrap()];
+            let signer = utils::addr_from_keyfile(matches.value_of("keyfile"))?;
+            let record = match cancel {
+                Cancel::Order(ref id) => getstate::fetch_orderstatus(matches.value_of("url"), Some(&signer))?.get(id).cloned(),
+                _ => None,
+            };
+            let touches = Touches::cancel(&cancel, record.as_ref(), &signer);
+            let clireqs = vec![Wrapper::Cn(cancel).to_cli_request_touching(&touches).unwrap()];
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("amend") => {
+            // dmktc amend <order id> [new open quantity] [--new-price <price>]
+            let id = match matches.value_of("price") {
+                Some(v) => String::from(v),
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Usage: amend <order id> [quantity] [--new-price <price>]")}))
+            };
+            // The order's status record says which book it is on, and so how its price and
+            // quantity are written; -i is only used for an order with no record.
+            let signer = utils::addr_from_keyfile(matches.value_of("keyfile"))?;
+            let record = getstate::fetch_orderstatus(matches.value_of("url"), Some(&signer))?.get(&id).cloned();
+            let instrument = match record {
+                Some(ref v) => v.instrument.clone(),
+                None => instrument,
+            };
+            let precision = getstate::fetch_precision(matches.value_of("url"), &instrument)?;
+            let qty: Option<u64> = match matches.value_of("quantity") {
+                Some(v) => Some(Decimal::parse(v, precision.qty)?.units),
+                None => None
+            };
+            let price: Option<u64> = match matches.value_of("new_price") {
+                Some(v) => Some(Decimal::parse(v, precision.price)?.units),
+                None => None
+            };
+            if qty.is_none() && price.is_none() {
+                return Err(Error::from(CliError::CustomError { contents: format!("Nothing to amend; give a new quantity and/or --new-price")}))
+            }
+
+            let ob = match record {
+                Some(ref v) => getstate::fetch_orderbook(matches.value_of("url"), &v.instrument)?,
+                None => None,
+            };
+            let amend = Amend::from_vals(id, price, qty);
+            let touches = Touches::amend(&ob.unwrap_or_else(|| OrderBook::new()), record.as_ref(), &amend, &signer);
+            let clireqs = vec![Wrapper::Md(amend).to_cli_request_touching(&touches).unwrap()];
             exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
         }
         Some("mk_auction") => {
@@ -122,12 +443,13 @@ fn run() -> Result<(), Error>  {
                 Some(v) => v.parse::<u64>()?,
                 None => return Err(Error::from(CliError::CustomError { contents: format!("Auction serial no. cannot be left blank!")}))
             };
+            let cash_places = getstate::fetch_instruments(matches.value_of("url"))?.cash_places;
             let price: u64 = match matches.value_of("quantity") {
-                Some(v) => v.parse::<u64>()?,
+                Some(v) => Decimal::parse(v, cash_places)?.units,
                 None => return Err(Error::from(CliError::CustomError { contents: format!("Bid amount cannot be left blank!")}))
             };
 
-            let bid: Wrapper = utils::mk_bid(matches.value_of("keyfile"), serial, price)?;
+            let bid: Wrapper = utils::mk_bid(serial, price);
             let mut clireqs: Vec<CliRequest> = vec![bid].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
 
             exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
@@ -183,12 +505,13 @@ fn run() -> Result<(), Error>  {
             exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
         }
         Some("mk_sealed_auction") => {
+            let precision = getstate::fetch_precision(matches.value_of("url"), &instrument)?;
             let auction_amt = match matches.value_of("price") {
-                Some(v) => v.parse::<u64>()?,
+                Some(v) => Decimal::parse(v, precision.qty)?.units,
                 None => 100_000_000
             };
 
-            let mut clireqs: Vec<CliRequest> = vec![generators::gen_sealed_auction_ng(auction_amt)].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            let mut clireqs: Vec<CliRequest> = vec![generators::gen_sealed_auction_ng(auction_amt, instrument)].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
             exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
         }
 
@@ -273,7 +596,281 @@ fn run() -> Result<(), Error>  {
             let mut clireqs: Vec<CliRequest> = vec![Wrapper::Cs].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
             exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
         }
+        Some("get_status") => {
+            getstate::init_get_state_marketstatus(matches.value_of("url"))?;
+        }
+        Some("admin_credit") | Some("admin_debit") => {
+            let precision = getstate::fetch_precision(matches.value_of("url"), &instrument)?;
+            let cash: u64 = match matches.value_of("price") {
+                Some(v) => Decimal::parse(v, precision.cash)?.units,
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Cash amount cannot be left blank!")}))
+            };
+            let assets: u64 = match matches.value_of("quantity") {
+                Some(v) => Decimal::parse(v, precision.qty)?.units,
+                None => 0
+            };
+            let addr = utils::addr_from_arg(matches.value_of("account"))?;
+
+            let change = BalanceChange::from_vals(addr, cash, assets, instrument);
+            let msg = match matches.value_of("verb") {
+                Some("admin_credit") => AdminMsg::AdminCredit(change),
+                _ => AdminMsg::AdminDebit(change),
+            };
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(msg)].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("new_user") => {
+            // Seeded assets are in the default instrument.
+            let precision = getstate::fetch_precision(matches.value_of("url"), DEFAULT_INSTRUMENT)?;
+            let cash: u64 = match matches.value_of("price") {
+                Some(v) => Decimal::parse(v, precision.cash)?.units,
+                None => 0
+            };
+            let assets: u64 = match matches.value_of("quantity") {
+                Some(v) => Decimal::parse(v, precision.qty)?.units,
+                None => 0
+            };
+            let addr = utils::addr_from_arg(matches.value_of("account"))?;
+            let name: String = match matches.value_of("name") {
+                Some(v) => String::from(v),
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Account name cannot be left blank! Pass it with --name <name>")}))
+            };
 
+            let acct = UserAccount::new_seeded(name, addr.to_string(), cash, assets)?;
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::NewUser(acct))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("admin_end_auction") => {
+            let serial: u64 = match matches.value_of("price") {
+                Some(v) => v.parse::<u64>()?,
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Auction serial no. cannot be left blank!")}))
+            };
+
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::EndAuction(serial))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("admin_clear_orderbook") => {
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::ClearOrderBook)].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("list_instrument") => {
+            let symbol: String = match matches.value_of("price") {
+                Some(v) => String::from(v),
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Instrument symbol cannot be left blank!")}))
+            };
+            let description: String = String::from(matches.value_of("name").unwrap_or(""));
+
+            let mut listing = Instrument::from_vals(symbol, description)?;
+            listing.mode = matches.value_of("mode").unwrap_or("continuous").parse()?;
+            listing.matching = matches.value_of("matching").unwrap_or("fifo").parse()?;
+            listing.price_places = matches.value_of("price_places").unwrap_or("0").parse::<u32>()?;
+            listing.qty_places = matches.value_of("qty_places").unwrap_or("0").parse::<u32>()?;
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::ListInstrument(listing))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("set_mode") => {
+            // dmktc set_mode <continuous|call> -i <instrument>
+            let mode: MarketMode = match matches.value_of("price") {
+                Some(v) => v.parse()?,
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Usage: set_mode <continuous|call> -i <instrument>")}))
+            };
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::SetMarketMode(instrument, mode))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("set_cash_places") => {
+            // dmktc set_cash_places <places>; balances aren't rescaled, so set it before crediting cash
+            let places: u32 = match matches.value_of("price") {
+                Some(v) => v.parse::<u32>()?,
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Usage: set_cash_places <places>")}))
+            };
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::SetCashPlaces(places))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("set_matching") => {
+            // dmktc set_matching <fifo|pro-rata|top-order> -i <instrument>
+            let matching: MatchingAlgorithm = match matches.value_of("price") {
+                Some(v) => v.parse()?,
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Usage: set_matching <fifo|pro-rata|top-order> -i <instrument>")}))
+            };
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::SetMatching(instrument, matching))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("call_auction") => {
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::CallAuction(instrument))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("get_instruments") => {
+            getstate::init_get_state_instruments(matches.value_of("url"))?;
+        }
+        Some("get_rules") => {
+            println!("{}: {}", instrument, getstate::fetch_rules(matches.value_of("url"), &instrument)?);
+        }
+        Some("set_band") => {
+            // dmktc set_band <width bps> -i <instrument> [--reference last|close]; a width of 0 removes the band
+            let width_bps: u64 = match matches.value_of("price") {
+                Some(v) => v.parse::<u64>()?,
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Usage: set_band <width bps> -i <instrument> [--reference last|close]")}))
+            };
+            let reference: ReferencePrice = matches.value_of("reference").unwrap_or("last").parse()?;
+            let band = match width_bps {
+                0 => None,
+                _ => Some(PriceBand { reference: reference, width_bps: width_bps }),
+            };
+
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::SetPriceBand(instrument, band))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("set_breaker") => {
+            // dmktc set_breaker <move bps> <halt blocks> -i <instrument> [--window blocks]; a move of 0 removes the breaker
+            let usage = || Error::from(CliError::CustomError { contents: format!("Usage: set_breaker <move bps> <halt blocks> -i <instrument> [--window blocks]")});
+            let move_bps: u64 = match matches.value_of("price") {
+                Some(v) => v.parse::<u64>()?,
+                None => return Err(usage())
+            };
+            let breaker = match move_bps {
+                0 => None,
+                _ => {
+                    let halt_blocks: u64 = match matches.value_of("quantity") {
+                        Some(v) => v.parse::<u64>()?,
+                        None => return Err(usage())
+                    };
+                    let window: u64 = matches.value_of("window").unwrap_or("1").parse::<u64>()?;
+                    let breaker = CircuitBreaker { move_bps: move_bps, window: window, halt_blocks: halt_blocks };
+                    breaker.check()?;
+                    Some(breaker)
+                }
+            };
+
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::SetCircuitBreaker(instrument, breaker))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("set_rules") => {
+            // dmktc set_rules -i <instrument> [--tick n] [--lot n] [--min-qty n] [--max-qty n] [--max-notional n]
+            // Anything left out keeps its current value. Ticks are written as prices, lots and
+            // limits as quantities and the notional limit as cash.
+            let arg = |x: &str, places: u32| -> Result<Option<u64>, Error> {
+                match matches.value_of(x) {
+                    Some(v) => Ok(Some(Decimal::parse(v, places)?.units)),
+                    None => Ok(None),
+                }
+            };
+            let precision = getstate::fetch_precision(matches.value_of("url"), &instrument)?;
+            let mut rules: TradingRules = getstate::fetch_rules(matches.value_of("url"), &instrument)?;
+            rules.tick_size = arg("tick", precision.price)?.unwrap_or(rules.tick_size);
+            rules.lot_size = arg("lot", precision.qty)?.unwrap_or(rules.lot_size);
+            rules.min_qty = arg("min_qty", precision.qty)?.unwrap_or(rules.min_qty);
+            if let Some(v) = arg("max_qty", precision.qty)? {
+                rules.max_qty = if v == 0 { None } else { Some(v) };
+            }
+            if let Some(v) = arg("max_notional", precision.cash)? {
+                rules.max_notional = if v == 0 { None } else { Some(v) };
+            }
+            rules.check()?;
+
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::SetTradingRules(instrument, rules))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("step") => {
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::Step)].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("get_order") => {
+            let id: String = match matches.value_of("price") {
+                Some(v) => String::from(v),
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Order ID cannot be left blank!")}))
+            };
+            getstate::init_get_state_orderstatus(matches.value_of("url"), OrderQuery::Id(id))?;
+        }
+        Some("my_orders") => {
+            let addr = utils::addr_from_keyfile(matches.value_of("keyfile"))?;
+            getstate::init_get_state_orderstatus(matches.value_of("url"), OrderQuery::Owner(addr))?;
+        }
+        Some("get_orders") => {
+            getstate::init_get_state_orderstatus(matches.value_of("url"), OrderQuery::All)?;
+        }
+        Some("set_fees") => {
+            // dmktc set_fees <maker bps> <taker bps> -a <treasury> [--tier account=maker:taker ...]
+            let bps = |x: Option<&str>| -> Result<u64, Error> {
+                match x {
+                    Some(v) => Ok(v.parse::<u64>()?),
+                    None => Err(Error::from(CliError::CustomError { contents: format!("Usage: set_fees <maker bps> <taker bps> -a <treasury account>")}))
+                }
+            };
+            let mut fees = FeeSchedule::new();
+            fees.maker_bps = bps(matches.value_of("price"))?;
+            fees.taker_bps = bps(matches.value_of("quantity"))?;
+            fees.treasury = Some(utils::addr_from_arg(matches.value_of("account"))?);
+            for tier in matches.values_of("tier").into_iter().flat_map(|x| x) {
+                let mut split = tier.splitn(2, '=');
+                let (account, rates) = match (split.next(), split.next()) {
+                    (Some(a), Some(r)) => (a, r),
+                    _ => return Err(Error::from(CliError::CustomError { contents: format!("Tiers must be given as account=maker:taker. Received: {}", tier)}))
+                };
+                let mut rates = rates.splitn(2, ':');
+                let rate = FeeTier { maker_bps: bps(rates.next())?, taker_bps: bps(rates.next())? };
+                fees.tiers.insert(utils::addr_from_arg(Some(account))?.to_string(), rate);
+            }
+
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Am(AdminMsg::SetFees(fees))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("get_fees") => {
+            println!("{}", getstate::fetch_fees(matches.value_of("url"))?);
+            println!("Fees collected: {}", getstate::fetch_tradeledger(matches.value_of("url"))?.fees_collected());
+        }
+        Some("get_trades") => {
+            let ledger = getstate::fetch_tradeledger(matches.value_of("url"))?;
+            match matches.value_of("account") {
+                Some(_) => {
+                    let addr = utils::addr_from_arg(matches.value_of("account"))?;
+                    ledger.for_account(&addr).iter().for_each(|x| println!("{}", x));
+                }
+                None => println!("trade ledger: {}", ledger),
+            }
+        }
+        Some("export_trades") => {
+            let path: &str = match matches.value_of("price") {
+                Some(v) => v,
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Output file cannot be left blank!")}))
+            };
+            let ledger = getstate::fetch_tradeledger(matches.value_of("url"))?;
+            let mut file = File::create(path)?;
+            file.write_all(ledger.to_csv().as_bytes())?;
+            println!("Wrote {} trades to {}", ledger.len(), path);
+        }
+        Some("watch") => {
+            // dmktc watch [event type] [--filter key=value ...]
+            let event_types = watch::event_types(matches.value_of("price"));
+            let filters: Vec<(String, String)> = match matches.values_of("filter") {
+                Some(v) => v.map(|x| watch::parse_filter(x)).collect::<Result<Vec<_>, Error>>()?,
+                None => Vec::new(),
+            };
+            match matches.value_of("events_file") {
+                Some(path) => watch::watch_file(path, event_types, filters)?,
+                None => watch::watch_validator(matches.value_of("validator").unwrap_or(watch::VALIDATOR), event_types, filters, matches.value_of("record"))?,
+            }
+        }
+        Some("get_permissions") => {
+            getstate::init_get_state_permissions(matches.value_of("url"))?;
+        }
+        Some("add_admin") => {
+            let pubkey: String = match matches.value_of("price") {
+                Some(v) => String::from(v),
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Public key of the new admin cannot be left blank!")}))
+            };
+
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Pm(PermissionMsg::AddAdmin(pubkey))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
+        Some("remove_admin") => {
+            let pubkey: String = match matches.value_of("price") {
+                Some(v) => String::from(v),
+                None => return Err(Error::from(CliError::CustomError { contents: format!("Public key of the admin to remove cannot be left blank!")}))
+            };
+
+            let mut clireqs: Vec<CliRequest> = vec![Wrapper::Pm(PermissionMsg::RemoveAdmin(pubkey))].into_iter().map(|x| x.to_cli_request().unwrap()).collect();
+            exec_wo_deps(clireqs, matches.value_of("keyfile"), matches.value_of("url"))?;
+        }
 
         _ => return Err(Error::from(CliError::ParsedNoneError))
 
@@ -284,6 +881,18 @@ fn run() -> Result<(), Error>  {
         Ok(())
 }
 
+// What _order touches matched against its book as it stands, from the block the next
+// transaction lands in. An instrument with no book yet has nothing to match against.
+fn order_touches<T: OrderT>(_url: Option<&str>, _order: &T) -> Result<Touches, Error> {
+    let ob = match getstate::fetch_orderbook(_url, _order.get_instrument())? {
+        Some(v) => v,
+        None => OrderBook::for_instrument(_order.get_instrument().clone()),
+    };
+    let fees = getstate::fetch_fees(_url)?;
+    let block = getstate::fetch_block_num(_url)?;
+    Ok(Touches::order(&ob, _order, block, &fees))
+}
+
 
 fn main() {
     if let Err(e) = run() {
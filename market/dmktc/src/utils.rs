@@ -45,7 +45,17 @@ pub fn addr_from_keyfile(x: Option<&str>) -> Result<Address, Error> {
 
 }
 
-pub fn mk_bid(_keyfile: Option<&str>, _serial: u64, _amt: u64) -> Result<Wrapper, Error> {
-    let address = addr_from_keyfile(_keyfile)?;
-    Ok(Wrapper::Ab(Bid::from_vals(address, _serial, _amt)))
-}
\ No newline at end of file
+// Bids are bound to the signer by the processor, so the address is left empty.
+pub fn mk_bid(_serial: u64, _amt: u64) -> Wrapper {
+    Wrapper::Ab(Bid::from_vals(Address::empty(), _serial, _amt))
+}
+
+// Admin commands take the target account either as a 40 character address or as the
+// participant's full public key.
+pub fn addr_from_arg(x: Option<&str>) -> Result<Address, Error> {
+    match x {
+        Some(v) if v.len() == 66 => Ok(Address::from_pubkey_string(&String::from(v))),
+        Some(v) => Ok(Address::from_string(String::from(v))?),
+        None => Err(Error::from(CliError::CustomError { contents: format!("Target account cannot be left blank! Pass it with -a <address or public key>")}))
+    }
+}
@@ -0,0 +1,192 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+
+use failure::Error;
+use protobuf;
+use protobuf::{Message as ProtobufMessage, RepeatedField};
+use rand::distributions::Alphanumeric;
+use rand::{self, Rng};
+use serde_json;
+
+use sawtooth_sdk::messages::client_event::{
+    ClientEventsSubscribeRequest, ClientEventsSubscribeResponse,
+    ClientEventsSubscribeResponse_Status,
+};
+use sawtooth_sdk::messages::events::{
+    Event, EventFilter, EventFilter_FilterType, EventList, EventSubscription,
+};
+use sawtooth_sdk::messages::validator::Message_MessageType;
+use sawtooth_sdk::messaging::stream::{MessageConnection, MessageSender};
+use sawtooth_sdk::messaging::zmq_stream::ZmqMessageConnection;
+
+use dmktp::events::{MarketEvent, ALL_EVENT_TYPES};
+use swth_cli_libv2::errors::CliError;
+
+// Events are delivered over the validator's component endpoint, not the REST API.
+pub const VALIDATOR: &'static str = "tcp://localhost:4004";
+
+// --filter arguments are given as key=value, e.g. --filter buyer=<address>
+pub fn parse_filter(_arg: &str) -> Result<(String, String), Error> {
+    let mut split = _arg.splitn(2, '=');
+    match (split.next(), split.next()) {
+        (Some(k), Some(v)) if !k.is_empty() => Ok((String::from(k), String::from(v))),
+        _ => Err(Error::from(CliError::CustomError {
+            contents: format!("Filters must be given as key=value. Received: {}", _arg),
+        })),
+    }
+}
+
+pub fn event_types(_arg: Option<&str>) -> Vec<String> {
+    match _arg {
+        Some(v) => vec![String::from(v)],
+        None => ALL_EVENT_TYPES.iter().map(|x| String::from(*x)).collect(),
+    }
+}
+
+fn from_proto(_event: &Event) -> MarketEvent {
+    MarketEvent {
+        event_type: String::from(_event.get_event_type()),
+        attributes: _event
+            .get_attributes()
+            .iter()
+            .map(|x| (String::from(x.get_key()), String::from(x.get_value())))
+            .collect(),
+        data: _event.get_data().to_vec(),
+    }
+}
+
+// Prints the event and, when recording, appends it to the record file as one JSON line.
+fn show(_event: &MarketEvent, _record: &mut Option<File>) -> Result<(), Error> {
+    println!("{}", _event);
+    if let Some(file) = _record.as_mut() {
+        writeln!(file, "{}", serde_json::to_string(_event)?)?;
+    }
+    Ok(())
+}
+
+fn open_record(_path: Option<&str>) -> Result<Option<File>, Error> {
+    match _path {
+        Some(v) => Ok(Some(OpenOptions::new().create(true).append(true).open(v)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn watch_validator(
+    _url: &str,
+    _event_types: Vec<String>,
+    _filters: Vec<(String, String)>,
+    _record: Option<&str>,
+) -> Result<(), Error> {
+    let mut record = open_record(_record)?;
+
+    let subscriptions: Vec<EventSubscription> = _event_types
+        .iter()
+        .map(|t| {
+            let filters: Vec<EventFilter> = _filters
+                .iter()
+                .map(|(k, v)| {
+                    let mut filter = EventFilter::new();
+                    filter.set_key(k.clone());
+                    filter.set_match_string(v.clone());
+                    filter.set_filter_type(EventFilter_FilterType::SIMPLE_ALL);
+                    filter
+                })
+                .collect();
+
+            let mut subscription = EventSubscription::new();
+            subscription.set_event_type(t.clone());
+            subscription.set_filters(RepeatedField::from_vec(filters));
+            subscription
+        })
+        .collect();
+
+    let mut request = ClientEventsSubscribeRequest::new();
+    request.set_subscriptions(RepeatedField::from_vec(subscriptions));
+
+    let connection = ZmqMessageConnection::new(_url);
+    let (sender, receiver) = connection.create();
+    let correlation_id: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .collect();
+
+    let mut future = match sender.send(
+        Message_MessageType::CLIENT_EVENTS_SUBSCRIBE_REQUEST,
+        &correlation_id,
+        &request.write_to_bytes()?,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(Error::from(CliError::CustomError {
+                contents: format!("could not send event subscription to {}: {:?}", _url, e),
+            }))
+        }
+    };
+    let response_msg = match future.get() {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(Error::from(CliError::CustomError {
+                contents: format!("no response to event subscription from {}: {:?}", _url, e),
+            }))
+        }
+    };
+    let response: ClientEventsSubscribeResponse =
+        protobuf::parse_from_bytes(response_msg.get_content())?;
+    if response.get_status() != ClientEventsSubscribeResponse_Status::OK {
+        return Err(Error::from(CliError::CustomError {
+            contents: format!(
+                "event subscription rejected: {:?} {}",
+                response.get_status(),
+                response.get_response_message()
+            ),
+        }));
+    }
+
+    println!("Watching {:?} on {}", _event_types, _url);
+    loop {
+        let msg = match receiver.recv() {
+            Ok(Ok(v)) => v,
+            Ok(Err(e)) => {
+                return Err(Error::from(CliError::CustomError {
+                    contents: format!("error receiving events: {:?}", e),
+                }))
+            }
+            Err(e) => {
+                return Err(Error::from(CliError::CustomError {
+                    contents: format!("connection to validator closed: {:?}", e),
+                }))
+            }
+        };
+        if msg.get_message_type() != Message_MessageType::CLIENT_EVENTS {
+            continue;
+        }
+
+        let event_list: EventList = protobuf::parse_from_bytes(msg.get_content())?;
+        event_list
+            .get_events()
+            .iter()
+            .map(|x| from_proto(x))
+            .try_for_each(|x| show(&x, &mut record))?;
+    }
+}
+
+// Replays a file written by `watch --record`, applying the same type and attribute filters.
+pub fn watch_file(
+    _path: &str,
+    _event_types: Vec<String>,
+    _filters: Vec<(String, String)>,
+) -> Result<(), Error> {
+    let reader = BufReader::new(File::open(_path)?);
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event: MarketEvent = serde_json::from_str(&line)?;
+        if _event_types.contains(&event.event_type) && event.matches(&_filters) {
+            show(&event, &mut None)?;
+        }
+    }
+
+    Ok(())
+}
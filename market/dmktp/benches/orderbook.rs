@@ -0,0 +1,130 @@
+// Nightly only, and built only with the bench feature; see Cargo.toml.
+#![feature(test)]
+
+extern crate dmktp;
+extern crate rand;
+extern crate serde_cbor;
+extern crate test;
+
+use dmktp::address::Address;
+use dmktp::amend::Amend;
+use dmktp::balancebook::BalanceBook;
+use dmktp::order::{BuyOrder, OrderT, SellOrder};
+use dmktp::orderbook::{fill_buy, OrderBook};
+use dmktp::useracct::UserAccount;
+use rand::prng::XorShiftRng;
+use rand::{Rng, SeedableRng};
+use test::Bencher;
+
+// Books of 100k resting orders: 50k buys priced 1..1000 under 50k sells priced 1001..2000,
+// spread over ten owners with plenty to cover them.
+const BOOK_SIZE: u64 = 100_000;
+
+fn owner(_n: u64) -> Address {
+    Address::from_string(format!("{:040}", _n)).unwrap()
+}
+
+fn mk_book() -> (OrderBook, BalanceBook) {
+    let mut bb = BalanceBook::new();
+    for n in 0..10 {
+        bb.insert_seeded(
+            UserAccount::new_seeded(
+                format!("owner{}", n),
+                owner(n).to_string(),
+                u64::max_value() / 100,
+                u64::max_value() / 100,
+            ).unwrap(),
+        ).unwrap();
+    }
+    let mut ob = OrderBook::new();
+    let mut rng = XorShiftRng::from_seed([7; 16]);
+    for n in 0..BOOK_SIZE {
+        let qty = rng.gen_range(1, 100) * 1_000;
+        match n % 2 {
+            0 => {
+                let mut order = BuyOrder::from_vals(owner(n % 10), rng.gen_range(1, 1001), qty, 0);
+                order.set_id(format!("o{}", n));
+                ob.insert_buy_order(&mut bb, order, true).unwrap();
+            }
+            _ => {
+                let mut order =
+                    SellOrder::from_vals(owner(n % 10), rng.gen_range(1001, 2001), qty, 0);
+                order.set_id(format!("o{}", n));
+                ob.insert_sell_order(&mut bb, order, true).unwrap();
+            }
+        }
+    }
+    (ob, bb)
+}
+
+#[bench]
+fn build_100k(b: &mut Bencher) {
+    b.iter(|| mk_book());
+}
+
+// A small marketable buy from an owner with no sells, taking from the best level.
+#[bench]
+fn match_against_100k(b: &mut Bencher) {
+    let (mut ob, mut bb) = mk_book();
+    let buyer = Address::from_string(format!("{:040}", 99)).unwrap();
+    bb.insert_seeded(
+        UserAccount::new_seeded(
+            String::from("buyer"),
+            buyer.to_string(),
+            u64::max_value() / 100,
+            0,
+        ).unwrap(),
+    ).unwrap();
+    b.iter(|| {
+        fill_buy(
+            &mut ob,
+            &mut bb,
+            BuyOrder::from_vals(buyer.clone(), 2_000, 1, 0),
+        ).unwrap()
+    });
+}
+
+// Rests an order deep in the book and cancels it again by ID.
+#[bench]
+fn cancel_by_id_in_100k(b: &mut Bencher) {
+    let (mut ob, mut bb) = mk_book();
+    let id = String::from("bench");
+    b.iter(|| {
+        let mut order = BuyOrder::from_vals(owner(0), 500, 10, 0);
+        order.set_id(id.clone());
+        ob.insert_buy_order(&mut bb, order, true).unwrap();
+        ob.cancel_by_id(&mut bb, &id, &owner(0)).unwrap()
+    });
+}
+
+// Moves a resting order between two prices, losing priority each time.
+#[bench]
+fn amend_in_100k(b: &mut Bencher) {
+    let (mut ob, mut bb) = mk_book();
+    let id = String::from("o0");
+    let mut price = 2;
+    b.iter(|| {
+        price = 3 - price;
+        let amend = Amend::from_vals(id.clone(), Some(price), None);
+        ob.amend_order(&mut bb, &amend, &owner(0)).unwrap()
+    });
+}
+
+#[bench]
+fn depth_of_100k(b: &mut Bencher) {
+    let (ob, _) = mk_book();
+    b.iter(|| (ob.buy_orders.depth(), ob.sell_orders.depth()));
+}
+
+#[bench]
+fn serialize_100k(b: &mut Bencher) {
+    let (ob, _) = mk_book();
+    b.iter(|| serde_cbor::to_vec(&ob).unwrap());
+}
+
+#[bench]
+fn deserialize_100k(b: &mut Bencher) {
+    let (ob, _) = mk_book();
+    let bytes = serde_cbor::to_vec(&ob).unwrap();
+    b.iter(|| serde_cbor::from_slice::<OrderBook>(&bytes).unwrap());
+}
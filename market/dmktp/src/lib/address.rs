@@ -34,6 +34,17 @@ impl Address {
         }
     }
 
+    // Placeholder used by clients; the processor fills it in from the transaction signer.
+    pub fn empty() -> Self {
+        Address {
+            contents: String::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty()
+    }
+
     pub fn from_pubkey_string(_input: &String) -> Self {
         let mut a = String::new();
         a.push_str(&_input[26..]);
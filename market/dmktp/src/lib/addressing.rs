@@ -1,15 +1,131 @@
+use address::Address;
+use crypto::digest::Digest;
+use crypto::sha2::Sha512;
+use instrument::DEFAULT_INSTRUMENT;
+use order::Side;
+
 pub const FAMILY_NAME: &'static str = "market";
 pub const FAMILY_VERSION: &'static str = "1.0";
 pub const FAMILY_PREFIX: &'static str = "6d2ca0";
 pub const ORDERBOOK: &'static str =
     "6d2ca0b2545e87511dbd9d616f2383cd94663a0123a7da69c297f0c2a5cead706c547a";
+// Every instrument's order book lives under this prefix, so a transaction that may touch
+// any book can declare the prefix instead of each address.
+pub const ORDERBOOK_PREFIX: &'static str = "6d2ca0b2";
+pub const INSTRUMENTS: &'static str =
+    "6d2ca0340cddbc572977185595835748bd5834a348e59410c434dfe8d89f1ff1c10615";
 pub const BALANCEBOOK: &'static str =
     "6d2ca0221df1aa49a6e67aef0aa55ecd66b108cc7752e1e7043d2d05bcc8faa544e557";
 pub const AUCTIONLIST: &'static str =
     "6d2ca0397e9c4300bb7adaf1650ecb87cd2fa14fff8f160cddc80c2f96954da6d4fa9e";
 pub const SEALEDAUCTIONLIST: &'static str =
     "6d2ca0397e9c4300bb7adaf1650ecb87cd2fa14fff8f160cddc80c2f96954da6d4fa9a";
+pub const ORDERSTATUS: &'static str =
+    "6d2ca09f0e265f41fabb731a6bcc20e6b868abebd13759ab20a5c60fd20503c60ab4c1";
+pub const TRADELEDGER: &'static str =
+    "6d2ca01cf9730ae1f2a9c009f924f680d12eecd2984f257770196a2a469764bea1fcfb";
+pub const MARKETSTATUS: &'static str =
+    "6d2ca0c6cb75119837152595bdea023897e5d0e7642d5748f6789d67307a95b20634f3";
+pub const FEESCHEDULE: &'static str =
+    "6d2ca014e532987d76c65fe8a713ce7739e835f4b73db7a4319ff114e3bef5cff03e2c";
+pub const PERMISSIONS: &'static str =
+    "6d2ca05493d6f1606745b1d22d8fbb7b8abf9aff39a7e7ce41153b7f1b759b2172c7f3";
+// Each account is stored at its own address under ACCOUNT_PREFIX. BALANCEBOOK still holds
+// the accounts of a market that predates that; they are read from there until first
+// written back.
+pub const ACCOUNT_PREFIX: &'static str = "6d2ca064";
+// Addresses of every account created since, for the routes that act on all of them.
+pub const ACCOUNTINDEX: &'static str =
+    "6d2ca0f6748212a987b757fb229e80c6826e61985e2f8c9ef7d794efee74e39027635f";
+// Open and sealed-bid auctions each have an address under these, by serial number. The
+// lists at AUCTIONLIST and SEALEDAUCTIONLIST keep the totals and the serials in use.
+pub const AUCTION_PREFIX: &'static str = "6d2ca0b5";
+pub const SEALED_AUCTION_PREFIX: &'static str = "6d2ca066";
+// Each trade is stored at its own address under TRADE_PREFIX, by instrument and sequence
+// number. TRADELEDGER still holds the trades of a market that predates that.
+pub const TRADE_PREFIX: &'static str = "6d2ca07a";
+// Each order's status record is stored at its own address under ORDERSTATUS_PREFIX, by
+// owner and ID. ORDERSTATUS still holds the records of a market that predates that; they
+// are read from there until first written back.
+pub const ORDERSTATUS_PREFIX: &'static str = "6d2ca05f";
+
+fn hash(_s: &str) -> String {
+    let mut hasher = Sha512::new();
+    hasher.input(_s.as_bytes());
+    hasher.result_str()
+}
+
+// The default instrument keeps the original single-book address.
+pub fn orderbook_addr(_instrument: &str) -> String {
+    if _instrument == DEFAULT_INSTRUMENT {
+        return String::from(ORDERBOOK);
+    }
+    let mut addr = String::from(ORDERBOOK_PREFIX);
+    addr.push_str(&hash(_instrument)[..62]);
+    addr
+}
+
+// The price levels of an instrument's book are stored one per address under this prefix;
+// the book's own address keeps everything else. For instruments other than the default
+// the book's address is under the prefix as well.
+pub fn levels_prefix(_instrument: &str) -> String {
+    let mut prefix = String::from(ORDERBOOK_PREFIX);
+    prefix.push_str(&hash(_instrument)[..30]);
+    prefix
+}
+
+pub fn level_addr(_instrument: &str, _side: Side, _price: u64) -> String {
+    let side = match _side {
+        Side::Buy => "0",
+        Side::Sell => "1",
+    };
+    format!("{}{}{:031x}", levels_prefix(_instrument), side, _price)
+}
+
+pub fn account_addr(_addr: &Address) -> String {
+    let mut addr = String::from(ACCOUNT_PREFIX);
+    addr.push_str(&hash(_addr.to_string_ref())[..62]);
+    addr
+}
+
+pub fn auction_addr(_serial: u64) -> String {
+    format!("{}{:062x}", AUCTION_PREFIX, _serial)
+}
+
+pub fn sealed_auction_addr(_serial: u64) -> String {
+    format!("{}{:062x}", SEALED_AUCTION_PREFIX, _serial)
+}
+
+pub fn order_status_prefix(_owner: &Address) -> String {
+    let mut prefix = String::from(ORDERSTATUS_PREFIX);
+    prefix.push_str(&hash(_owner.to_string_ref())[..30]);
+    prefix
+}
+
+// IDs are hashed as well, since a cancel or amend may name any.
+pub fn order_status_addr(_owner: &Address, _id: &str) -> String {
+    let mut addr = order_status_prefix(_owner);
+    addr.push_str(&hash(_id)[..32]);
+    addr
+}
+
+pub fn trades_prefix(_instrument: &str) -> String {
+    let mut prefix = String::from(TRADE_PREFIX);
+    prefix.push_str(&hash(_instrument)[..30]);
+    prefix
+}
+
+pub fn trade_addr(_instrument: &str, _seq: u64) -> String {
+    format!("{}{:032x}", trades_prefix(_instrument), _seq)
+}
+
+// The validator's Block Info family keeps its config here, with the number of the latest
+// block it has recorded. Orders and auctions expire by block number, so the market can't run
+// without Block Info enabled on the validator (see README).
+pub const BLOCKINFO_CONFIG: &'static str =
+    "00b10c0100000000000000000000000000000000000000000000000000000000000000";
 
+// Bootstrap admin; only consulted until a permission list has been written on-chain.
 pub const ADMIN: &'static str =
     "03d88919731f4f0e402624c42eb950da2e308e049aeb40b044f7ffb7e07d2b624d";
 
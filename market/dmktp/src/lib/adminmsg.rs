@@ -1,5 +1,12 @@
 use address::Address;
-use auction::{Auction, Bid};
+use auction::{Auction, AuctionList, Bid};
+use balancebook::BalanceBook;
+use failure::Error;
+use fees::FeeSchedule;
+use instrument::{default_instrument, Instrument, MarketMode};
+use matching::MatchingAlgorithm;
+use pricelimits::{CircuitBreaker, PriceBand};
+use rules::TradingRules;
 use useracct::UserAccount;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -7,24 +14,53 @@ pub enum AdminMsg {
     AdminCredit(BalanceChange),
     AdminDebit(BalanceChange),
     NewUser(UserAccount),
-    EndAuction(String /* Auction address */),
+    EndAuction(u64 /* Auction serial */),
     ClearOrderBook,
     Step,
+    ListInstrument(Instrument),
+    SetMarketMode(String /* instrument */, MarketMode),
+    // Takes effect from the next order; what rests on the book stays where it is.
+    SetMatching(String /* instrument */, MatchingAlgorithm),
+    // Runs one round of a call-mode instrument's auction.
+    CallAuction(String /* instrument */),
+    SetFees(FeeSchedule),
+    SetTradingRules(String /* instrument */, TradingRules),
+    // None removes the instrument's band or breaker.
+    SetPriceBand(String /* instrument */, Option<PriceBand>),
+    SetCircuitBreaker(String /* instrument */, Option<CircuitBreaker>),
+    // Decimal places of cash. Existing balances are not rescaled; set this before crediting
+    // any cash.
+    SetCashPlaces(u32),
 }
 
+// Changes liquid balances only. Holds belong to the orders and bids that took them, so an
+// admin can't move them; cancel the order or end the auction instead.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BalanceChange {
     pub addr: Address,
-    pub cash: (u64, u64),
-    pub assets: (u64, u64),
+    pub cash: u64,
+    pub assets: u64,
+    #[serde(default = "default_instrument")]
+    pub instrument: String,
 }
 
 impl BalanceChange {
-    pub fn from_vals(_addr: Address, _cash: (u64, u64), _assets: (u64, u64)) -> Self {
+    pub fn from_vals(_addr: Address, _cash: u64, _assets: u64, _instrument: String) -> Self {
         BalanceChange {
             addr: _addr,
             cash: _cash,
             assets: _assets,
+            instrument: _instrument,
         }
     }
+
+    pub fn credit(&self, _balance_book: &mut BalanceBook) -> Result<(), Error> {
+        _balance_book.credit_cash(&self.addr, self.cash)?;
+        _balance_book.credit_assets(&self.addr, &self.instrument, self.assets)
+    }
+
+    pub fn debit(&self, _balance_book: &mut BalanceBook) -> Result<(), Error> {
+        _balance_book.debit_cash(&self.addr, self.cash)?;
+        _balance_book.debit_assets(&self.addr, &self.instrument, self.assets)
+    }
 }
@@ -0,0 +1,23 @@
+// Changes the price and/or open quantity of one of the signer's resting orders, leaving
+// whatever is not given as it is. `qty` is the quantity left on the book, not the original
+// order size.
+//
+// Reducing the quantity keeps the order's place in the queue. Changing the price or
+// increasing the quantity moves it to the back: the order is given a new nonce, as if it
+// had just been submitted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Amend {
+    pub id: String,
+    pub price: Option<u64>,
+    pub qty: Option<u64>,
+}
+
+impl Amend {
+    pub fn from_vals(_id: String, _price: Option<u64>, _qty: Option<u64>) -> Self {
+        Amend {
+            id: _id,
+            price: _price,
+            qty: _qty,
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use errors::LibError;
+
+// Cash, prices and quantities are all stored as u64. Orders and accounts keep them in the
+// types here, whose only arithmetic is the checked methods below: products are worked out
+// in u128 and narrowed back only if they fit, and a result that doesn't is an error rather
+// than a panic or a wrapped value. The value inside is private; `get` reads it out for
+// comparing and display. Keeping them apart also means a price can't be added to a
+// quantity, or a quantity passed where cash is wanted. Each is stored as the bare u64.
+// Cash for a quantity at a price is worked out by decimal::Precision::notional.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+pub struct Price(u64);
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+pub struct Quantity(u64);
+
+// Cash, in its smallest unit.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+pub struct Amount(u64);
+
+impl Price {
+    pub fn new(_units: u64) -> Self {
+        Price(_units)
+    }
+
+    pub fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl Quantity {
+    pub fn new(_units: u64) -> Self {
+        Quantity(_units)
+    }
+
+    pub fn get(self) -> u64 {
+        self.0
+    }
+
+    pub fn add(self, _other: Quantity, _origin: &str) -> Result<Quantity, LibError> {
+        add(self.0, _other.0, _origin).map(Quantity)
+    }
+
+    pub fn sub(self, _other: Quantity, _origin: &str) -> Result<Quantity, LibError> {
+        sub(self.0, _other.0, _origin).map(Quantity)
+    }
+
+    // See scale below.
+    pub fn scale(self, _num: u64, _den: u64, _origin: &str) -> Result<Quantity, LibError> {
+        scale(self.0, _num, _den, false, _origin).map(Quantity)
+    }
+}
+
+impl Amount {
+    pub fn new(_units: u64) -> Self {
+        Amount(_units)
+    }
+
+    pub fn get(self) -> u64 {
+        self.0
+    }
+
+    pub fn add(self, _other: Amount, _origin: &str) -> Result<Amount, LibError> {
+        add(self.0, _other.0, _origin).map(Amount)
+    }
+
+    pub fn sub(self, _other: Amount, _origin: &str) -> Result<Amount, LibError> {
+        sub(self.0, _other.0, _origin).map(Amount)
+    }
+
+    // See scale below.
+    pub fn scale(self, _num: u64, _den: u64, _origin: &str) -> Result<Amount, LibError> {
+        scale(self.0, _num, _den, false, _origin).map(Amount)
+    }
+
+    // As scale, rounded up, so that what is taken never falls short of the share.
+    pub fn scale_up(self, _num: u64, _den: u64, _origin: &str) -> Result<Amount, LibError> {
+        scale(self.0, _num, _den, true, _origin).map(Amount)
+    }
+}
+
+fn add(_a: u64, _b: u64, _origin: &str) -> Result<u64, LibError> {
+    match _a.checked_add(_b) {
+        Some(v) => Ok(v),
+        None => Err(LibError::IntOverflowError {
+            origin: String::from(_origin),
+            fst: _a as usize,
+            snd: _b as usize,
+            intsize: String::from("u64"),
+        }),
+    }
+}
+
+fn sub(_a: u64, _b: u64, _origin: &str) -> Result<u64, LibError> {
+    match _a.checked_sub(_b) {
+        Some(v) => Ok(v),
+        None => Err(LibError::IntUnderflowError {
+            origin: String::from(_origin),
+            fst: _a as usize,
+            snd: _b as usize,
+            intsize: String::from("u64"),
+        }),
+    }
+}
+
+// _amt * _num / _den, rounded down unless _up, for fees in basis points, pro-rata shares
+// and the like. The product never overflows, so only a result too large for a u64 is an
+// error.
+fn scale(_amt: u64, _num: u64, _den: u64, _up: bool, _origin: &str) -> Result<u64, LibError> {
+    if _den == 0 {
+        return Err(LibError::EmptyZeroError {
+            field: format!("divisor in {}", _origin),
+        });
+    }
+    let product = _amt as u128 * _num as u128;
+    let rounding = match _up {
+        true => _den as u128 - 1,
+        false => 0,
+    };
+    narrow((product + rounding) / _den as u128, _origin, _amt, _num)
+}
+
+fn narrow(_wide: u128, _origin: &str, _fst: u64, _snd: u64) -> Result<u64, LibError> {
+    if _wide > u64::max_value() as u128 {
+        return Err(LibError::IntOverflowError {
+            origin: String::from(_origin),
+            fst: _fst as usize,
+            snd: _snd as usize,
+            intsize: String::from("u64"),
+        });
+    }
+    Ok(_wide as u64)
+}
@@ -1,15 +1,17 @@
 use address::Address;
 use addressing;
+use amount::{Amount, Quantity};
 use balancebook::BalanceBook;
 use errors::LibError;
 use failure::Error;
+use instrument::default_instrument;
 use std::collections::BTreeMap;
 use useracct::UserAccount;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct AuctionList {
     pub contents: BTreeMap<u64, Auction>,
-    pub total_auctioned: u64,
+    pub total_auctioned: Quantity,
 }
 
 #[derive(Serialize, Deserialize, Debug, Hash, Clone, PartialEq)]
@@ -17,25 +19,39 @@ pub struct Auction {
     serial: u64,
     description: String,
     is_open: bool,
-    auction_amt: u64,
+    auction_amt: Quantity,
     high_bidder: Address,
     high_bid: Option<Bid>,
     end_date: u64,
     history: Vec<Bid>,
+    #[serde(default = "default_instrument")]
+    instrument: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Hash, Clone, PartialEq)]
 pub struct Bid {
     pub addr: Address,
     pub auction_serial: u64,
-    pub bid_amt: u64,
+    pub bid_amt: Amount,
+}
+
+// How an auction closed. `winner` is None when nobody bid; `price` is what the winner paid.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuctionResult {
+    pub serial: u64,
+    pub sealed: bool,
+    pub winner: Option<Address>,
+    pub price: Amount,
+    pub qty: Quantity,
+    #[serde(default = "default_instrument")]
+    pub instrument: String,
 }
 
 impl AuctionList {
     pub fn new() -> Self {
         AuctionList {
             contents: BTreeMap::new(),
-            total_auctioned: 0,
+            total_auctioned: Quantity::default(),
         }
     }
 
@@ -49,7 +65,7 @@ impl Bid {
         Bid {
             addr: _addr,
             auction_serial: _serial,
-            bid_amt: _amt,
+            bid_amt: Amount::new(_amt),
         }
     }
 }
@@ -69,7 +85,7 @@ impl AuctionList {
     }
 
     pub fn get_total(&self) -> u64 {
-        self.total_auctioned
+        self.total_auctioned.get()
     }
 
     pub fn show_high_bid(&self, _auction_serial: u64) -> Result<Option<Bid>, Error> {
@@ -88,7 +104,13 @@ impl AuctionList {
         }
     }
 
-    pub fn place_bid(&mut self, _balance_book: &mut BalanceBook, _bid: Bid) -> Result<(), Error> {
+    // Bids are accepted through the auction's end_date, a block number.
+    pub fn place_bid(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        _bid: Bid,
+        _block: u64,
+    ) -> Result<(), Error> {
         let target_auction = match self.contents.get_mut(&_bid.auction_serial) {
             Some(v) => v,
             None => {
@@ -97,13 +119,21 @@ impl AuctionList {
                 }))
             }
         };
+        if _block > target_auction.end_date {
+            return Err(Error::from(LibError::CustomError {
+                contents: format!(
+                    "Bidding closed at block {}; this is block {}",
+                    target_auction.end_date, _block
+                ),
+            }));
+        }
         match target_auction.is_open {
             false => Err(Error::from(LibError::CustomError {
                 contents: String::from("Auction is no longer open for bidding"),
             })),
             // auction still open
             true => {
-                let bidder_liquid_cash: u64 = match _balance_book.get_by_addr(&_bid.addr) {
+                let bidder_liquid_cash: Amount = match _balance_book.get_by_addr(&_bid.addr) {
                     Some(acc) => acc.cash,
                     None => {
                         return Err(Error::from(LibError::CustomError {
@@ -112,9 +142,9 @@ impl AuctionList {
                     }
                 };
 
-                let current_high_bid: u64 = match target_auction.high_bid {
-                    Some(ref b) => b.bid_amt.clone(),
-                    _ => 0,
+                let current_high_bid: Amount = match target_auction.high_bid {
+                    Some(ref b) => b.bid_amt,
+                    _ => Amount::default(),
                 };
 
                 match (bidder_liquid_cash >= _bid.bid_amt, _bid.bid_amt > current_high_bid) {
@@ -125,13 +155,13 @@ impl AuctionList {
                         if let Some(bid) = target_auction.high_bid.clone() { target_auction.history.push(bid.clone()) };
                         let prev_high_bidder = target_auction.high_bidder.clone();
                         //change new high bidder's balances
-                        _balance_book.debit_cash(&_bid.addr, _bid.bid_amt)?;
-                        _balance_book.credit_hold_cash(&_bid.addr, _bid.bid_amt)?;
+                        _balance_book.debit_cash(&_bid.addr, _bid.bid_amt.get())?;
+                        _balance_book.credit_hold_cash(&_bid.addr, _bid.bid_amt.get())?;
                         // credit previous high bidder's money
                         // Only execute this block is old bid was non-zero.
-                        if current_high_bid > 0 {
-                            _balance_book.credit_cash(&prev_high_bidder, current_high_bid)?;
-                            _balance_book.debit_hold_cash(&prev_high_bidder, current_high_bid)?;
+                        if current_high_bid.get() > 0 {
+                            _balance_book.credit_cash(&prev_high_bidder, current_high_bid.get())?;
+                            _balance_book.debit_hold_cash(&prev_high_bidder, current_high_bid.get())?;
                         } else {
                             ();
                         }
@@ -144,11 +174,24 @@ impl AuctionList {
         }
     }
 
+    // (bidder, amount) of the high bid of each open auction, which holds that cash.
+    pub fn held(&self) -> Vec<(Address, u64)> {
+        self.contents
+            .values()
+            .filter(|x| x.is_open)
+            .filter_map(|x| {
+                x.high_bid
+                    .as_ref()
+                    .map(|b| (x.high_bidder.clone(), b.bid_amt.get()))
+            })
+            .collect()
+    }
+
     pub fn end_auction(
         &mut self,
         _balance_book: &mut BalanceBook,
         _serial: u64,
-    ) -> Result<(), Error> {
+    ) -> Result<AuctionResult, Error> {
         let target_auction = match self.contents.get_mut(&_serial) {
             Some(v) => v,
             None => {
@@ -158,18 +201,40 @@ impl AuctionList {
             }
         };
         target_auction.is_open = false;
+        let mut result = AuctionResult {
+            serial: _serial,
+            sealed: false,
+            winner: None,
+            price: Amount::default(),
+            qty: target_auction.auction_amt,
+            instrument: target_auction.instrument.clone(),
+        };
         if let Some(ref mut high_bid) = target_auction.high_bid {
-            _balance_book.debit_hold_cash(&target_auction.high_bidder, high_bid.bid_amt)?;
-            _balance_book.credit_assets(&target_auction.high_bidder, target_auction.auction_amt)?;
-            self.total_auctioned += target_auction.auction_amt;
-            Ok(())
-        } else {
-            Ok(())
+            _balance_book.debit_hold_cash(&target_auction.high_bidder, high_bid.bid_amt.get())?;
+            _balance_book.credit_assets(
+                &target_auction.high_bidder,
+                &target_auction.instrument,
+                target_auction.auction_amt.get(),
+            )?;
+            self.total_auctioned = self
+                .total_auctioned
+                .add(target_auction.auction_amt, "total auctioned")?;
+            result.winner = Some(target_auction.high_bidder.clone());
+            result.price = high_bid.bid_amt;
         }
+        Ok(result)
     }
 }
 
 impl Auction {
+    pub fn get_serial(&self) -> u64 {
+        self.serial
+    }
+
+    pub fn get_high_bidder(&self) -> &Address {
+        &self.high_bidder
+    }
+
     // get address/caller identity from payload signer public key
     pub fn new(
         _serial: u64,
@@ -177,24 +242,30 @@ impl Auction {
         _start_open: bool,
         _amt: u64,
         _end_date: u64,
+        _instrument: String,
     ) -> Self {
         Auction {
             serial: _serial,
             description: _description,
             is_open: _start_open,
-            auction_amt: _amt,
+            auction_amt: Quantity::new(_amt),
             high_bidder: Address::from_pubkey_string(&String::from(addressing::ADMIN)),
             high_bid: None,
             end_date: _end_date,
             history: Vec::new(),
+            instrument: _instrument,
         }
     }
 
     pub fn end_auction(&mut self, _balance_book: &mut BalanceBook) -> Result<(), Error> {
         self.is_open = false;
         if let Some(ref mut high_bid) = self.high_bid {
-            _balance_book.debit_hold_cash(&self.high_bidder, high_bid.bid_amt)?;
-            _balance_book.credit_assets(&self.high_bidder, self.auction_amt)?;
+            _balance_book.debit_hold_cash(&self.high_bidder, high_bid.bid_amt.get())?;
+            _balance_book.credit_assets(
+                &self.high_bidder,
+                &self.instrument,
+                self.auction_amt.get(),
+            )?;
             Ok(())
         } else {
             Ok(())
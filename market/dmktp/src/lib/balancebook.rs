@@ -1,12 +1,14 @@
 use address::Address;
-use errors::LibError::{
-    self, IntOverflowError, IntUnderflowError, NExistKeyError, UserExistsError,
-};
+use amount::{Amount, Quantity};
+use errors::LibError::{self, NExistKeyError, UserExistsError};
 use failure::Error;
+use instrument::InstrumentRegistry;
 use std::collections::BTreeMap;
 use std::fmt;
-use useracct::UserAccount;
+use useracct::{Holding, UserAccount};
 
+// A book may hold only some of the market's accounts (see getset); the caller loads the
+// ones it will touch.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BalanceBook {
     pub balance_book: BTreeMap<String, UserAccount>,
@@ -21,6 +23,14 @@ impl fmt::Display for BalanceBook {
 }
 
 impl BalanceBook {
+    // Every account as UserAccount::show has it.
+    pub fn show(&self, _instruments: &InstrumentRegistry) -> String {
+        self.balance_book
+            .values()
+            .map(|x| format!("\n{}\n", x.show(_instruments)))
+            .collect()
+    }
+
     pub fn new() -> Self {
         BalanceBook {
             balance_book: BTreeMap::new(),
@@ -101,17 +111,10 @@ impl BalanceBook {
     pub fn credit_cash(&mut self, _addr: &Address, _amt: u64) -> Result<(), Error> {
         match self.balance_book.get_mut(&_addr.contents) {
             Some(val) => {
-                if let Some(checked_sum) = val.cash.checked_add(_amt) {
-                    val.cash = checked_sum;
-                    return Ok(());
-                } else {
-                    return Err(Error::from(IntOverflowError {
-                        origin: String::from("credit cash balance method"),
-                        fst: val.cash as usize,
-                        snd: _amt as usize,
-                        intsize: String::from("u64"),
-                    }));
-                }
+                val.cash = val
+                    .cash
+                    .add(Amount::new(_amt), "credit cash balance method")?;
+                Ok(())
             }
             None => {
                 return Err(Error::from(NExistKeyError {
@@ -122,20 +125,22 @@ impl BalanceBook {
         }
     }
 
-    pub fn credit_assets(&mut self, _addr: &Address, _amt: u64) -> Result<(), Error> {
+    pub fn credit_assets(
+        &mut self,
+        _addr: &Address,
+        _instrument: &str,
+        _amt: u64,
+    ) -> Result<(), Error> {
         match self.balance_book.get_mut(_addr.to_string_ref()) {
-            Some(val) => {
-                if let Some(checked_sum) = val.assets.checked_add(_amt) {
-                    val.assets = checked_sum;
-                    return Ok(());
-                } else {
-                    return Err(Error::from(IntOverflowError {
-                        origin: String::from("credit asset balance method"),
-                        fst: val.assets as usize,
-                        snd: _amt as usize,
-                        intsize: String::from("u64"),
-                    }));
-                }
+            Some(acct) => {
+                let val: &mut Holding = acct
+                    .holdings
+                    .entry(String::from(_instrument))
+                    .or_insert_with(Holding::default);
+                val.assets = val
+                    .assets
+                    .add(Quantity::new(_amt), "credit asset balance method")?;
+                Ok(())
             }
             None => {
                 return Err(Error::from(NExistKeyError {
@@ -148,17 +153,10 @@ impl BalanceBook {
     pub fn credit_hold_cash(&mut self, _addr: &Address, _amt: u64) -> Result<(), Error> {
         match self.balance_book.get_mut(_addr.to_string_ref()) {
             Some(val) => {
-                if let Some(checked_sum) = val.hold_cash.checked_add(_amt) {
-                    val.hold_cash = checked_sum;
-                    return Ok(());
-                } else {
-                    return Err(Error::from(IntOverflowError {
-                        origin: String::from("credit hold_cash balance method"),
-                        fst: val.hold_cash as usize,
-                        snd: _amt as usize,
-                        intsize: String::from("u64"),
-                    }));
-                }
+                val.hold_cash = val
+                    .hold_cash
+                    .add(Amount::new(_amt), "credit hold_cash balance method")?;
+                Ok(())
             }
             None => {
                 return Err(Error::from(NExistKeyError {
@@ -168,20 +166,22 @@ impl BalanceBook {
             }
         }
     }
-    pub fn credit_hold_assets(&mut self, _addr: &Address, _amt: u64) -> Result<(), Error> {
+    pub fn credit_hold_assets(
+        &mut self,
+        _addr: &Address,
+        _instrument: &str,
+        _amt: u64,
+    ) -> Result<(), Error> {
         match self.balance_book.get_mut(_addr.to_string_ref()) {
-            Some(val) => {
-                if let Some(checked_sum) = val.hold_assets.checked_add(_amt) {
-                    val.hold_assets = checked_sum;
-                    Ok(())
-                } else {
-                    return Err(Error::from(IntOverflowError {
-                        origin: String::from("credit asset balance method"),
-                        fst: val.hold_assets as usize,
-                        snd: _amt as usize,
-                        intsize: String::from("u64"),
-                    }));
-                }
+            Some(acct) => {
+                let val: &mut Holding = acct
+                    .holdings
+                    .entry(String::from(_instrument))
+                    .or_insert_with(Holding::default);
+                val.hold_assets = val
+                    .hold_assets
+                    .add(Quantity::new(_amt), "credit asset balance method")?;
+                Ok(())
             }
             None => {
                 return Err(Error::from(NExistKeyError {
@@ -195,17 +195,10 @@ impl BalanceBook {
     pub fn debit_cash(&mut self, _addr: &Address, _amt: u64) -> Result<(), Error> {
         match self.balance_book.get_mut(_addr.to_string_ref()) {
             Some(val) => {
-                if let Some(checked_diff) = val.cash.checked_sub(_amt) {
-                    val.cash = checked_diff;
-                    Ok(())
-                } else {
-                    return Err(Error::from(IntUnderflowError {
-                        origin: String::from("debit cash balance method"),
-                        fst: val.cash as usize,
-                        snd: _amt as usize,
-                        intsize: String::from("u64"),
-                    }));
-                }
+                val.cash = val
+                    .cash
+                    .sub(Amount::new(_amt), "debit cash balance method")?;
+                Ok(())
             }
             None => {
                 return Err(Error::from(NExistKeyError {
@@ -215,20 +208,22 @@ impl BalanceBook {
             }
         }
     }
-    pub fn debit_assets(&mut self, _addr: &Address, _amt: u64) -> Result<(), Error> {
+    pub fn debit_assets(
+        &mut self,
+        _addr: &Address,
+        _instrument: &str,
+        _amt: u64,
+    ) -> Result<(), Error> {
         match self.balance_book.get_mut(_addr.to_string_ref()) {
-            Some(val) => {
-                if let Some(checked_diff) = val.assets.checked_sub(_amt) {
-                    val.assets = checked_diff;
-                    Ok(())
-                } else {
-                    return Err(Error::from(IntUnderflowError {
-                        origin: String::from("debit asset balance method"),
-                        fst: val.assets as usize,
-                        snd: _amt as usize,
-                        intsize: String::from("u64"),
-                    }));
-                }
+            Some(acct) => {
+                let val: &mut Holding = acct
+                    .holdings
+                    .entry(String::from(_instrument))
+                    .or_insert_with(Holding::default);
+                val.assets = val
+                    .assets
+                    .sub(Quantity::new(_amt), "debit asset balance method")?;
+                Ok(())
             }
             None => {
                 return Err(Error::from(NExistKeyError {
@@ -241,17 +236,10 @@ impl BalanceBook {
     pub fn debit_hold_cash(&mut self, _addr: &Address, _amt: u64) -> Result<(), Error> {
         match self.balance_book.get_mut(_addr.to_string_ref()) {
             Some(val) => {
-                if let Some(checked_diff) = val.hold_cash.checked_sub(_amt) {
-                    val.hold_cash = checked_diff;
-                    Ok(())
-                } else {
-                    return Err(Error::from(IntUnderflowError {
-                        origin: String::from("debit hold_cash balance method"),
-                        fst: val.hold_cash as usize,
-                        snd: _amt as usize,
-                        intsize: String::from("u64"),
-                    }));
-                }
+                val.hold_cash = val
+                    .hold_cash
+                    .sub(Amount::new(_amt), "debit hold_cash balance method")?;
+                Ok(())
             }
             None => {
                 return Err(Error::from(NExistKeyError {
@@ -261,20 +249,22 @@ impl BalanceBook {
             }
         }
     }
-    pub fn debit_hold_assets(&mut self, _addr: &Address, _amt: u64) -> Result<(), Error> {
+    pub fn debit_hold_assets(
+        &mut self,
+        _addr: &Address,
+        _instrument: &str,
+        _amt: u64,
+    ) -> Result<(), Error> {
         match self.balance_book.get_mut(_addr.to_string_ref()) {
-            Some(val) => {
-                if let Some(checked_diff) = val.hold_assets.checked_sub(_amt) {
-                    val.hold_assets = checked_diff;
-                    Ok(())
-                } else {
-                    return Err(Error::from(IntUnderflowError {
-                        origin: String::from("debit hold_assets balance method"),
-                        fst: val.hold_cash as usize,
-                        snd: _amt as usize,
-                        intsize: String::from("u64"),
-                    }));
-                }
+            Some(acct) => {
+                let val: &mut Holding = acct
+                    .holdings
+                    .entry(String::from(_instrument))
+                    .or_insert_with(Holding::default);
+                val.hold_assets = val
+                    .hold_assets
+                    .sub(Quantity::new(_amt), "debit hold_assets balance method")?;
+                Ok(())
             }
             None => {
                 return Err(Error::from(NExistKeyError {
@@ -284,4 +274,77 @@ impl BalanceBook {
             }
         }
     }
+
+    // Moves cash between an account's free balance and its holds so that `_from` held becomes
+    // `_to` held. Either both balances change or, on error, neither does.
+    pub fn adjust_hold_cash(&mut self, _addr: &Address, _from: u64, _to: u64) -> Result<(), Error> {
+        let val = self.get_acct_mut(_addr)?;
+        let (cash, hold_cash) = match _to >= _from {
+            true => {
+                let moved = Amount::new(_to - _from);
+                (val.cash.sub(moved, ""), val.hold_cash.add(moved, ""))
+            }
+            false => {
+                let moved = Amount::new(_from - _to);
+                (val.cash.add(moved, ""), val.hold_cash.sub(moved, ""))
+            }
+        };
+        match (cash, hold_cash) {
+            (Ok(c), Ok(h)) => {
+                val.cash = c;
+                val.hold_cash = h;
+                Ok(())
+            }
+            _ => Err(Error::from(LibError::CustomError {
+                contents: format!(
+                    "Cannot change cash held from {} to {}; have cash: {}, held: {}",
+                    _from,
+                    _to,
+                    val.cash.get(),
+                    val.hold_cash.get()
+                ),
+            })),
+        }
+    }
+
+    pub fn adjust_hold_assets(
+        &mut self,
+        _addr: &Address,
+        _instrument: &str,
+        _from: u64,
+        _to: u64,
+    ) -> Result<(), Error> {
+        let val: &mut Holding = self
+            .get_acct_mut(_addr)?
+            .holdings
+            .entry(String::from(_instrument))
+            .or_insert_with(Holding::default);
+        let (assets, hold_assets) = match _to >= _from {
+            true => {
+                let moved = Quantity::new(_to - _from);
+                (val.assets.sub(moved, ""), val.hold_assets.add(moved, ""))
+            }
+            false => {
+                let moved = Quantity::new(_from - _to);
+                (val.assets.add(moved, ""), val.hold_assets.sub(moved, ""))
+            }
+        };
+        match (assets, hold_assets) {
+            (Ok(a), Ok(h)) => {
+                val.assets = a;
+                val.hold_assets = h;
+                Ok(())
+            }
+            _ => Err(Error::from(LibError::CustomError {
+                contents: format!(
+                    "Cannot change {} held from {} to {}; have: {}, held: {}",
+                    _instrument,
+                    _from,
+                    _to,
+                    val.assets.get(),
+                    val.hold_assets.get()
+                ),
+            })),
+        }
+    }
 }
@@ -0,0 +1,108 @@
+use errors::LibError;
+
+// Reads the validator's Block Info config, a protobuf BlockInfoConfig:
+//
+//     message BlockInfoConfig {
+//         uint64 latest_block = 1;
+//         uint64 oldest_block = 2;
+//         uint64 target_count = 3;
+//         uint64 sync_tolerance = 4;
+//     }
+//
+// The sdk doesn't ship the Block Info messages, and only latest_block is wanted, so the
+// fields are walked by hand. An absent field is 0, as protobuf has it.
+pub fn latest_block(_config: &[u8]) -> Result<u64, LibError> {
+    let mut pos = 0;
+    while pos < _config.len() {
+        let key = varint(_config, &mut pos)?;
+        match key & 7 {
+            0 => {
+                let value = varint(_config, &mut pos)?;
+                if key >> 3 == 1 {
+                    return Ok(value);
+                }
+            }
+            1 => pos += 8,
+            2 => {
+                let len = varint(_config, &mut pos)?;
+                pos = pos.saturating_add(len as usize);
+            }
+            5 => pos += 4,
+            _ => return Err(malformed()),
+        }
+    }
+    match pos == _config.len() {
+        true => Ok(0),
+        false => Err(malformed()),
+    }
+}
+
+// The number of the block a transaction is in. Block Info records each block at the start
+// of the next one, so the latest it has is the previous block. The market can't tell time
+// without it: with no config in state there is no block number to fall back on, since
+// expiries, halts and breaker windows would all stall at whatever was picked.
+pub fn block_num(_config: Option<&[u8]>) -> Result<u64, LibError> {
+    match _config {
+        Some(v) => Ok(latest_block(v)?.saturating_add(1)),
+        None => Err(LibError::NExistKeyError {
+            contents: String::from("Block Info config"),
+            structure: String::from("state"),
+        }),
+    }
+}
+
+fn varint(_data: &[u8], _pos: &mut usize) -> Result<u64, LibError> {
+    let mut value: u64 = 0;
+    for shift in 0..10 {
+        let byte = match _data.get(*_pos) {
+            Some(v) => *v,
+            None => return Err(malformed()),
+        };
+        *_pos += 1;
+        value |= ((byte & 0x7f) as u64) << (7 * shift);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(malformed())
+}
+
+fn malformed() -> LibError {
+    LibError::CustomError {
+        contents: String::from("Block Info config is malformed"),
+    }
+}
+
+#[cfg(test)]
+mod blockinfo_tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_block() {
+        // latest_block 300, oldest_block 2, target_count 256, sync_tolerance 300
+        let config = [
+            0x08, 0xac, 0x02, 0x10, 0x02, 0x18, 0x80, 0x02, 0x20, 0xac, 0x02,
+        ];
+        assert_eq!(latest_block(&config).unwrap(), 300);
+        // fields may come in any order
+        let config = [0x10, 0x02, 0x08, 0x05];
+        assert_eq!(latest_block(&config).unwrap(), 5);
+        assert_eq!(latest_block(&[]).unwrap(), 0);
+        assert!(latest_block(&[0x08, 0xac]).is_err());
+    }
+
+    #[test]
+    fn test_block_num() {
+        assert_eq!(block_num(Some(&[0x08, 0x05])).unwrap(), 6);
+        assert_eq!(block_num(Some(&[])).unwrap(), 1);
+        // without Block Info running there is no block number at all
+        match block_num(None) {
+            Err(LibError::NExistKeyError { .. }) => (),
+            other => panic!("expected a missing Block Info error, got {:?}", other),
+        }
+        match block_num(Some(&[0x08, 0xac])) {
+            Err(LibError::CustomError { .. }) => (),
+            other => panic!("expected a malformed config error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use address::Address;
+use balancebook::BalanceBook;
+use errors::LibError;
+use failure::Error;
+use matching;
+use order::{BuyOrder, OrderT, OrderType, SellOrder, Side};
+use orderbook::{check_instrument, check_peak, Fill, OrderBook};
+use pricelevels::PriceLevels;
+use settlement::{settle, BuyFunding, SellFunding};
+use std::collections::BTreeMap;
+use std::mem;
+
+// Uniform-price batch matching for call-mode instruments. Orders are rested as they
+// arrive, holds and all, and nothing trades until the instrument's auction is run.
+//
+// The clearing price is the limit price that executes the most volume. Ties go to the
+// price leaving the smallest imbalance between demand and supply, then to the one nearest
+// the last clearing price, then to the lowest. Every crossing order trades at that price.
+// The side with more volume on offer is rationed: price levels fill fully in priority order
+// until the level where volume runs out, which is shared pro rata by quantity; units lost
+// to rounding go one each to that level's earliest orders.
+//
+// An owner's buys are never paired with their own sells, whatever the orders' self-trade
+// prevention modes, which are for an incoming order meeting a resting one. Volume only
+// counts towards the clearing price as far as different owners can trade it. Allocations
+// are paired off in priority order, skipping sells of the buy's owner; what is left of
+// one goes to other owners' crossing orders that haven't traded all they could.
+
+// Only limit orders can wait for an auction.
+pub fn submit<T: OrderT>(
+    _order: T,
+    _bb: &mut BalanceBook,
+    _ob: &mut OrderBook,
+) -> Result<(), Error> {
+    check_instrument(_ob, &_order)?;
+    check_peak(&_order)?;
+    if _order.get_order_type() != OrderType::Limit {
+        return Err(Error::from(LibError::CustomError {
+            contents: format!(
+                "{} orders can't be submitted to {}, which trades in call auctions",
+                _order.get_order_type(),
+                _ob.instrument
+            ),
+        }));
+    }
+    _order.rest(_bb, _ob)
+}
+
+// Returns the clearing price and the volume that would execute at it, or None when the
+// book doesn't cross. An owner's orders can't trade with each other, so the volume at a
+// price is the most that can be paired between different owners: the lesser of demand and
+// supply, and of what is left of both once the owner with the most on the two sides
+// together is taken out. A book crossing only its owners' own orders has none.
+pub fn clearing_price(_ob: &OrderBook) -> Option<(u64, u64)> {
+    // (owner, quantity bid, quantity offered) of each order, by price
+    let mut at: BTreeMap<u64, Vec<(&Address, u64, u64)>> = BTreeMap::new();
+    for x in _ob.buy_orders.iter() {
+        at.entry(x.get_price())
+            .or_insert_with(Vec::new)
+            .push((x.get_addr_ref(), x.get_qty(), 0));
+    }
+    for x in _ob.sell_orders.iter() {
+        at.entry(x.get_price())
+            .or_insert_with(Vec::new)
+            .push((x.get_addr_ref(), 0, x.get_qty()));
+    }
+
+    // Demand at a price is everything bid at it or above and supply everything offered at
+    // it or below, so going up the prices supply takes on each price's offers as demand
+    // gives up the bids of the price before; each owner's share of the two follows along.
+    let mut owners = Owners::new();
+    let mut demand: u64 = 0;
+    for x in _ob.buy_orders.iter() {
+        demand = demand.saturating_add(x.get_qty());
+        owners.add(x.get_addr_ref(), x.get_qty());
+    }
+    let mut supply: u64 = 0;
+    let mut below: Vec<(&Address, u64, u64)> = Vec::new();
+    let mut best: Option<(u64, u64, u64)> = None; // (price, volume, imbalance)
+    for (price, orders) in at.into_iter() {
+        for &(owner, bid, _) in below.iter() {
+            demand = demand.saturating_sub(bid);
+            owners.sub(owner, bid);
+        }
+        for &(owner, _, offered) in orders.iter() {
+            supply = supply.saturating_add(offered);
+            owners.add(owner, offered);
+        }
+        below = orders;
+        let volume = demand.min(supply).min(
+            demand
+                .saturating_add(supply)
+                .saturating_sub(owners.largest()),
+        );
+        let imbalance = distance(demand, supply);
+        if volume == 0 {
+            continue;
+        }
+        let better = match best {
+            None => true,
+            Some((b_price, b_volume, b_imbalance)) => {
+                if volume != b_volume {
+                    volume > b_volume
+                } else if imbalance != b_imbalance {
+                    imbalance < b_imbalance
+                } else {
+                    match _ob.last_price {
+                        // Candidates are visited in increasing order, so an equal distance
+                        // keeps the lower price.
+                        Some(last) => distance(price, last) < distance(b_price, last),
+                        None => false,
+                    }
+                }
+            }
+        };
+        if better {
+            best = Some((price, volume, imbalance));
+        }
+    }
+    best.map(|(price, volume, _)| (price, volume))
+}
+
+// Each owner's bids and offers crossing the price clearing_price is looking at, and how
+// many owners have each total, so the largest is always to hand.
+struct Owners<'a> {
+    totals: BTreeMap<&'a Address, u64>,
+    counts: BTreeMap<u64, usize>,
+}
+
+impl<'a> Owners<'a> {
+    fn new() -> Self {
+        Owners {
+            totals: BTreeMap::new(),
+            counts: BTreeMap::new(),
+        }
+    }
+
+    fn add(&mut self, _owner: &'a Address, _qty: u64) {
+        let old = self.totals.get(_owner).cloned().unwrap_or(0);
+        self.set(_owner, old, old.saturating_add(_qty));
+    }
+
+    fn sub(&mut self, _owner: &'a Address, _qty: u64) {
+        let old = self.totals.get(_owner).cloned().unwrap_or(0);
+        self.set(_owner, old, old.saturating_sub(_qty));
+    }
+
+    fn set(&mut self, _owner: &'a Address, _old: u64, _new: u64) {
+        let emptied = match self.counts.get_mut(&_old) {
+            Some(v) => {
+                *v -= 1;
+                *v == 0
+            }
+            None => false,
+        };
+        if emptied {
+            self.counts.remove(&_old);
+        }
+        *self.counts.entry(_new).or_insert(0) += 1;
+        self.totals.insert(_owner, _new);
+    }
+
+    fn largest(&self) -> u64 {
+        self.counts.keys().next_back().cloned().unwrap_or(0)
+    }
+}
+
+fn distance(_a: u64, _b: u64) -> u64 {
+    _a.max(_b) - _a.min(_b)
+}
+
+// How much of _volume each order gets. `_priority` is (price, nonce, qty) for every order
+// that crosses the clearing price, best price first and oldest first within a price.
+fn ration(_priority: &Vec<(u64, u64, u64)>, _volume: u64) -> Result<Vec<u64>, Error> {
+    let mut allocs: Vec<u64> = vec![0; _priority.len()];
+    let mut remaining = _volume;
+    let mut start = 0;
+    while start < _priority.len() && remaining > 0 {
+        let price = _priority[start].0;
+        let end = start
+            + _priority[start..]
+                .iter()
+                .take_while(|x| x.0 == price)
+                .count();
+        let level = _priority[start..end]
+            .iter()
+            .fold(0, |acc: u64, x| acc.saturating_add(x.2));
+        if remaining >= level {
+            for i in start..end {
+                allocs[i] = _priority[i].2;
+            }
+            remaining -= level;
+        } else {
+            // A level is in time order already.
+            let qtys: Vec<u64> = _priority[start..end].iter().map(|x| x.2).collect();
+            let shares = matching::pro_rata(remaining, &qtys)?;
+            allocs[start..end].copy_from_slice(&shares);
+            remaining = 0;
+        }
+        start = end;
+    }
+    Ok(allocs)
+}
+
+// Runs the auction: executes every crossing order at the clearing price, settles balances
+// and leaves what is unfilled on the book with its time priority. Returns the fills.
+pub fn clear(_ob: &mut OrderBook, _bb: &mut BalanceBook) -> Result<Vec<Fill>, Error> {
+    let (price, volume) = match clearing_price(_ob) {
+        Some(v) => v,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut buys: Vec<BuyOrder> = mem::replace(&mut _ob.buy_orders, PriceLevels::new()).into_vec();
+    let mut sells: Vec<SellOrder> =
+        mem::replace(&mut _ob.sell_orders, PriceLevels::new()).into_vec();
+    let buy_priority: Vec<(u64, u64, u64)> = buys
+        .iter()
+        .take_while(|x| x.get_price() >= price)
+        .map(|x| (x.get_price(), x.get_nonce(), x.get_qty()))
+        .collect();
+    let sell_priority: Vec<(u64, u64, u64)> = sells
+        .iter()
+        .take_while(|x| x.get_price() <= price)
+        .map(|x| (x.get_price(), x.get_nonce(), x.get_qty()))
+        .collect();
+    let mut buy_allocs = ration(&buy_priority, volume)?;
+    let mut sell_allocs = ration(&sell_priority, volume)?;
+    // What each crossing order has traded so far.
+    let mut bought: Vec<u64> = vec![0; buy_allocs.len()];
+    let mut sold: Vec<u64> = vec![0; sell_allocs.len()];
+
+    // Pair the allocations off in priority order; `first` is the first sell with some left.
+    let mut fills: Vec<Fill> = Vec::new();
+    let mut first = 0;
+    for b in 0..buy_allocs.len() {
+        while first < sell_allocs.len() && sell_allocs[first] == 0 {
+            first += 1;
+        }
+        let mut s = first;
+        while buy_allocs[b] > 0 && s < sell_allocs.len() {
+            if sell_allocs[s] == 0 || sells[s].get_addr_ref() == buys[b].get_addr_ref() {
+                s += 1;
+                continue;
+            }
+            let qty = buy_allocs[b].min(sell_allocs[s]);
+            fills.push(pair(_ob, _bb, &buys[b], bought[b], &sells[s], price, qty)?);
+            buy_allocs[b] -= qty;
+            sell_allocs[s] -= qty;
+            bought[b] += qty;
+            sold[s] += qty;
+        }
+    }
+
+    // What is left of an allocation could only have been paired with its owner's own
+    // orders. It goes instead to what other owners' crossing orders have not yet traded,
+    // in priority order, so their volume isn't left crossing for want of a counterparty.
+    for b in 0..buy_allocs.len() {
+        for s in 0..sell_allocs.len() {
+            if buy_allocs[b] == 0 {
+                break;
+            }
+            if sells[s].get_addr_ref() == buys[b].get_addr_ref() {
+                continue;
+            }
+            let qty = buy_allocs[b].min(sells[s].get_qty() - sold[s]);
+            if qty > 0 {
+                fills.push(pair(_ob, _bb, &buys[b], bought[b], &sells[s], price, qty)?);
+                buy_allocs[b] -= qty;
+                sell_allocs[s] = sell_allocs[s].saturating_sub(qty);
+                bought[b] += qty;
+                sold[s] += qty;
+            }
+        }
+    }
+    for s in 0..sell_allocs.len() {
+        for b in 0..buy_allocs.len() {
+            if sell_allocs[s] == 0 {
+                break;
+            }
+            if sells[s].get_addr_ref() == buys[b].get_addr_ref() {
+                continue;
+            }
+            let qty = sell_allocs[s].min(buys[b].get_qty() - bought[b]);
+            if qty > 0 {
+                fills.push(pair(_ob, _bb, &buys[b], bought[b], &sells[s], price, qty)?);
+                sell_allocs[s] -= qty;
+                buy_allocs[b] = buy_allocs[b].saturating_sub(qty);
+                bought[b] += qty;
+                sold[s] += qty;
+            }
+        }
+    }
+
+    for (order, qty) in buys.iter_mut().zip(bought.iter()) {
+        order.dec_qty_by(*qty)?;
+    }
+    for (order, qty) in sells.iter_mut().zip(sold.iter()) {
+        order.dec_qty_by(*qty)?;
+    }
+    _ob.buy_orders = buys
+        .into_iter()
+        .filter(|x| x.get_qty() > 0)
+        .map(|mut x| {
+            if x.visible_qty() == 0 {
+                x.refill();
+            }
+            x
+        })
+        .collect();
+    _ob.sell_orders = sells
+        .into_iter()
+        .filter(|x| x.get_qty() > 0)
+        .map(|mut x| {
+            if x.visible_qty() == 0 {
+                x.refill();
+            }
+            x
+        })
+        .collect();
+    _ob.last_price = Some(price);
+    Ok(fills)
+}
+
+// Settles _qty at _price between a buy that has already traded _bought in this auction and
+// a sell.
+fn pair(
+    _ob: &OrderBook,
+    _bb: &mut BalanceBook,
+    _buy: &BuyOrder,
+    _bought: u64,
+    _sell: &SellOrder,
+    _price: u64,
+    _qty: u64,
+) -> Result<Fill, Error> {
+    // There is no incoming order in a batch; the later of the two is counted as the
+    // aggressor.
+    let aggressor = match _buy.get_nonce() > _sell.get_nonce() {
+        true => Side::Buy,
+        false => Side::Sell,
+    };
+    let settled = settle(
+        _bb,
+        _ob,
+        (
+            _buy.get_addr_ref(),
+            BuyFunding::Held(_buy.get_price(), _buy.get_qty() - _bought, _buy.fee_bps),
+        ),
+        (_sell.get_addr_ref(), SellFunding::Held),
+        aggressor,
+        _price,
+        _qty,
+    )?;
+    Ok(Fill::from_orders(
+        _buy, _sell, _price, _qty, settled, aggressor,
+    ))
+}
@@ -0,0 +1,11 @@
+// Withdraws resting orders from the book. Orders are identified either by their order
+// ID, or by the side of the book they rest on and the nonce the orderbook assigned them
+// when they were inserted (shown by `dmktc get_orderbook`). Nonces are per book, so the
+// latter also name the instrument. Only the owner of an order can cancel it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Cancel {
+    Order(String /* order id */),
+    Buy(String /* instrument */, u64 /* nonce */),
+    Sell(String /* instrument */, u64 /* nonce */),
+    All,
+}
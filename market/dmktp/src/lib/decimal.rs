@@ -0,0 +1,202 @@
+use amount::Amount;
+use errors::LibError;
+use fees::MAX_BPS;
+use std::fmt;
+
+// More places than this and a single unit no longer fits in a u64.
+pub const MAX_PLACES: u32 = 18;
+
+// A fixed-point number: `units` of 10^-places. Prices, quantities and cash are all stored
+// as a whole number of their smallest unit; this is how they are read and written.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decimal {
+    pub units: u64,
+    pub places: u32,
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.places == 0 {
+            return write!(f, "{}", self.units);
+        }
+        let digits = format!("{:0>width$}", self.units, width = self.places as usize + 1);
+        let (whole, frac) = digits.split_at(digits.len() - self.places as usize);
+        write!(f, "{}.{}", whole, frac)
+    }
+}
+
+impl Decimal {
+    pub fn new(_units: u64, _places: u32) -> Self {
+        Decimal {
+            units: _units,
+            places: _places,
+        }
+    }
+
+    // Reads "12.35" as 1235 with 2 places. Fewer decimal digits than _places are padded;
+    // more are an error rather than rounded away.
+    pub fn parse(_s: &str, _places: u32) -> Result<Self, LibError> {
+        let bad = || LibError::EncodingError {
+            field: String::from("decimal"),
+            encoding: format!("digits with at most {} after the point", _places),
+            got: String::from(_s),
+        };
+        let mut split = _s.splitn(2, '.');
+        let whole = split.next().unwrap_or("");
+        let frac = split.next().unwrap_or("");
+        if (whole.is_empty() && frac.is_empty())
+            || frac.len() > _places as usize
+            || !whole
+                .chars()
+                .chain(frac.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(bad());
+        }
+        let digits = format!("{}{:0<width$}", whole, frac, width = _places as usize);
+        let mut units: u64 = 0;
+        for c in digits.chars() {
+            units = match units
+                .checked_mul(10)
+                .and_then(|x| x.checked_add(c as u64 - '0' as u64))
+            {
+                Some(v) => v,
+                None => {
+                    return Err(LibError::IntOverflowError {
+                        origin: format!("parsing decimal {}", _s),
+                        fst: units as usize,
+                        snd: 10,
+                        intsize: String::from("u64"),
+                    })
+                }
+            };
+        }
+        Ok(Decimal::new(units, _places))
+    }
+}
+
+// Decimal places of an instrument's prices and quantities, and of the market's cash.
+// A price is cash per whole unit of the instrument.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Precision {
+    pub price: u32,
+    pub qty: u32,
+    pub cash: u32,
+}
+
+impl fmt::Display for Precision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "price places: {}, quantity places: {}, cash places: {}",
+            self.price, self.qty, self.cash
+        )
+    }
+}
+
+impl Precision {
+    pub fn check(&self) -> Result<(), LibError> {
+        if self.price > MAX_PLACES || self.qty > MAX_PLACES || self.cash > MAX_PLACES {
+            return Err(LibError::CustomError {
+                contents: format!("Precision {} is over {} places", self, MAX_PLACES),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn price(&self, _units: u64) -> Decimal {
+        Decimal::new(_units, self.price)
+    }
+
+    pub fn qty(&self, _units: u64) -> Decimal {
+        Decimal::new(_units, self.qty)
+    }
+
+    pub fn cash(&self, _units: u64) -> Decimal {
+        Decimal::new(_units, self.cash)
+    }
+
+    // Cash, in its smallest unit, for _qty at _price. Anything smaller than that unit is
+    // rounded down, so a trade settles the same way wherever it is worked out.
+    pub fn notional(&self, _price: u64, _qty: u64) -> Result<u64, LibError> {
+        let overflow = || LibError::IntOverflowError {
+            origin: String::from("notional (price * qty)"),
+            fst: _price as usize,
+            snd: _qty as usize,
+            intsize: String::from("u64"),
+        };
+        let product = _price as u128 * _qty as u128;
+        let shift = self.cash as i64 - self.price as i64 - self.qty as i64;
+        let cash = match shift >= 0 {
+            true => product.checked_mul(10u128.pow(shift as u32)),
+            false => Some(product / 10u128.pow((-shift) as u32)),
+        };
+        match cash {
+            Some(v) if v <= u64::max_value() as u128 => Ok(v as u64),
+            _ => Err(overflow()),
+        }
+    }
+
+    // The most that can be bought at _price for _cash, the inverse of notional. A price of
+    // 0 puts no limit on it.
+    pub fn affordable(&self, _price: u64, _cash: u64) -> u64 {
+        if _price == 0 {
+            return u64::max_value();
+        }
+        let shift = self.cash as i64 - self.price as i64 - self.qty as i64;
+        // notional rounds down, so anything short of _cash + 1 whole units will do
+        let qty = match shift >= 0 {
+            true => 10u128
+                .pow(shift as u32)
+                .checked_mul(_price as u128)
+                .map(|x| _cash as u128 / x),
+            false => (_cash as u128 + 1)
+                .checked_mul(10u128.pow((-shift) as u32))
+                .map(|x| (x - 1) / _price as u128),
+        };
+        match qty {
+            Some(v) if v <= u64::max_value() as u128 => v as u64,
+            Some(_) => u64::max_value(),
+            // only a price too large to pay even one unit of overflows the divisor
+            None if shift >= 0 => 0,
+            None => u64::max_value(),
+        }
+    }
+
+    // What a buy resting at _price holds for _qty: the notional, and the most its owner can
+    // be charged in fees on it at _fee_bps, rounded up as fees are.
+    pub fn held(&self, _price: u64, _qty: u64, _fee_bps: u64) -> Result<u64, LibError> {
+        let notional = Amount::new(self.notional(_price, _qty)?);
+        notional
+            .add(
+                notional.scale_up(_fee_bps, MAX_BPS, "buy hold fee")?,
+                "buy hold",
+            )
+            .map(Amount::get)
+    }
+
+    // What a buy resting at _price with _fee_bps held holds for _open, less what it holds
+    // once _qty of that has traded. Working this out from the rounded holds, rather than
+    // rounding the cash for _qty on its own, leaves nothing of the hold behind once the
+    // order is done.
+    pub fn released(
+        &self,
+        _price: u64,
+        _open: u64,
+        _qty: u64,
+        _fee_bps: u64,
+    ) -> Result<u64, LibError> {
+        let remaining = match _open.checked_sub(_qty) {
+            Some(v) => v,
+            None => {
+                return Err(LibError::IntUnderflowError {
+                    origin: String::from("hold release"),
+                    fst: _open as usize,
+                    snd: _qty as usize,
+                    intsize: String::from("u64"),
+                })
+            }
+        };
+        Ok(self.held(_price, _open, _fee_bps)? - self.held(_price, remaining, _fee_bps)?)
+    }
+}
@@ -72,6 +72,28 @@ pub enum LibError {
         snd: usize,
         intsize: String,
     },
+    #[fail(
+        display = "Payload address {} does not match the address {} of the transaction signer",
+        payload,
+        signer
+    )]
+    SignerMismatchError { payload: String, signer: String },
+    #[fail(display = "Signer {} is not authorized to {}", signer, action)]
+    PermissionError { signer: String, action: String },
+    #[fail(display = "Price {} is not a positive multiple of the tick size {}", price, tick_size)]
+    TickSizeError { price: u64, tick_size: u64 },
+    #[fail(display = "Quantity {} is not a multiple of the lot size {}", qty, lot_size)]
+    LotSizeError { qty: u64, lot_size: u64 },
+    #[fail(display = "Quantity {} is below the minimum order quantity {}", qty, min_qty)]
+    MinQtyError { qty: u64, min_qty: u64 },
+    #[fail(display = "Quantity {} is above the maximum order quantity {}", qty, max_qty)]
+    MaxQtyError { qty: u64, max_qty: u64 },
+    #[fail(display = "Order notional {} is above the maximum notional {}", notional, max_notional)]
+    NotionalError { notional: u64, max_notional: u64 },
+    #[fail(display = "Price {} is outside the price band of {} to {}", price, low, high)]
+    PriceBandError { price: u64, low: u64, high: u64 },
+    #[fail(display = "Trading in {} is halted until block {}", instrument, resumes_at)]
+    HaltedError { instrument: String, resumes_at: u64 },
     #[fail(display = "Serde CBOR failed to decode the HTTP response from CBOR")]
     SerdeCborError,
     #[fail(display = "Got a 'None' when unwrapping a result")]
@@ -0,0 +1,129 @@
+use auction::AuctionResult;
+use failure::Error;
+use orderstatus::OrderRecord;
+use pricelimits::Halt;
+use serde_cbor;
+use std::cmp;
+use std::fmt;
+use tradeledger::Trade;
+
+pub const TRADE: &'static str = "market/trade";
+pub const ORDER_RESTED: &'static str = "market/order_rested";
+pub const AUCTION_CLOSED: &'static str = "market/auction_closed";
+pub const TRADING_HALTED: &'static str = "market/trading_halted";
+
+pub const ALL_EVENT_TYPES: [&'static str; 4] =
+    [TRADE, ORDER_RESTED, AUCTION_CLOSED, TRADING_HALTED];
+
+// A Sawtooth event as the market emits it. Attributes hold the fields subscribers can
+// filter on; `data` is the CBOR of the record the event describes. dmktc also writes
+// recorded events in this shape.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MarketEvent {
+    pub event_type: String,
+    pub attributes: Vec<(String, String)>,
+    pub data: Vec<u8>,
+}
+
+impl fmt::Display for MarketEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.event_type)?;
+        self.attributes
+            .iter()
+            .try_for_each(|(k, v)| write!(f, " {}={}", k, v))
+    }
+}
+
+impl MarketEvent {
+    pub fn trade(_trade: &Trade) -> Result<Self, Error> {
+        Ok(MarketEvent {
+            event_type: String::from(TRADE),
+            attributes: vec![
+                (String::from("seq"), _trade.seq.to_string()),
+                (String::from("block"), _trade.block.to_string()),
+                (String::from("instrument"), _trade.instrument.clone()),
+                (String::from("buyer"), _trade.buyer.to_string()),
+                (String::from("seller"), _trade.seller.to_string()),
+                (String::from("price"), _trade.price.to_string()),
+                (String::from("qty"), _trade.qty.to_string()),
+                (String::from("aggressor"), _trade.aggressor.to_string()),
+                (String::from("buy_id"), _trade.buy_id.clone()),
+                (String::from("sell_id"), _trade.sell_id.clone()),
+                (String::from("buyer_fee"), _trade.buyer_fee.to_string()),
+                (String::from("seller_fee"), _trade.seller_fee.to_string()),
+            ],
+            data: serde_cbor::to_vec(_trade)?,
+        })
+    }
+
+    // `qty` is what is left resting on the book, not the original order size. Events are
+    // public, so an iceberg order is described as an ordinary order for its shown quantity.
+    pub fn order_rested(_record: &OrderRecord) -> Result<Self, Error> {
+        let mut shown = _record.clone();
+        if let Some(v) = shown.peak.take() {
+            shown.qty = shown.filled + cmp::min(v, _record.qty - _record.filled);
+        }
+        let _record = &shown;
+        Ok(MarketEvent {
+            event_type: String::from(ORDER_RESTED),
+            attributes: vec![
+                (String::from("order_id"), _record.id.clone()),
+                (String::from("side"), _record.side.to_string()),
+                (String::from("instrument"), _record.instrument.clone()),
+                (String::from("owner"), _record.addr.to_string()),
+                (String::from("price"), _record.price.to_string()),
+                (
+                    String::from("qty"),
+                    (_record.qty - _record.filled).to_string(),
+                ),
+            ],
+            data: serde_cbor::to_vec(_record)?,
+        })
+    }
+
+    pub fn auction_closed(_result: &AuctionResult) -> Result<Self, Error> {
+        let winner = match _result.winner {
+            Some(ref v) => v.to_string(),
+            None => String::new(),
+        };
+        Ok(MarketEvent {
+            event_type: String::from(AUCTION_CLOSED),
+            attributes: vec![
+                (String::from("serial"), _result.serial.to_string()),
+                (String::from("sealed"), _result.sealed.to_string()),
+                (String::from("instrument"), _result.instrument.clone()),
+                (String::from("winner"), winner),
+                (String::from("price"), _result.price.get().to_string()),
+                (String::from("qty"), _result.qty.get().to_string()),
+            ],
+            data: serde_cbor::to_vec(_result)?,
+        })
+    }
+
+    pub fn trading_halted(_halt: &Halt) -> Result<Self, Error> {
+        Ok(MarketEvent {
+            event_type: String::from(TRADING_HALTED),
+            attributes: vec![
+                (String::from("instrument"), _halt.instrument.clone()),
+                (String::from("price"), _halt.price.to_string()),
+                (String::from("block"), _halt.block.to_string()),
+                (String::from("resumes_at"), _halt.resumes_at.to_string()),
+            ],
+            data: serde_cbor::to_vec(_halt)?,
+        })
+    }
+
+    pub fn get_attribute(&self, _key: &str) -> Option<&String> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == _key)
+            .map(|(_, v)| v)
+    }
+
+    // Every (key, value) filter has to match, as with Sawtooth's SIMPLE_ALL filters.
+    pub fn matches(&self, _filters: &Vec<(String, String)>) -> bool {
+        _filters
+            .iter()
+            .all(|(k, v)| self.get_attribute(k) == Some(v))
+    }
+}
@@ -0,0 +1,159 @@
+use address::Address;
+use amount::Amount;
+use errors::LibError;
+use failure::Error;
+use order::Side;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub const MAX_BPS: u64 = 10_000;
+
+// Maker and taker rates in basis points of the cash a trade changes hands for.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct FeeTier {
+    pub maker_bps: u64,
+    pub taker_bps: u64,
+}
+
+// Trading fees, stored at addressing::FEESCHEDULE. Both sides pay in cash, as part of
+// settling the trade (see settlement::settle): the buyer on top of what it pays, the seller
+// out of what it receives. The taker is the fill's aggressor, the maker the order it traded
+// against. Fees are rounded up, so no trade is too small to pay any, and credited to the
+// treasury account; nothing is charged until a treasury is set.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FeeSchedule {
+    pub maker_bps: u64,
+    pub taker_bps: u64,
+    pub treasury: Option<Address>,
+    // Rates for particular accounts, keyed by address, in place of the ones above.
+    pub tiers: BTreeMap<String, FeeTier>,
+}
+
+impl fmt::Display for FeeTier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "maker {} bps, taker {} bps",
+            self.maker_bps, self.taker_bps
+        )
+    }
+}
+
+impl fmt::Display for FeeSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.treasury {
+            Some(ref v) => write!(f, "Treasury: {}\n", v)?,
+            None => write!(f, "Treasury: none (no fees are charged)\n")?,
+        }
+        write!(f, "Default: {}\n", self.default_tier())?;
+        self.tiers
+            .iter()
+            .try_for_each(|(k, v)| write!(f, "    {}: {}\n", k, v))
+    }
+}
+
+impl FeeTier {
+    pub fn check(&self) -> Result<(), LibError> {
+        if self.maker_bps > MAX_BPS || self.taker_bps > MAX_BPS {
+            return Err(LibError::CustomError {
+                contents: format!(
+                    "Fee rates are in basis points and can be at most {}; got {}",
+                    MAX_BPS, self
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        FeeSchedule::new()
+    }
+}
+
+impl FeeSchedule {
+    pub fn new() -> Self {
+        FeeSchedule {
+            maker_bps: 0,
+            taker_bps: 0,
+            treasury: None,
+            tiers: BTreeMap::new(),
+        }
+    }
+
+    pub fn default_tier(&self) -> FeeTier {
+        FeeTier {
+            maker_bps: self.maker_bps,
+            taker_bps: self.taker_bps,
+        }
+    }
+
+    pub fn tier_for(&self, _addr: &Address) -> FeeTier {
+        match self.tiers.get(_addr.to_string_ref()) {
+            Some(v) => *v,
+            None => self.default_tier(),
+        }
+    }
+
+    pub fn check(&self) -> Result<(), LibError> {
+        self.default_tier().check()?;
+        self.tiers.values().try_for_each(|x| x.check())
+    }
+
+    // The buyer's and the seller's fee on a trade worth _cash between them.
+    pub fn fees_on(
+        &self,
+        _buyer: &Address,
+        _seller: &Address,
+        _aggressor: Side,
+        _cash: u64,
+    ) -> Result<(u64, u64), Error> {
+        if self.treasury.is_none() {
+            return Ok((0, 0));
+        }
+        let (buyer_bps, seller_bps) = match _aggressor {
+            Side::Buy => (
+                self.tier_for(_buyer).taker_bps,
+                self.tier_for(_seller).maker_bps,
+            ),
+            Side::Sell => (
+                self.tier_for(_buyer).maker_bps,
+                self.tier_for(_seller).taker_bps,
+            ),
+        };
+        Ok((fee(_cash, buyer_bps)?, fee(_cash, seller_bps)?))
+    }
+
+    // The rate a buy of _addr's holds for in fees while it rests: the higher of its maker
+    // and taker rates, as a resting order can trade as either in a call auction. A resting
+    // buy's fee is capped at what its hold has left once the trade is paid for, so a later
+    // rise in the schedule never leaves it short.
+    pub fn hold_bps(&self, _addr: &Address) -> u64 {
+        match self.treasury {
+            Some(_) => {
+                let tier = self.tier_for(_addr);
+                cmp::max(tier.maker_bps, tier.taker_bps)
+            }
+            None => 0,
+        }
+    }
+
+    // The most _addr can trade for out of _cash when taking, so the rest pays the fee.
+    pub fn spendable(&self, _addr: &Address, _cash: u64) -> Result<u64, Error> {
+        let bps = match self.treasury {
+            Some(_) => self.tier_for(_addr).taker_bps,
+            None => 0,
+        };
+        Ok(Amount::new(_cash)
+            .scale(MAX_BPS, MAX_BPS + bps, "spendable cash calculation")?
+            .get())
+    }
+}
+
+fn fee(_amt: u64, _bps: u64) -> Result<u64, Error> {
+    Ok(Amount::new(_amt)
+        .scale_up(_bps, MAX_BPS, "trading fee calculation")?
+        .get())
+}
@@ -1,165 +1,597 @@
 use sawtooth_sdk::processor::handler::{ApplyError, ContextError, TransactionContext};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::mem;
 #[allow(unused_imports)]
 #[allow(dead_code)]
 use std::fs::File;
 use std::io::prelude::*;
 
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_cbor;
 
 use errors::LibError;
+use events::MarketEvent;
 use failure::Error;
+use fees::FeeSchedule;
+use instrument::InstrumentRegistry;
 
+use address::Address;
 use addressing;
+use amount::Quantity;
 use auction::{Auction, AuctionList, Bid};
 use balancebook::BalanceBook;
-use order::{BuyOrder, OrderT, SellOrder};
+use blockinfo;
+use marketstatus::MarketStatus;
+use order::{BuyOrder, OrderT, SellOrder, Side};
 use orderbook::{fill_buy, fill_sell, OrderBook};
-use sealedbid::SealedAuctionList;
+use orderstatus::{OrderRecord, OrderStatusBook};
+use permissions::Permissions;
+use pricelevels::PriceLevels;
+use sealedbid::{SealedAuction, SealedAuctionList};
+use tradeledger::TradeLedger;
 use useracct::UserAccount;
 use wrapper::Wrapper;
 
+// What AUCTIONLIST and SEALEDAUCTIONLIST hold now that each auction has its own address.
+// `contents` keeps the auctions of a list stored whole before that; they are read from there
+// until first written back.
+#[derive(Serialize, Deserialize)]
+struct AuctionIndex<T> {
+    #[serde(default = "BTreeMap::new")]
+    contents: BTreeMap<u64, T>,
+    total_auctioned: Quantity,
+    #[serde(default)]
+    serials: BTreeSet<u64>,
+}
+
+impl<T> AuctionIndex<T> {
+    fn new() -> Self {
+        AuctionIndex {
+            contents: BTreeMap::new(),
+            total_auctioned: Quantity::default(),
+            serials: BTreeSet::new(),
+        }
+    }
+}
+
+// Transactions on the books declare just what the client found they touch (see
+// wrapper::Touches). If the state has moved on since, so that one touches an address it
+// didn't declare, it is rejected and can be resubmitted.
+fn context_error(_addr: &str, _op: &str, _e: ContextError) -> ApplyError {
+    match _e {
+        ContextError::AuthorizationError(e) => ApplyError::InvalidTransaction(format!(
+            "{} is not declared by the transaction, whose view of the state is out of date; resubmit it: {}\n",
+            _addr, e
+        )),
+        e => ApplyError::InternalError(format!(
+            "failed to {} state at {} in getset module: {:?}\n",
+            _op, _addr, e
+        )),
+    }
+}
+
 pub struct FamilyState<'a> {
     context: &'a mut TransactionContext,
+    // What each address this transaction has read holds, including its own writes.
+    cache: HashMap<String, Option<Vec<u8>>>,
+    // The balance book stored at BALANCEBOOK before accounts had their own addresses.
+    legacy_balances: Option<BalanceBook>,
+    // Likewise the order status book stored at ORDERSTATUS.
+    legacy_status: Option<OrderStatusBook>,
 }
 
 impl<'a> FamilyState<'a> {
     pub fn new(context: &'a mut TransactionContext) -> FamilyState {
-        FamilyState { context: context }
+        FamilyState {
+            context: context,
+            cache: HashMap::new(),
+            legacy_balances: None,
+            legacy_status: None,
+        }
     }
 
-    pub fn get_balancebook(&mut self) -> Result<BalanceBook, ApplyError> {
-        let ser_balancebook_response = self.context.get_state(addressing::BALANCEBOOK)?;
-        match ser_balancebook_response {
-            Some(contents) => {
-                let deser_balancebook: BalanceBook = match serde_cbor::from_slice(&contents[0..]) {
-                    Ok(v) => v,
-                    Err(e) => return Err(ApplyError::InternalError(format!("Error deserializing balancebook (serde_cbor::from_slice) in geset: {:?}\n.", e)))
-                };
-                Ok(deser_balancebook)
+    fn read(&mut self, _addr: &str) -> Result<Option<Vec<u8>>, ApplyError> {
+        if let Some(v) = self.cache.get(_addr) {
+            return Ok(v.clone());
+        }
+        let contents = match self.context.get_state(_addr) {
+            Ok(v) => v,
+            Err(e) => return Err(context_error(_addr, "get", e)),
+        };
+        self.cache.insert(String::from(_addr), contents.clone());
+        Ok(contents)
+    }
+
+    fn read_as<T: DeserializeOwned>(&mut self, _addr: &str, _what: &str) -> Result<Option<T>, ApplyError> {
+        match self.read(_addr)? {
+            Some(contents) => match serde_cbor::from_slice(&contents[0..]) {
+                Ok(v) => Ok(Some(v)),
+                Err(e) => Err(ApplyError::InternalError(format!(
+                    "Error deserializing {} (serde_cbor::from_slice) in geset: {:?}\n.",
+                    _what, e
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    // Addresses that already hold _data are left alone, so a transaction only writes what it
+    // changed and never an unchanged address it didn't declare as an output.
+    fn write(&mut self, _addr: &str, _data: Vec<u8>) -> Result<(), ApplyError> {
+        if self.read(_addr)?.as_ref() == Some(&_data) {
+            return Ok(());
+        }
+        if let Err(e) = self.context.set_state(_addr, &_data) {
+            return Err(context_error(_addr, "set", e));
+        }
+        self.cache.insert(String::from(_addr), Some(_data));
+        Ok(())
+    }
+
+    fn delete(&mut self, _addr: &str) -> Result<(), ApplyError> {
+        if self.read(_addr)?.is_none() {
+            return Ok(());
+        }
+        if let Err(e) = self.context.delete_state(_addr) {
+            return Err(context_error(_addr, "delete", e));
+        }
+        self.cache.insert(String::from(_addr), None);
+        Ok(())
+    }
+
+    fn load_legacy_balances(&mut self) -> Result<(), ApplyError> {
+        if self.legacy_balances.is_none() {
+            let legacy = self.read_as(addressing::BALANCEBOOK, "balancebook")?;
+            self.legacy_balances = Some(legacy.unwrap_or_else(BalanceBook::new));
+        }
+        Ok(())
+    }
+
+    fn legacy_account(&mut self, _addr: &Address) -> Result<Option<UserAccount>, ApplyError> {
+        self.load_legacy_balances()?;
+        Ok(self.legacy_balances.as_ref().and_then(|x| x.get_by_addr(_addr)).cloned())
+    }
+
+    pub fn get_account_index(&mut self) -> Result<BTreeSet<Address>, ApplyError> {
+        let index = self.read_as(addressing::ACCOUNTINDEX, "account index")?;
+        Ok(index.unwrap_or_else(BTreeSet::new))
+    }
+
+    // A balance book of just the accounts of _addrs, which must cover every account the
+    // caller may touch. Addresses without an account are left out.
+    pub fn get_balancebook_for(&mut self, _addrs: &[Address]) -> Result<BalanceBook, ApplyError> {
+        let mut bb = BalanceBook::new();
+        for addr in _addrs.iter() {
+            let acct = match self.read_as(&addressing::account_addr(addr), "user account")? {
+                Some(v) => Some(v),
+                None => self.legacy_account(addr)?,
+            };
+            if let Some(v) = acct {
+                bb.balance_book.insert(addr.to_string(), v);
             }
-            None => Ok(BalanceBook::new()),
         }
+        Ok(bb)
     }
 
-    pub fn get_orderbook(&mut self) -> Result<OrderBook, ApplyError> {
-        let ser_orderbook_response = self.context.get_state(addressing::ORDERBOOK)?;
-        match ser_orderbook_response {
-            Some(contents) => {
-                let deser_orderbook: OrderBook = match serde_cbor::from_slice(&contents[0..]) {
-                    Ok(v) => v,
-                    Err(e) => return Err(ApplyError::InternalError(format!(
-                        "Error deserializing orderbook (serde_cbor::from_slice) in geset: {:?}\n.",
-                        e
-                    ))),
-                };
+    // The book's own address holds it with empty sides and the prices of its levels, which
+    // are read from their own addresses. A book stored whole before that reads as it is.
+    pub fn get_orderbook(&mut self, _instrument: &str) -> Result<OrderBook, ApplyError> {
+        let mut ob = self.get_book_header(_instrument)?;
+        for price in ob.unread_buys.clone().into_iter() {
+            self.read_level(&mut ob, Side::Buy, price)?;
+        }
+        for price in ob.unread_sells.clone().into_iter() {
+            self.read_level(&mut ob, Side::Sell, price)?;
+        }
+        Ok(ob)
+    }
+
+    // Just the book's own address. Its levels are read with read_level as they are needed;
+    // set_orderbook leaves the ones never read as they are.
+    pub fn get_book_header(&mut self, _instrument: &str) -> Result<OrderBook, ApplyError> {
+        let mut ob: OrderBook = match self.read_as(&addressing::orderbook_addr(_instrument), "orderbook")? {
+            Some(v) => v,
+            None => OrderBook::for_instrument(String::from(_instrument)),
+        };
+        let instruments = self.get_instruments()?;
+        ob.precision = instruments.precision(_instrument);
+        ob.matching = instruments.get(_instrument).map(|x| x.matching).unwrap_or_default();
+        ob.mode = instruments.get(_instrument).map(|x| x.mode).unwrap_or_default();
+        ob.unread_buys = mem::replace(&mut ob.buy_levels, Vec::new()).into_iter().collect();
+        ob.unread_sells = mem::replace(&mut ob.sell_levels, Vec::new()).into_iter().collect();
+        Ok(ob)
+    }
 
-                Ok(deser_orderbook)
+    // Levels already read, or not stored, are left alone.
+    pub fn read_level(&mut self, _ob: &mut OrderBook, _side: Side, _price: u64) -> Result<(), ApplyError> {
+        let addr = addressing::level_addr(&_ob.instrument, _side, _price);
+        match _side {
+            Side::Buy => {
+                if _ob.unread_buys.remove(&_price) {
+                    let level: Option<Vec<BuyOrder>> = self.read_as(&addr, "price level")?;
+                    level.unwrap_or_default().into_iter().for_each(|x| _ob.buy_orders.push(x));
+                }
+            }
+            Side::Sell => {
+                if _ob.unread_sells.remove(&_price) {
+                    let level: Option<Vec<SellOrder>> = self.read_as(&addr, "price level")?;
+                    level.unwrap_or_default().into_iter().for_each(|x| _ob.sell_orders.push(x));
+                }
             }
-            None => Ok(OrderBook::new()),
         }
+        Ok(())
     }
 
-    pub fn get_auctionlist(&mut self) -> Result<AuctionList, ApplyError> {
-        let ser_auctionlist_response = self.context.get_state(addressing::AUCTIONLIST)?;
-        match ser_auctionlist_response {
-            Some(contents) => {
-                let deser_auctionlist: AuctionList = match serde_cbor::from_slice(&contents[0..]) {
-                    Ok(v) => v,
-                    Err(e) => return Err(ApplyError::InternalError(format!(
-                        "Error deserializing orderbook (serde_cbor::from_slice) in geset: {:?}\n.",
-                        e
-                    ))),
-                };
+    // Writes the levels that changed, removes the ones that emptied and returns the prices
+    // left. The _unread levels were never read, so they are kept as they are.
+    fn set_levels<T: OrderT + Serialize>(
+        &mut self,
+        _instrument: &str,
+        _side: Side,
+        _orders: Vec<T>,
+        _stored: &[u64],
+        _unread: &BTreeSet<u64>,
+    ) -> Result<Vec<u64>, ApplyError> {
+        let mut levels: BTreeMap<u64, Vec<T>> = BTreeMap::new();
+        for order in _orders.into_iter() {
+            levels.entry(order.get_price()).or_insert_with(Vec::new).push(order);
+        }
+        for price in _stored.iter().filter(|x| !levels.contains_key(x) && !_unread.contains(x)) {
+            self.delete(&addressing::level_addr(_instrument, _side, *price))?;
+        }
+        for (price, orders) in levels.iter() {
+            self.write(&addressing::level_addr(_instrument, _side, *price), serde_cbor::to_vec(orders)?)?;
+        }
+        let mut prices: BTreeSet<u64> = levels.keys().cloned().collect();
+        prices.extend(_unread.iter().cloned());
+        Ok(prices.into_iter().collect())
+    }
+
+    fn get_auctions<T: DeserializeOwned>(
+        &mut self,
+        _list: &str,
+        _addr: fn(u64) -> String,
+        _only: Option<u64>,
+    ) -> Result<(BTreeMap<u64, T>, Quantity), ApplyError> {
+        let index: AuctionIndex<T> = self.read_as(_list, "auction list")?.unwrap_or_else(AuctionIndex::new);
+        let serials: BTreeSet<u64> = index
+            .serials
+            .iter()
+            .chain(index.contents.keys())
+            .cloned()
+            .filter(|x| _only.map_or(true, |v| v == *x))
+            .collect();
+        let mut legacy = index.contents;
+        let mut contents = BTreeMap::new();
+        for serial in serials.into_iter() {
+            let auction = match self.read_as(&_addr(serial), "auction")? {
+                Some(v) => Some(v),
+                None => legacy.remove(&serial),
+            };
+            if let Some(v) = auction {
+                contents.insert(serial, v);
+            }
+        }
+        Ok((contents, index.total_auctioned))
+    }
+
+    // The list itself is only rewritten when an auction is added or the total changes.
+    fn set_auctions<T: Serialize + DeserializeOwned>(
+        &mut self,
+        _list: &str,
+        _addr: fn(u64) -> String,
+        _contents: &BTreeMap<u64, T>,
+        _total_auctioned: Quantity,
+    ) -> Result<(), ApplyError> {
+        let mut index: AuctionIndex<T> = self.read_as(_list, "auction list")?.unwrap_or_else(AuctionIndex::new);
+        let mut changed = index.total_auctioned != _total_auctioned;
+        for (serial, auction) in _contents.iter() {
+            self.write(&_addr(*serial), serde_cbor::to_vec(auction)?)?;
+            if !index.contents.contains_key(serial) {
+                changed |= index.serials.insert(*serial);
+            }
+        }
+        if !changed && self.read(_list)?.is_some() {
+            return Ok(());
+        }
+        index.total_auctioned = _total_auctioned;
+        self.write(_list, serde_cbor::to_vec(&index)?)
+    }
 
-                Ok(deser_auctionlist)
+    fn clear_auctions<T: Serialize + DeserializeOwned>(
+        &mut self,
+        _list: &str,
+        _addr: fn(u64) -> String,
+    ) -> Result<(), ApplyError> {
+        let index: AuctionIndex<T> = self.read_as(_list, "auction list")?.unwrap_or_else(AuctionIndex::new);
+        for serial in index.serials.iter().chain(index.contents.keys()) {
+            self.delete(&_addr(*serial))?;
+        }
+        self.write(_list, serde_cbor::to_vec(&AuctionIndex::<T>::new())?)
+    }
+
+    // A status book of just the records of _orders, (owner, ID) pairs, which must cover
+    // every record the caller may touch. Orders without a record are left out.
+    pub fn get_orderstatus(&mut self, _orders: &[(Address, String)]) -> Result<OrderStatusBook, ApplyError> {
+        let mut status = OrderStatusBook::new();
+        for (owner, id) in _orders.iter() {
+            let record = match self.read_as(&addressing::order_status_addr(owner, id), "order status record")? {
+                Some(v) => Some(v),
+                None => self.legacy_record(id)?,
+            };
+            if let Some(v) = record {
+                status.records.insert(id.clone(), v);
             }
-            None => Ok(AuctionList::new()),
         }
+        Ok(status)
+    }
+
+    fn legacy_record(&mut self, _id: &String) -> Result<Option<OrderRecord>, ApplyError> {
+        if self.legacy_status.is_none() {
+            let legacy = self.read_as(addressing::ORDERSTATUS, "order status book")?;
+            self.legacy_status = Some(legacy.unwrap_or_else(OrderStatusBook::new));
+        }
+        Ok(self.legacy_status.as_ref().and_then(|x| x.get(_id)).cloned())
+    }
+
+    pub fn get_auctionlist(&mut self) -> Result<AuctionList, ApplyError> {
+        let (contents, total_auctioned) = self.get_auctions(addressing::AUCTIONLIST, addressing::auction_addr, None)?;
+        Ok(AuctionList { contents: contents, total_auctioned: total_auctioned })
+    }
+
+    // The list with just the auction _serial in it, if there is one.
+    pub fn get_auction(&mut self, _serial: u64) -> Result<AuctionList, ApplyError> {
+        let (contents, total_auctioned) = self.get_auctions(addressing::AUCTIONLIST, addressing::auction_addr, Some(_serial))?;
+        Ok(AuctionList { contents: contents, total_auctioned: total_auctioned })
     }
 
     pub fn get_sealed_auctionlist(&mut self) -> Result<SealedAuctionList, ApplyError> {
-        let ser_auctionlist_response = self.context.get_state(addressing::SEALEDAUCTIONLIST)?;
-        match ser_auctionlist_response {
+        let (contents, total_auctioned) = self.get_auctions(addressing::SEALEDAUCTIONLIST, addressing::sealed_auction_addr, None)?;
+        Ok(SealedAuctionList { contents: contents, total_auctioned: total_auctioned })
+    }
+
+    pub fn get_sealed_auction(&mut self, _serial: u64) -> Result<SealedAuctionList, ApplyError> {
+        let (contents, total_auctioned) = self.get_auctions(addressing::SEALEDAUCTIONLIST, addressing::sealed_auction_addr, Some(_serial))?;
+        Ok(SealedAuctionList { contents: contents, total_auctioned: total_auctioned })
+    }
+
+    // Writes each account to its own address. Accounts that were never stored before are
+    // added to the account index, which is how the whole book can be found again.
+    pub fn set_balancebook(&mut self, _balance_book: BalanceBook) -> Result<(), ApplyError> {
+        let mut added: Vec<Address> = Vec::new();
+        for acct in _balance_book.balance_book.values() {
+            let addr = addressing::account_addr(&acct.addr);
+            if self.read(&addr)?.is_none() && self.legacy_account(&acct.addr)?.is_none() {
+                added.push(acct.addr.clone());
+            }
+            self.write(&addr, serde_cbor::to_vec(acct)?)?;
+        }
+        if !added.is_empty() {
+            let mut index = self.get_account_index()?;
+            index.extend(added.into_iter());
+            self.write(addressing::ACCOUNTINDEX, serde_cbor::to_vec(&index)?)?;
+        }
+        Ok(())
+    }
+
+    // The address of every account, wherever it is stored.
+    pub fn get_account_addrs(&mut self) -> Result<Vec<Address>, ApplyError> {
+        let mut addrs = self.get_account_index()?;
+        self.load_legacy_balances()?;
+        if let Some(ref legacy) = self.legacy_balances {
+            addrs.extend(legacy.balance_book.values().map(|x| x.addr.clone()));
+        }
+        Ok(addrs.into_iter().collect())
+    }
+
+    // Removes every account, wherever it is stored.
+    pub fn clear_balancebook(&mut self) -> Result<(), ApplyError> {
+        for addr in self.get_account_addrs()?.iter() {
+            self.delete(&addressing::account_addr(addr))?;
+        }
+        self.legacy_balances = Some(BalanceBook::new());
+        self.write(addressing::BALANCEBOOK, serde_cbor::to_vec(&BalanceBook::new())?)?;
+        self.write(addressing::ACCOUNTINDEX, serde_cbor::to_vec(&BTreeSet::<Address>::new())?)
+    }
+
+    pub fn set_orderbook(&mut self, _order_book: OrderBook) -> Result<(), ApplyError> {
+        let mut ob = _order_book;
+        let instrument = ob.instrument.clone();
+        let (stored_buys, stored_sells) = match self.read_as::<OrderBook>(&addressing::orderbook_addr(&instrument), "orderbook")? {
+            Some(v) => (v.buy_levels, v.sell_levels),
+            None => (Vec::new(), Vec::new()),
+        };
+        let buys = mem::replace(&mut ob.buy_orders, PriceLevels::new()).into_vec();
+        let sells = mem::replace(&mut ob.sell_orders, PriceLevels::new()).into_vec();
+        let (unread_buys, unread_sells) = (mem::replace(&mut ob.unread_buys, BTreeSet::new()), mem::replace(&mut ob.unread_sells, BTreeSet::new()));
+        ob.buy_levels = self.set_levels(&instrument, Side::Buy, buys, &stored_buys, &unread_buys)?;
+        ob.sell_levels = self.set_levels(&instrument, Side::Sell, sells, &stored_sells, &unread_sells)?;
+        self.write(&addressing::orderbook_addr(&instrument), serde_cbor::to_vec(&ob)?)
+    }
+
+    // Each trade goes to its own address; the ledger's are new, so nothing is overwritten.
+    pub fn set_tradeledger(&mut self, _tradeledger: TradeLedger) -> Result<(), ApplyError> {
+        for trade in _tradeledger.trades.iter() {
+            self.write(&addressing::trade_addr(&trade.instrument, trade.seq), serde_cbor::to_vec(trade)?)?;
+        }
+        Ok(())
+    }
+
+    // Each record goes to its own address, which is left alone if it already holds it.
+    pub fn set_orderstatus(&mut self, _orderstatus: OrderStatusBook) -> Result<(), ApplyError> {
+        for record in _orderstatus.records.values() {
+            self.write(&addressing::order_status_addr(&record.addr, &record.id), serde_cbor::to_vec(record)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_auctionlist(&mut self, _auctionlist: AuctionList) -> Result<(), ApplyError> {
+        self.set_auctions(addressing::AUCTIONLIST, addressing::auction_addr, &_auctionlist.contents, _auctionlist.total_auctioned)
+    }
+
+    pub fn clear_auctionlist(&mut self) -> Result<(), ApplyError> {
+        self.clear_auctions::<Auction>(addressing::AUCTIONLIST, addressing::auction_addr)
+    }
+
+    pub fn set_sealed_auctionlist(
+        &mut self,
+        _sealedlist: SealedAuctionList,
+    ) -> Result<(), ApplyError> {
+        self.set_auctions(addressing::SEALEDAUCTIONLIST, addressing::sealed_auction_addr, &_sealedlist.contents, _sealedlist.total_auctioned)
+    }
+
+    pub fn clear_sealed_auctionlist(&mut self) -> Result<(), ApplyError> {
+        self.clear_auctions::<SealedAuction>(addressing::SEALEDAUCTIONLIST, addressing::sealed_auction_addr)
+    }
+
+    // The number of the block this transaction is in; see blockinfo::block_num. Block Info
+    // is a requirement of the market, as the README says.
+    pub fn get_block_num(&mut self) -> Result<u64, ApplyError> {
+        let config = self.read(addressing::BLOCKINFO_CONFIG)?;
+        match blockinfo::block_num(config.as_ref().map(|v| &v[..])) {
+            Ok(v) => Ok(v),
+            Err(LibError::NExistKeyError { .. }) => Err(ApplyError::InvalidTransaction(String::from(
+                "No block number is recorded at the Block Info config; the market needs the Block Info family running alongside it (see README)\n",
+            ))),
+            Err(e) => Err(ApplyError::InternalError(format!(
+                "{} in getset -> get_block_num\n",
+                e
+            ))),
+        }
+    }
+
+    pub fn get_marketstatus(&mut self) -> Result<MarketStatus, ApplyError> {
+        let ser_status_response = self.context.get_state(addressing::MARKETSTATUS)?;
+        match ser_status_response {
             Some(contents) => {
-                let deser_auctionlist: SealedAuctionList = match serde_cbor::from_slice(
-                    &contents[0..],
-                ) {
+                let deser_status: MarketStatus = match serde_cbor::from_slice(&contents[0..]) {
                     Ok(v) => v,
                     Err(e) => return Err(ApplyError::InternalError(format!(
-                        "Error deserializing orderbook (serde_cbor::from_slice) in geset: {:?}\n.",
+                        "Error deserializing market status (serde_cbor::from_slice) in geset: {:?}\n.",
                         e
                     ))),
                 };
 
-                Ok(deser_auctionlist)
+                Ok(deser_status)
             }
-            None => Ok(SealedAuctionList::new()),
+            None => Ok(MarketStatus::new()),
         }
     }
 
-    pub fn set_balancebook(&mut self, _balance_book: BalanceBook) -> Result<(), ApplyError> {
-        let balancebook_cbor = serde_cbor::to_vec(&_balance_book)?;
-        //        info!("location getset: calling set balancebook with: {}\n", _balance_book);
+    pub fn set_marketstatus(&mut self, _status: MarketStatus) -> Result<(), ApplyError> {
+        let status_cbor = serde_cbor::to_vec(&_status)?;
         match self
             .context
-            .set_state(addressing::BALANCEBOOK, &balancebook_cbor)
+            .set_state(addressing::MARKETSTATUS, &status_cbor)
         {
             Ok(_) => Ok(()),
             Err(e) => {
                 return Err(ApplyError::InternalError(format!(
-                    "failed to execute set_balancebook in getset module: {:?}\n",
+                    "failed to execute set_marketstatus in getset module: {:?}\n",
                     e
                 )))
             }
         }
     }
 
-    pub fn set_orderbook(&mut self, _order_book: OrderBook) -> Result<(), ApplyError> {
-        let orderbook_cbor = serde_cbor::to_vec(&_order_book)?;
+    pub fn get_instruments(&mut self) -> Result<InstrumentRegistry, ApplyError> {
+        let ser_instruments_response = self.context.get_state(addressing::INSTRUMENTS)?;
+        match ser_instruments_response {
+            Some(contents) => {
+                let deser_instruments: InstrumentRegistry = match serde_cbor::from_slice(&contents[0..]) {
+                    Ok(v) => v,
+                    Err(e) => return Err(ApplyError::InternalError(format!(
+                        "Error deserializing instrument registry (serde_cbor::from_slice) in geset: {:?}\n.",
+                        e
+                    ))),
+                };
+
+                Ok(deser_instruments)
+            }
+            None => Ok(InstrumentRegistry::new()),
+        }
+    }
+
+    pub fn set_instruments(&mut self, _instruments: InstrumentRegistry) -> Result<(), ApplyError> {
+        let instruments_cbor = serde_cbor::to_vec(&_instruments)?;
         match self
             .context
-            .set_state(addressing::ORDERBOOK, &orderbook_cbor)
+            .set_state(addressing::INSTRUMENTS, &instruments_cbor)
         {
             Ok(_) => Ok(()),
             Err(e) => {
                 return Err(ApplyError::InternalError(format!(
-                    "failed to execute set_orderbook in getset module: {:?}\n",
+                    "failed to execute set_instruments in getset module: {:?}\n",
                     e
                 )))
             }
         }
     }
 
-    pub fn set_auctionlist(&mut self, _auctionlist: AuctionList) -> Result<(), ApplyError> {
-        let auctionlist_cbor = serde_cbor::to_vec(&_auctionlist)?;
+    pub fn get_fees(&mut self) -> Result<FeeSchedule, ApplyError> {
+        let ser_fees_response = self.context.get_state(addressing::FEESCHEDULE)?;
+        match ser_fees_response {
+            Some(contents) => {
+                let deser_fees: FeeSchedule = match serde_cbor::from_slice(&contents[0..]) {
+                    Ok(v) => v,
+                    Err(e) => return Err(ApplyError::InternalError(format!(
+                        "Error deserializing fee schedule (serde_cbor::from_slice) in geset: {:?}\n.",
+                        e
+                    ))),
+                };
+
+                Ok(deser_fees)
+            }
+            None => Ok(FeeSchedule::new()),
+        }
+    }
+
+    pub fn set_fees(&mut self, _fees: FeeSchedule) -> Result<(), ApplyError> {
+        let fees_cbor = serde_cbor::to_vec(&_fees)?;
         match self
             .context
-            .set_state(addressing::AUCTIONLIST, &auctionlist_cbor)
+            .set_state(addressing::FEESCHEDULE, &fees_cbor)
         {
             Ok(_) => Ok(()),
             Err(e) => {
                 return Err(ApplyError::InternalError(format!(
-                    "failed to execute set_auctionlist in getset module: {:?}\n",
+                    "failed to execute set_fees in getset module: {:?}\n",
                     e
                 )))
             }
         }
     }
 
-    pub fn set_sealed_auctionlist(
-        &mut self,
-        _sealedlist: SealedAuctionList,
-    ) -> Result<(), ApplyError> {
-        let auctionlist_cbor = serde_cbor::to_vec(&_sealedlist)?;
+    // Falls back to the bootstrap admin until a permission list exists on-chain.
+    pub fn get_permissions(&mut self) -> Result<Permissions, ApplyError> {
+        let ser_permissions_response = self.context.get_state(addressing::PERMISSIONS)?;
+        match ser_permissions_response {
+            Some(contents) => {
+                let deser_permissions: Permissions = match serde_cbor::from_slice(&contents[0..]) {
+                    Ok(v) => v,
+                    Err(e) => return Err(ApplyError::InternalError(format!(
+                        "Error deserializing permissions (serde_cbor::from_slice) in geset: {:?}\n.",
+                        e
+                    ))),
+                };
+
+                Ok(deser_permissions)
+            }
+            None => Ok(Permissions::new()),
+        }
+    }
+
+    pub fn set_permissions(&mut self, _permissions: Permissions) -> Result<(), ApplyError> {
+        let permissions_cbor = serde_cbor::to_vec(&_permissions)?;
         match self
             .context
-            .set_state(addressing::SEALEDAUCTIONLIST, &auctionlist_cbor)
+            .set_state(addressing::PERMISSIONS, &permissions_cbor)
         {
             Ok(_) => Ok(()),
             Err(e) => {
                 return Err(ApplyError::InternalError(format!(
-                    "failed to execute set sealed auctionlist in getset module: {:?}\n",
+                    "failed to execute set_permissions in getset module: {:?}\n",
                     e
                 )))
             }
@@ -180,4 +612,19 @@ impl<'a> FamilyState<'a> {
             }
         }
     }
+
+    pub fn add_event(&mut self, _event: MarketEvent) -> Result<(), ApplyError> {
+        match self
+            .context
+            .add_event(_event.event_type, _event.attributes, &_event.data)
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                return Err(ApplyError::InternalError(format!(
+                    "failed to execute add_event in getset module: {:?}\n",
+                    e
+                )))
+            }
+        }
+    }
 }
@@ -75,25 +75,41 @@ impl TransactionHandler for FamilyHandler {
             }
         };
 
+        // Clearing state, auction management and permission changes are restricted
+        // to the keys in the on-chain permission list.
+        if deserd_payload.requires_admin() {
+            routes::admin_check(&mut family_state, &msg_author_pubkey)?;
+        }
+
         let exec_result = match deserd_payload {
             Wrapper::Ua(_acct) => {
-                match routes::acct_route(&mut family_state, _acct) {
+                match routes::acct_route(&mut family_state, _acct, &msg_author_addr, &msg_author_pubkey) {
                     Ok(v) =>  /*return*/ Ok(()),
+                    Err(ApplyError::InvalidTransaction(e)) => Err(ApplyError::InvalidTransaction(e)),
                     Err(e) => /*return*/ Err(ApplyError::InternalError(format!("Error executing add useraccount match route in handler: {:?}\n", e))),
                 }
             }
             Wrapper::Bo(_buyorder) => {
-                match routes::bo_route(&mut family_state, _buyorder) {
+                match routes::bo_route(&mut family_state, _buyorder, &msg_author_addr, request.get_signature()) {
                     Ok(v) =>  /*return*/  Ok(()),
+                    Err(ApplyError::InvalidTransaction(e)) => Err(ApplyError::InvalidTransaction(e)),
                     Err(e) => /*return*/ Err(ApplyError::InternalError(format!("Error executing buyorder match route in handler: {:?}\n", e))),
                 }
             }
             Wrapper::So(_sellorder) => {
-                match routes::so_route(&mut family_state, _sellorder) {
+                match routes::so_route(&mut family_state, _sellorder, &msg_author_addr, request.get_signature()) {
                     Ok(v) =>  /*return*/  Ok(()),
+                    Err(ApplyError::InvalidTransaction(e)) => Err(ApplyError::InvalidTransaction(e)),
                     Err(e) => /*return*/ Err(ApplyError::InternalError(format!("Error executing sellorder match route in handler: {:?}\n", e))),
                 }
             }
+            Wrapper::St(_stop) => {
+                match routes::st_route(&mut family_state, _stop, &msg_author_addr, request.get_signature()) {
+                    Ok(v) =>  /*return*/  Ok(()),
+                    Err(ApplyError::InvalidTransaction(e)) => Err(ApplyError::InvalidTransaction(e)),
+                    Err(e) => /*return*/ Err(ApplyError::InternalError(format!("Error executing stop order match route in handler: {:?}\n", e))),
+                }
+            }
             Wrapper::Na(_newauction) => {
                 match routes::na_route(&mut family_state, _newauction) {
                     Ok(v) =>  /*return*/  Ok(()),
@@ -101,8 +117,9 @@ impl TransactionHandler for FamilyHandler {
                 }
             }
             Wrapper::Ab(_bid) => {
-                match routes::ab_route(&mut family_state, _bid) {
+                match routes::ab_route(&mut family_state, _bid, &msg_author_addr) {
                     Ok(v) =>  /*return*/  Ok(()),
+                    Err(ApplyError::InvalidTransaction(e)) => Err(ApplyError::InvalidTransaction(e)),
                     Err(e) => /*return*/ Err(ApplyError::InternalError(format!("Error executing bid match route in handler: {:?}\n", e))),
                 }
             }
@@ -112,6 +129,20 @@ impl TransactionHandler for FamilyHandler {
                     Err(e) => /*return*/ Err(ApplyError::InternalError(format!("Error executing end auction match route in handler: {:?}\n", e))),
                 }
             }
+            Wrapper::Cn(_cancel) => {
+                match routes::cn_route(&mut family_state, _cancel, &msg_author_addr) {
+                    Ok(v) =>  /*return*/  Ok(()),
+                    Err(ApplyError::InvalidTransaction(e)) => Err(ApplyError::InvalidTransaction(e)),
+                    Err(e) => /*return*/ Err(ApplyError::InternalError(format!("Error executing cancel order match route in handler: {:?}\n", e))),
+                }
+            }
+            Wrapper::Md(_amend) => {
+                match routes::md_route(&mut family_state, _amend, &msg_author_addr) {
+                    Ok(v) =>  /*return*/  Ok(()),
+                    Err(ApplyError::InvalidTransaction(e)) => Err(ApplyError::InvalidTransaction(e)),
+                    Err(e) => /*return*/ Err(ApplyError::InternalError(format!("Error executing amend order match route in handler: {:?}\n", e))),
+                }
+            }
             Wrapper::Co => {
                 match routes::co_route(&mut family_state) {
                     Ok(v) =>  /*return*/  Ok(()),
@@ -143,8 +174,9 @@ impl TransactionHandler for FamilyHandler {
                 }
             }
             Wrapper::Ub(_unsealedbid) => {
-                match routes::ub_route(&mut family_state, _unsealedbid) {
+                match routes::ub_route(&mut family_state, _unsealedbid, &msg_author_addr) {
                     Ok(v) =>  /*return*/  Ok(()),
+                    Err(ApplyError::InvalidTransaction(e)) => Err(ApplyError::InvalidTransaction(e)),
                     Err(e) => /*return*/ Err(ApplyError::InternalError(format!("Error executing 'unsealed bid' route handler in handler.rs: {:?}\n", e)))
                 }
             }
@@ -160,11 +192,30 @@ impl TransactionHandler for FamilyHandler {
                     Err(e) => /*return*/ Err(ApplyError::InternalError(format!("Error executing 'clear sealed auctionlist' route handler in handler.rs: {:?}\n", e)))
                 }
             }
-            _ => unreachable!(),
+            Wrapper::Pm(_msg) => {
+                match routes::pm_route(&mut family_state, _msg) {
+                    Ok(v) =>  /*return*/  Ok(()),
+                    Err(ApplyError::InvalidTransaction(e)) => Err(ApplyError::InvalidTransaction(e)),
+                    Err(e) => /*return*/ Err(ApplyError::InternalError(format!("Error executing 'permission message' route handler in handler.rs: {:?}\n", e)))
+                }
+            }
+            Wrapper::Am(_adminmsg) => {
+                match routes::am_route(&mut family_state, _adminmsg) {
+                    Ok(v) =>  /*return*/  Ok(()),
+                    Err(ApplyError::InvalidTransaction(e)) => Err(ApplyError::InvalidTransaction(e)),
+                    Err(e) => /*return*/ Err(ApplyError::InternalError(format!("Error executing 'admin message' route handler in handler.rs: {:?}\n", e)))
+                }
+            }
         };
 
         match exec_result {
             Ok(v) => return Ok(()),
+            // Invalid transactions (e.g. signer/payload address mismatch) are rejected
+            // so the submitter can see why; anything else is logged as before.
+            Err(ApplyError::InvalidTransaction(e)) => {
+                info!("transaction rejected: {}\n", e);
+                return Err(ApplyError::InvalidTransaction(e));
+            }
             Err(e) => {
                 info!("transaction processing experienced error: {:?}\n", e);
                 return Ok(());
@@ -0,0 +1,288 @@
+use decimal::{Precision, MAX_PLACES};
+use errors::LibError;
+use failure::Error;
+use matching::MatchingAlgorithm;
+use pricelimits::{CircuitBreaker, PriceBand};
+use rules::TradingRules;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+// Symbol of the commodity the market traded before instruments existed. Orders, auctions
+// and balance changes that don't name an instrument refer to it.
+pub const DEFAULT_INSTRUMENT: &'static str = "COMMODITY";
+
+pub fn default_instrument() -> String {
+    String::from(DEFAULT_INSTRUMENT)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Instrument {
+    pub symbol: String,
+    pub description: String,
+    #[serde(default)]
+    pub mode: MarketMode,
+    #[serde(default)]
+    pub matching: MatchingAlgorithm,
+    #[serde(default)]
+    pub rules: TradingRules,
+    #[serde(default)]
+    pub band: Option<PriceBand>,
+    #[serde(default)]
+    pub breaker: Option<CircuitBreaker>,
+    // Decimal places of the instrument's prices and quantities. Orders, bids and holdings
+    // store whole numbers of the smallest unit; 0 keeps them whole.
+    #[serde(default)]
+    pub price_places: u32,
+    #[serde(default)]
+    pub qty_places: u32,
+}
+
+// How an instrument's order book matches. Continuous books match every order as it
+// arrives. Call books only collect orders; they are matched in batches by an admin
+// CallAuction message, all at one clearing price (see callauction).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarketMode {
+    Continuous,
+    Call,
+}
+
+impl Default for MarketMode {
+    fn default() -> Self {
+        MarketMode::Continuous
+    }
+}
+
+impl fmt::Display for MarketMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MarketMode::Continuous => write!(f, "continuous"),
+            MarketMode::Call => write!(f, "call"),
+        }
+    }
+}
+
+impl FromStr for MarketMode {
+    type Err = LibError;
+
+    fn from_str(_s: &str) -> Result<Self, LibError> {
+        match _s {
+            "continuous" => Ok(MarketMode::Continuous),
+            "call" => Ok(MarketMode::Call),
+            _ => Err(LibError::CustomError {
+                contents: format!("Unknown market mode {}; expected continuous or call", _s),
+            }),
+        }
+    }
+}
+
+// Instruments that can be traded and auctioned, keyed by symbol.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InstrumentRegistry {
+    pub instruments: BTreeMap<String, Instrument>,
+    // Decimal places of cash, which every instrument is priced in.
+    #[serde(default)]
+    pub cash_places: u32,
+}
+
+impl fmt::Display for Instrument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} ({})\n    {}\n",
+            self.symbol, self.description, self.mode, self.rules
+        )?;
+        if self.matching != MatchingAlgorithm::Fifo {
+            write!(f, "    {} matching\n", self.matching)?;
+        }
+        if self.price_places != 0 || self.qty_places != 0 {
+            write!(
+                f,
+                "    price places: {}, quantity places: {}\n",
+                self.price_places, self.qty_places
+            )?;
+        }
+        if let Some(ref v) = self.band {
+            write!(f, "    {}\n", v)?;
+        }
+        if let Some(ref v) = self.breaker {
+            write!(f, "    {}\n", v)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for InstrumentRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.instruments
+            .values()
+            .try_for_each(|x| write!(f, "{}", x))
+    }
+}
+
+impl Instrument {
+    pub fn from_vals(_symbol: String, _description: String) -> Result<Self, LibError> {
+        if _symbol.len() == 0 {
+            return Err(LibError::EmptyZeroError {
+                field: String::from("Instrument symbol"),
+            });
+        } else if _symbol.len() > 16 {
+            return Err(LibError::CustomError {
+                contents: String::from("Instrument symbols must be at most 16 characters"),
+            });
+        } else if !_symbol
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(LibError::EncodingError {
+                field: String::from("Instrument symbol"),
+                encoding: String::from("ASCII alphanumeric, '-' and '_'"),
+                got: _symbol,
+            });
+        }
+        Ok(Instrument {
+            symbol: _symbol,
+            description: _description,
+            mode: MarketMode::Continuous,
+            matching: MatchingAlgorithm::Fifo,
+            rules: TradingRules::default(),
+            band: None,
+            breaker: None,
+            price_places: 0,
+            qty_places: 0,
+        })
+    }
+}
+
+impl InstrumentRegistry {
+    pub fn new() -> Self {
+        let mut instruments = BTreeMap::new();
+        instruments.insert(
+            default_instrument(),
+            Instrument {
+                symbol: default_instrument(),
+                description: String::from("Default commodity"),
+                mode: MarketMode::Continuous,
+                matching: MatchingAlgorithm::Fifo,
+                rules: TradingRules::default(),
+                band: None,
+                breaker: None,
+                price_places: 0,
+                qty_places: 0,
+            },
+        );
+        InstrumentRegistry {
+            instruments: instruments,
+            cash_places: 0,
+        }
+    }
+
+    pub fn get(&self, _symbol: &str) -> Option<&Instrument> {
+        self.instruments.get(_symbol)
+    }
+
+    pub fn symbols(&self) -> Vec<String> {
+        self.instruments.keys().cloned().collect()
+    }
+
+    // Unlisted instruments are whole-numbered.
+    pub fn precision(&self, _symbol: &str) -> Precision {
+        let (price, qty) = match self.instruments.get(_symbol) {
+            Some(v) => (v.price_places, v.qty_places),
+            None => (0, 0),
+        };
+        Precision {
+            price: price,
+            qty: qty,
+            cash: self.cash_places,
+        }
+    }
+
+    pub fn check_listed(&self, _symbol: &str) -> Result<&Instrument, LibError> {
+        match self.instruments.get(_symbol) {
+            Some(v) => Ok(v),
+            None => Err(LibError::NExistKeyError {
+                contents: format!("Instrument {}", _symbol),
+                structure: String::from("instrument registry"),
+            }),
+        }
+    }
+
+    pub fn list(&mut self, _instrument: Instrument) -> Result<(), Error> {
+        if self.instruments.contains_key(&_instrument.symbol) {
+            return Err(Error::from(LibError::CustomError {
+                contents: format!("Instrument {} is already listed", _instrument.symbol),
+            }));
+        }
+        Precision {
+            price: _instrument.price_places,
+            qty: _instrument.qty_places,
+            cash: self.cash_places,
+        }
+        .check()?;
+        self.instruments
+            .insert(_instrument.symbol.clone(), _instrument);
+        Ok(())
+    }
+
+    fn listed_mut(&mut self, _symbol: &str) -> Result<&mut Instrument, LibError> {
+        match self.instruments.get_mut(_symbol) {
+            Some(v) => Ok(v),
+            None => Err(LibError::NExistKeyError {
+                contents: format!("Instrument {}", _symbol),
+                structure: String::from("instrument registry"),
+            }),
+        }
+    }
+
+    // Balances are stored in the smallest unit of cash and are not rescaled, so this is for
+    // setting a market up before any cash is credited; the admin route refuses it after.
+    pub fn set_cash_places(&mut self, _places: u32) -> Result<(), LibError> {
+        if _places > MAX_PLACES {
+            return Err(LibError::CustomError {
+                contents: format!("Cash can have at most {} decimal places", MAX_PLACES),
+            });
+        }
+        self.cash_places = _places;
+        Ok(())
+    }
+
+    pub fn set_mode(&mut self, _symbol: &str, _mode: MarketMode) -> Result<(), LibError> {
+        self.listed_mut(_symbol)?.mode = _mode;
+        Ok(())
+    }
+
+    pub fn set_matching(
+        &mut self,
+        _symbol: &str,
+        _matching: MatchingAlgorithm,
+    ) -> Result<(), LibError> {
+        self.listed_mut(_symbol)?.matching = _matching;
+        Ok(())
+    }
+
+    pub fn set_rules(&mut self, _symbol: &str, _rules: TradingRules) -> Result<(), LibError> {
+        _rules.check()?;
+        self.listed_mut(_symbol)?.rules = _rules;
+        Ok(())
+    }
+
+    // None removes the band.
+    pub fn set_band(&mut self, _symbol: &str, _band: Option<PriceBand>) -> Result<(), LibError> {
+        self.listed_mut(_symbol)?.band = _band;
+        Ok(())
+    }
+
+    // None removes the breaker; a halt already in force runs its course.
+    pub fn set_breaker(
+        &mut self,
+        _symbol: &str,
+        _breaker: Option<CircuitBreaker>,
+    ) -> Result<(), LibError> {
+        if let Some(ref v) = _breaker {
+            v.check()?;
+        }
+        self.listed_mut(_symbol)?.breaker = _breaker;
+        Ok(())
+    }
+}
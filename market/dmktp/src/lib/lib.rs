@@ -28,15 +28,35 @@ extern crate swth_cli_libv2;
 pub mod address;
 pub mod addressing;
 pub mod adminmsg;
+pub mod amend;
+pub mod amount;
 pub mod auction;
 pub mod balancebook;
+pub mod blockinfo;
+pub mod callauction;
+pub mod cancel;
+pub mod decimal;
 pub mod errors;
+pub mod events;
+pub mod fees;
 pub mod getset;
 pub mod handler;
+pub mod instrument;
+pub mod marketstatus;
+pub mod matching;
 pub mod order;
 pub mod orderbook;
+pub mod orderstatus;
+pub mod permissions;
+pub mod pricelevels;
+pub mod pricelimits;
+pub mod reach;
 pub mod routes;
+pub mod rules;
 pub mod sealedbid;
+pub mod settlement;
+pub mod stoporder;
+pub mod tradeledger;
 pub mod useracct;
 pub mod validation;
 pub mod wrapper;
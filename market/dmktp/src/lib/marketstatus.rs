@@ -0,0 +1,30 @@
+use std::fmt;
+
+// Session-level state for the market. `step` counts trading periods: it only moves when an
+// admin submits AdminMsg::Step, which closes the period for the books' previous close.
+// Expiry goes by block number instead (see FamilyState::get_block_num), and circuit breaker
+// halts are kept on each book, so trading never writes here.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MarketStatus {
+    pub step: u64,
+}
+
+impl fmt::Display for MarketStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Market step: {}\n", self.step)
+    }
+}
+
+impl MarketStatus {
+    pub fn new() -> Self {
+        MarketStatus { step: 0 }
+    }
+
+    pub fn get_step(&self) -> u64 {
+        self.step
+    }
+
+    pub fn step(&mut self) {
+        self.step += 1;
+    }
+}
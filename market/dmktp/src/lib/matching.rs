@@ -0,0 +1,115 @@
+use amount::Quantity;
+use errors::LibError;
+use std::fmt;
+use std::str::FromStr;
+
+// How a continuous book shares an incoming order among the orders resting at the best
+// price. Price always comes first; this only decides who trades within a level.
+//
+// Fifo fills the level's orders one at a time, oldest first. ProRata shares the incoming
+// quantity across the whole level in proportion to what each order shows. TopOrder fills
+// the level's oldest order first and shares what is left pro rata among the rest. Pro-rata
+// shares are rounded down, and the units that leaves go one each to the level's oldest
+// orders with room for them, so the same book and order always trade the same way.
+//
+// Call auctions ignore this; their marginal level is always shared pro rata (see
+// callauction).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchingAlgorithm {
+    Fifo,
+    ProRata,
+    TopOrder,
+}
+
+impl Default for MatchingAlgorithm {
+    fn default() -> Self {
+        MatchingAlgorithm::Fifo
+    }
+}
+
+impl fmt::Display for MatchingAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MatchingAlgorithm::Fifo => write!(f, "fifo"),
+            MatchingAlgorithm::ProRata => write!(f, "pro-rata"),
+            MatchingAlgorithm::TopOrder => write!(f, "top-order"),
+        }
+    }
+}
+
+impl FromStr for MatchingAlgorithm {
+    type Err = LibError;
+
+    fn from_str(_s: &str) -> Result<Self, LibError> {
+        match _s {
+            "fifo" => Ok(MatchingAlgorithm::Fifo),
+            "pro-rata" => Ok(MatchingAlgorithm::ProRata),
+            "top-order" => Ok(MatchingAlgorithm::TopOrder),
+            _ => Err(LibError::CustomError {
+                contents: format!(
+                    "Unknown matching algorithm {}; expected fifo, pro-rata or top-order",
+                    _s
+                ),
+            }),
+        }
+    }
+}
+
+impl MatchingAlgorithm {
+    // How much of _qty each order at a level gets. `_shown` is what each order shows, oldest
+    // first; the allocations come back in the same order and never exceed it.
+    pub fn allocate(&self, _qty: u64, _shown: &[u64]) -> Result<Vec<u64>, LibError> {
+        match self {
+            MatchingAlgorithm::Fifo => {
+                let mut remaining = _qty;
+                Ok(_shown
+                    .iter()
+                    .map(|x| {
+                        let take = remaining.min(*x);
+                        remaining -= take;
+                        take
+                    })
+                    .collect())
+            }
+            MatchingAlgorithm::ProRata => pro_rata(_qty, _shown),
+            MatchingAlgorithm::TopOrder => match _shown.split_first() {
+                Some((top, rest)) => {
+                    let first = _qty.min(*top);
+                    let mut allocs = vec![first];
+                    allocs.extend(pro_rata(_qty - first, rest)?);
+                    Ok(allocs)
+                }
+                None => Ok(Vec::new()),
+            },
+        }
+    }
+}
+
+// Shares _qty among orders showing _shown, oldest first, in proportion to what they show.
+// Shares are rounded down and the units left over go one each to the oldest orders with
+// room for another.
+pub fn pro_rata(_qty: u64, _shown: &[u64]) -> Result<Vec<u64>, LibError> {
+    let total = _shown.iter().fold(0, |acc: u64, x| acc.saturating_add(*x));
+    if _qty >= total {
+        return Ok(_shown.to_vec());
+    }
+    let mut allocs: Vec<u64> = Vec::with_capacity(_shown.len());
+    for x in _shown.iter() {
+        allocs.push(
+            Quantity::new(_qty)
+                .scale(*x, total, "pro-rata allocation")?
+                .get(),
+        );
+    }
+    let mut leftover = _qty - allocs.iter().sum::<u64>();
+    for (alloc, shown) in allocs.iter_mut().zip(_shown.iter()) {
+        if leftover == 0 {
+            break;
+        }
+        if *alloc < *shown {
+            *alloc += 1;
+            leftover -= 1;
+        }
+    }
+    Ok(allocs)
+}
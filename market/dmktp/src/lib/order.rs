@@ -1,29 +1,195 @@
-use std::cmp::Ordering;
+use std::cmp::{self, Ordering};
 use std::fmt;
+use std::str::FromStr;
 use std::u64::MAX;
 use swth_cli_libv2::clireq::{CliRequest, ClientRequest};
 
 use address::Address;
+use amount::{Price, Quantity};
 use balancebook::BalanceBook;
+use decimal::Precision;
+use errors::LibError;
 use failure::Error;
-use orderbook::{self, fill_buy, fill_sell, OrderBook};
+use instrument::default_instrument;
+use orderbook::{self, fill_buy, fill_sell, Fill, OrderBook};
 use serde_cbor;
 use wrapper::Wrapper;
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct BuyOrder {
     pub addr: Address,
-    pub price: u64,
-    pub qty: u64,
+    pub price: Price,
+    pub qty: Quantity,
     pub nonce: u64,
+    // Assigned by the processor from the transaction signature; see orderstatus::order_id
+    #[serde(default)]
+    pub id: String,
+    #[serde(default = "default_instrument")]
+    pub instrument: String,
+    #[serde(default)]
+    pub order_type: OrderType,
+    // Last block the order is good for; None is good till cancelled.
+    #[serde(default)]
+    pub expires: Option<u64>,
+    // Iceberg orders show at most `peak` of their quantity on the book. `shown` is what is
+    // left of the current peak; the rest is held in reserve. The reserve is only hidden
+    // from matching and from clients' views (see OrderBook::view_for); it is stored in
+    // the clear like the rest of the order.
+    #[serde(default)]
+    pub peak: Option<Quantity>,
+    #[serde(default)]
+    pub shown: Quantity,
+    // What happens when the order meets one of its owner's own resting orders.
+    #[serde(default)]
+    pub stp: SelfTradePrevention,
+    // The fee rate held for on top of the notional while the order rests, set by the book
+    // when it rests it; see FeeSchedule::hold_bps. Whatever a client sends is ignored.
+    #[serde(default)]
+    pub fee_bps: u64,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct SellOrder {
     pub addr: Address,
-    pub price: u64,
-    pub qty: u64,
+    pub price: Price,
+    pub qty: Quantity,
     pub nonce: u64,
+    #[serde(default)]
+    pub id: String,
+    #[serde(default = "default_instrument")]
+    pub instrument: String,
+    #[serde(default)]
+    pub order_type: OrderType,
+    #[serde(default)]
+    pub expires: Option<u64>,
+    #[serde(default)]
+    pub peak: Option<Quantity>,
+    #[serde(default)]
+    pub shown: Quantity,
+    #[serde(default)]
+    pub stp: SelfTradePrevention,
+}
+
+// How much of an order may execute and what happens to the rest. For every type but Limit
+// the unfilled remainder is cancelled instead of resting, so no hold is ever taken for it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum OrderType {
+    Limit,
+    // Sweeps the opposite side at whatever prices rest there; `price` is ignored. A market
+    // buy stops once its owner's cash won't pay for another unit at the next price.
+    Market,
+    ImmediateOrCancel,
+    // Executes in full against the book or not at all.
+    FillOrKill,
+}
+
+impl Default for OrderType {
+    fn default() -> Self {
+        OrderType::Limit
+    }
+}
+
+impl OrderType {
+    pub fn rests(&self) -> bool {
+        *self == OrderType::Limit
+    }
+
+    pub fn has_limit(&self) -> bool {
+        *self != OrderType::Market
+    }
+}
+
+impl fmt::Display for OrderType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderType::Limit => write!(f, "limit"),
+            OrderType::Market => write!(f, "market"),
+            OrderType::ImmediateOrCancel => write!(f, "ioc"),
+            OrderType::FillOrKill => write!(f, "fok"),
+        }
+    }
+}
+
+impl FromStr for OrderType {
+    type Err = LibError;
+
+    fn from_str(_s: &str) -> Result<Self, LibError> {
+        match _s {
+            "limit" => Ok(OrderType::Limit),
+            "market" => Ok(OrderType::Market),
+            "ioc" => Ok(OrderType::ImmediateOrCancel),
+            "fok" => Ok(OrderType::FillOrKill),
+            _ => Err(LibError::CustomError {
+                contents: format!(
+                    "Unknown order type {}; expected limit, market, ioc or fok",
+                    _s
+                ),
+            }),
+        }
+    }
+}
+
+// Self-trade prevention: an incoming order never trades with a resting order of the same
+// address. The incoming order's mode decides what happens instead when one is at the top
+// of the book. Decrement takes the smaller of the two quantities off both orders, without a
+// trade, and the incoming order carries on matching with what is left.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum SelfTradePrevention {
+    CancelNewest,
+    CancelOldest,
+    CancelBoth,
+    Decrement,
+}
+
+impl Default for SelfTradePrevention {
+    fn default() -> Self {
+        SelfTradePrevention::CancelNewest
+    }
+}
+
+impl fmt::Display for SelfTradePrevention {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelfTradePrevention::CancelNewest => write!(f, "cancel-newest"),
+            SelfTradePrevention::CancelOldest => write!(f, "cancel-oldest"),
+            SelfTradePrevention::CancelBoth => write!(f, "cancel-both"),
+            SelfTradePrevention::Decrement => write!(f, "decrement"),
+        }
+    }
+}
+
+impl FromStr for SelfTradePrevention {
+    type Err = LibError;
+
+    fn from_str(_s: &str) -> Result<Self, LibError> {
+        match _s {
+            "cancel-newest" => Ok(SelfTradePrevention::CancelNewest),
+            "cancel-oldest" => Ok(SelfTradePrevention::CancelOldest),
+            "cancel-both" => Ok(SelfTradePrevention::CancelBoth),
+            "decrement" => Ok(SelfTradePrevention::Decrement),
+            _ => Err(LibError::CustomError {
+                contents: format!(
+                    "Unknown self-trade prevention mode {}; expected cancel-newest, cancel-oldest, cancel-both or decrement",
+                    _s
+                ),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Side::Buy => write!(f, "buy"),
+            Side::Sell => write!(f, "sell"),
+        }
+    }
 }
 
 pub trait OrderT {
@@ -34,18 +200,53 @@ pub trait OrderT {
     fn get_price(&self) -> u64;
     fn get_nonce(&self) -> u64;
     fn set_nonce(&mut self, _nonce: u64);
-    fn dec_qty_by(&mut self, _dec_qty: u64);
+    fn get_id(&self) -> &String;
+    fn set_id(&mut self, _id: String);
+    fn get_instrument(&self) -> &String;
+    fn set_instrument(&mut self, _instrument: String);
+    fn get_order_type(&self) -> OrderType;
+    fn set_order_type(&mut self, _order_type: OrderType);
+    fn get_expires(&self) -> Option<u64>;
+    fn set_expires(&mut self, _expires: Option<u64>);
+    fn is_expired(&self, _block: u64) -> bool {
+        match self.get_expires() {
+            Some(v) => _block > v,
+            None => false,
+        }
+    }
+    fn get_peak(&self) -> Option<u64>;
+    fn set_peak(&mut self, _peak: Option<u64>);
+    fn get_stp(&self) -> SelfTradePrevention;
+    fn set_stp(&mut self, _stp: SelfTradePrevention);
+    // The part of the order other participants can see and trade against right now.
+    fn visible_qty(&self) -> u64;
+    // Shows the next peak of an iceberg order from its reserve.
+    fn refill(&mut self);
+    fn side(&self) -> Side;
+    fn dec_qty_by(&mut self, _dec_qty: u64) -> Result<(), LibError>;
     fn wrap(self) -> Wrapper;
-    fn execute(self, _bb: &mut BalanceBook, _ob: &mut OrderBook) -> Result<(), Error>;
+    // Matches the order against the book and rests any remainder. Returns the fills, in
+    // execution order.
+    fn execute(self, _bb: &mut BalanceBook, _ob: &mut OrderBook) -> Result<Vec<Fill>, Error>;
+    // Puts the order on the book without matching it, as call-mode books do.
+    fn rest(self, _bb: &mut BalanceBook, _ob: &mut OrderBook) -> Result<(), Error>;
 }
 
 impl OrderT for BuyOrder {
     fn from_vals(_addr: Address, _price: u64, _qty: u64, _nonce: u64) -> Self {
         BuyOrder {
             addr: _addr,
-            price: _price,
-            qty: _qty,
+            price: Price::new(_price),
+            qty: Quantity::new(_qty),
             nonce: _nonce,
+            id: String::new(),
+            instrument: default_instrument(),
+            order_type: OrderType::Limit,
+            expires: None,
+            peak: None,
+            shown: Quantity::default(),
+            stp: SelfTradePrevention::CancelNewest,
+            fee_bps: 0,
         }
     }
     fn get_addr(&self) -> Address {
@@ -55,39 +256,117 @@ impl OrderT for BuyOrder {
         &self.addr
     }
     fn get_qty(&self) -> u64 {
-        self.qty
+        self.qty.get()
     }
     fn get_price(&self) -> u64 {
-        self.price
+        self.price.get()
     }
     fn get_nonce(&self) -> u64 {
         self.nonce
     }
 
-    fn dec_qty_by(&mut self, _dec_qty: u64) {
-        self.qty -= _dec_qty;
+    fn dec_qty_by(&mut self, _dec_qty: u64) -> Result<(), LibError> {
+        self.qty = self.qty.sub(Quantity::new(_dec_qty), "order decrement")?;
+        self.shown = Quantity::new(self.shown.get().saturating_sub(_dec_qty));
+        Ok(())
     }
 
     fn set_nonce(&mut self, _nonce: u64) {
         self.nonce = _nonce;
     }
 
+    fn get_id(&self) -> &String {
+        &self.id
+    }
+
+    fn set_id(&mut self, _id: String) {
+        self.id = _id;
+    }
+
+    fn get_instrument(&self) -> &String {
+        &self.instrument
+    }
+
+    fn set_instrument(&mut self, _instrument: String) {
+        self.instrument = _instrument;
+    }
+
+    fn get_order_type(&self) -> OrderType {
+        self.order_type
+    }
+
+    fn set_order_type(&mut self, _order_type: OrderType) {
+        self.order_type = _order_type;
+    }
+
+    fn get_expires(&self) -> Option<u64> {
+        self.expires
+    }
+
+    fn set_expires(&mut self, _expires: Option<u64>) {
+        self.expires = _expires;
+    }
+
+    fn get_peak(&self) -> Option<u64> {
+        self.peak.map(Quantity::get)
+    }
+
+    fn set_peak(&mut self, _peak: Option<u64>) {
+        self.peak = _peak.map(Quantity::new);
+    }
+
+    fn get_stp(&self) -> SelfTradePrevention {
+        self.stp
+    }
+
+    fn set_stp(&mut self, _stp: SelfTradePrevention) {
+        self.stp = _stp;
+    }
+
+    fn visible_qty(&self) -> u64 {
+        match self.peak {
+            Some(_) => cmp::min(self.shown, self.qty).get(),
+            None => self.qty.get(),
+        }
+    }
+
+    fn refill(&mut self) {
+        if let Some(v) = self.peak {
+            self.shown = cmp::min(v, self.qty);
+        }
+    }
+
+    fn side(&self) -> Side {
+        Side::Buy
+    }
+
     fn wrap(self) -> Wrapper {
         Wrapper::Bo(self)
     }
 
-    fn execute(self, _bb: &mut BalanceBook, _ob: &mut OrderBook) -> Result<(), Error> {
+    fn execute(self, _bb: &mut BalanceBook, _ob: &mut OrderBook) -> Result<Vec<Fill>, Error> {
         fill_buy(_ob, _bb, self)
     }
+
+    fn rest(self, _bb: &mut BalanceBook, _ob: &mut OrderBook) -> Result<(), Error> {
+        _ob.insert_buy_order(_bb, self, true)
+    }
 }
 
 impl OrderT for SellOrder {
     fn from_vals(_addr: Address, _price: u64, _qty: u64, _nonce: u64) -> Self {
         SellOrder {
             addr: _addr,
-            price: _price,
-            qty: _qty,
+            price: Price::new(_price),
+            qty: Quantity::new(_qty),
             nonce: _nonce,
+            id: String::new(),
+            instrument: default_instrument(),
+            order_type: OrderType::Limit,
+            expires: None,
+            peak: None,
+            shown: Quantity::default(),
+            stp: SelfTradePrevention::CancelNewest,
         }
     }
 
@@ -98,30 +377,101 @@ impl OrderT for SellOrder {
         &self.addr
     }
     fn get_qty(&self) -> u64 {
-        self.qty
+        self.qty.get()
     }
     fn get_price(&self) -> u64 {
-        self.price
+        self.price.get()
     }
     fn get_nonce(&self) -> u64 {
         self.nonce
     }
 
-    fn dec_qty_by(&mut self, _dec_qty: u64) {
-        self.qty -= _dec_qty;
+    fn dec_qty_by(&mut self, _dec_qty: u64) -> Result<(), LibError> {
+        self.qty = self.qty.sub(Quantity::new(_dec_qty), "order decrement")?;
+        self.shown = Quantity::new(self.shown.get().saturating_sub(_dec_qty));
+        Ok(())
     }
 
     fn set_nonce(&mut self, _nonce: u64) {
         self.nonce = _nonce;
     }
 
+    fn get_id(&self) -> &String {
+        &self.id
+    }
+
+    fn set_id(&mut self, _id: String) {
+        self.id = _id;
+    }
+
+    fn get_instrument(&self) -> &String {
+        &self.instrument
+    }
+
+    fn set_instrument(&mut self, _instrument: String) {
+        self.instrument = _instrument;
+    }
+
+    fn get_order_type(&self) -> OrderType {
+        self.order_type
+    }
+
+    fn set_order_type(&mut self, _order_type: OrderType) {
+        self.order_type = _order_type;
+    }
+
+    fn get_expires(&self) -> Option<u64> {
+        self.expires
+    }
+
+    fn set_expires(&mut self, _expires: Option<u64>) {
+        self.expires = _expires;
+    }
+
+    fn get_peak(&self) -> Option<u64> {
+        self.peak.map(Quantity::get)
+    }
+
+    fn set_peak(&mut self, _peak: Option<u64>) {
+        self.peak = _peak.map(Quantity::new);
+    }
+
+    fn get_stp(&self) -> SelfTradePrevention {
+        self.stp
+    }
+
+    fn set_stp(&mut self, _stp: SelfTradePrevention) {
+        self.stp = _stp;
+    }
+
+    fn visible_qty(&self) -> u64 {
+        match self.peak {
+            Some(_) => cmp::min(self.shown, self.qty).get(),
+            None => self.qty.get(),
+        }
+    }
+
+    fn refill(&mut self) {
+        if let Some(v) = self.peak {
+            self.shown = cmp::min(v, self.qty);
+        }
+    }
+
+    fn side(&self) -> Side {
+        Side::Sell
+    }
+
     fn wrap(self) -> Wrapper {
         Wrapper::So(self)
     }
 
-    fn execute(self, _bb: &mut BalanceBook, _ob: &mut OrderBook) -> Result<(), Error> {
+    fn execute(self, _bb: &mut BalanceBook, _ob: &mut OrderBook) -> Result<Vec<Fill>, Error> {
         fill_sell(_ob, _bb, self)
     }
+
+    fn rest(self, _bb: &mut BalanceBook, _ob: &mut OrderBook) -> Result<(), Error> {
+        _ob.insert_sell_order(_bb, self, true)
+    }
 }
 
 // Implementation of ord/eq for orders to allow for judgment
@@ -142,7 +492,7 @@ impl PartialOrd for SellOrder {
     }
 }
 
-// BinaryHeap ordering
+// Price-time priority: the greater order trades first. PriceLevels keeps the same order.
 impl Ord for BuyOrder {
     fn cmp(&self, other: &BuyOrder) -> Ordering {
         match self.price == other.price {
@@ -205,20 +555,60 @@ impl Ord for SellOrder {
 
 impl fmt::Display for BuyOrder {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Buy Order\nOrigin address: {}\nUnit Price: {}\nQuantity: {}\nNonce: {}\n",
-            self.addr, self.price, self.qty, self.nonce
-        )
+        write!(f, "{}", self.show(&Precision::default()))
     }
 }
 
 impl fmt::Display for SellOrder {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "SellOrder\nOrigin address: {}\nUnit Price: {}\nQuantity: {}\nNonce: {}\n",
-            self.addr, self.price, self.qty, self.nonce
-        )
+        write!(f, "{}", self.show(&Precision::default()))
+    }
+}
+
+impl BuyOrder {
+    // The order with its price and quantities as decimals at _precision.
+    pub fn show(&self, _precision: &Precision) -> String {
+        let mut shown = format!(
+            "Buy Order\nOrder ID: {}\nType: {}\nInstrument: {}\nOrigin address: {}\nUnit Price: {}\nQuantity: {}\nNonce: {}\n",
+            self.id,
+            self.order_type,
+            self.instrument,
+            self.addr,
+            _precision.price(self.price.get()),
+            _precision.qty(self.qty.get()),
+            self.nonce
+        );
+        if let Some(v) = self.peak {
+            shown += &format!(
+                "Showing: {} (peak {})\n",
+                _precision.qty(self.visible_qty()),
+                _precision.qty(v.get())
+            );
+        }
+        shown
+    }
+}
+
+impl SellOrder {
+    // The order with its price and quantities as decimals at _precision.
+    pub fn show(&self, _precision: &Precision) -> String {
+        let mut shown = format!(
+            "SellOrder\nOrder ID: {}\nType: {}\nInstrument: {}\nOrigin address: {}\nUnit Price: {}\nQuantity: {}\nNonce: {}\n",
+            self.id,
+            self.order_type,
+            self.instrument,
+            self.addr,
+            _precision.price(self.price.get()),
+            _precision.qty(self.qty.get()),
+            self.nonce
+        );
+        if let Some(v) = self.peak {
+            shown += &format!(
+                "Showing: {} (peak {})\n",
+                _precision.qty(self.visible_qty()),
+                _precision.qty(v.get())
+            );
+        }
+        shown
     }
 }
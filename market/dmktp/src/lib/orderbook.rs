@@ -1,17 +1,124 @@
-use std::collections::BinaryHeap;
+use std::cmp;
+use std::collections::BTreeSet;
 use std::fmt;
+use std::mem;
 
+use address::Address;
+use amend::Amend;
+use amount::{Price, Quantity};
 use balancebook::BalanceBook;
+use decimal::Precision;
 use errors::LibError;
 use failure::Error;
-use order::{BuyOrder, OrderT, SellOrder};
+use fees::FeeSchedule;
+use instrument::{default_instrument, MarketMode};
+use matching::MatchingAlgorithm;
+use order::{BuyOrder, OrderT, OrderType, SelfTradePrevention, SellOrder, Side};
+use pricelevels::PriceLevels;
+use pricelimits::{Halt, ReferencePrice};
+use settlement::{settle, BuyFunding, SellFunding, Settled};
+use stoporder::StopOrder;
 use useracct::UserAccount;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct OrderBook {
-    pub buy_orders: BinaryHeap<BuyOrder>,
-    pub sell_orders: BinaryHeap<SellOrder>,
+    pub buy_orders: PriceLevels<BuyOrder>,
+    pub sell_orders: PriceLevels<SellOrder>,
     pub nonce: u64,
+    #[serde(default = "default_instrument")]
+    pub instrument: String,
+    // Price of the most recent fill on this book; what stop orders trigger on.
+    #[serde(default)]
+    pub last_price: Option<u64>,
+    // Waiting stop orders, oldest first.
+    #[serde(default)]
+    pub stop_orders: Vec<StopOrder>,
+    // last_price as of the most recent market step.
+    #[serde(default)]
+    pub prev_close: Option<u64>,
+    // (block, price) of the fills within the instrument's circuit breaker window; only the
+    // lowest and highest price of each block are kept.
+    #[serde(default)]
+    pub recent_prices: Vec<(u64, u64)>,
+    // (order ID, quantity) taken off orders by self-trade prevention during matching, for
+    // the caller to collect with take_self_trades. Never stored.
+    #[serde(skip)]
+    pub self_trades: Vec<(String, u64)>,
+    // Prices whose level is stored at its own address (see addressing::level_addr). Only
+    // set on the copy getset writes to the book's address, which has empty sides.
+    #[serde(default)]
+    pub buy_levels: Vec<u64>,
+    #[serde(default)]
+    pub sell_levels: Vec<u64>,
+    // The instrument's, as of when the book was last read; see getset::get_orderbook. Not
+    // stored, so the registry is the only record of them.
+    #[serde(skip)]
+    pub precision: Precision,
+    #[serde(skip)]
+    pub matching: MatchingAlgorithm,
+    #[serde(skip)]
+    pub mode: MarketMode,
+    // What trades on the book are charged; set by the route matching on it. Not stored.
+    #[serde(skip)]
+    pub fees: FeeSchedule,
+    // Number of trades recorded on this book, and so the sequence number of the next.
+    #[serde(default)]
+    pub trades: u64,
+    // The most recent circuit breaker halt, in force until its resumes_at block.
+    #[serde(default)]
+    pub halt: Option<Halt>,
+    // Stored levels not yet read into the sides; see getset::get_book_header. Never stored.
+    #[serde(skip)]
+    pub unread_buys: BTreeSet<u64>,
+    #[serde(skip)]
+    pub unread_sells: BTreeSet<u64>,
+}
+
+// One execution between an incoming (aggressor) order and a resting order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Fill {
+    #[serde(default = "default_instrument")]
+    pub instrument: String,
+    pub buy_id: String,
+    pub sell_id: String,
+    pub buyer: Address,
+    pub seller: Address,
+    pub price: u64,
+    pub qty: u64,
+    // What the buyer paid the seller, in the smallest unit of cash.
+    #[serde(default)]
+    pub cash: u64,
+    pub aggressor: Side,
+    // Charged in cash as the fill settles; see fees::FeeSchedule.
+    #[serde(default)]
+    pub buyer_fee: u64,
+    #[serde(default)]
+    pub seller_fee: u64,
+}
+
+impl Fill {
+    pub fn from_orders(
+        _buy: &BuyOrder,
+        _sell: &SellOrder,
+        _price: u64,
+        _qty: u64,
+        _settled: Settled,
+        _aggressor: Side,
+    ) -> Self {
+        Fill {
+            instrument: _buy.get_instrument().clone(),
+            buy_id: _buy.get_id().clone(),
+            sell_id: _sell.get_id().clone(),
+            buyer: _buy.get_addr(),
+            seller: _sell.get_addr(),
+            price: _price,
+            qty: _qty,
+            cash: _settled.cash.get(),
+            aggressor: _aggressor,
+            buyer_fee: _settled.buyer_fee.get(),
+            seller_fee: _settled.seller_fee.get(),
+        }
+    }
 }
 
 impl From<OrderBook> for String {
@@ -25,22 +132,44 @@ impl fmt::Display for OrderBook {
         //       let chained = self.buy_orders.iter().chain(self.sell_orders.iter()).try_for_each(|x| write!(f, "\n{}\n", x))
         self.buy_orders
             .iter()
-            .try_for_each(|x| write!(f, "\n{}\n", x))?;
+            .try_for_each(|x| write!(f, "\n{}\n", x.show(&self.precision)))?;
         self.sell_orders
             .iter()
-            .try_for_each(|x| write!(f, "\n{}\n", x))?;
-        write!(f, "\nNonce: {}\n", self.nonce)
+            .try_for_each(|x| write!(f, "\n{}\n", x.show(&self.precision)))?;
+        write!(
+            f,
+            "\nInstrument: {}\nNonce: {}\n",
+            self.instrument, self.nonce
+        )
     }
 }
 
 impl OrderBook {
     pub fn new() -> Self {
-        let buy: BinaryHeap<BuyOrder> = BinaryHeap::new();
-        let sell: BinaryHeap<SellOrder> = BinaryHeap::new();
+        OrderBook::for_instrument(default_instrument())
+    }
+
+    pub fn for_instrument(_instrument: String) -> Self {
         OrderBook {
-            buy_orders: buy,
-            sell_orders: sell,
+            buy_orders: PriceLevels::new(),
+            sell_orders: PriceLevels::new(),
             nonce: 0,
+            instrument: _instrument,
+            last_price: None,
+            stop_orders: Vec::new(),
+            prev_close: None,
+            recent_prices: Vec::new(),
+            self_trades: Vec::new(),
+            buy_levels: Vec::new(),
+            sell_levels: Vec::new(),
+            precision: Precision::default(),
+            matching: MatchingAlgorithm::default(),
+            mode: MarketMode::default(),
+            fees: FeeSchedule::new(),
+            trades: 0,
+            halt: None,
+            unread_buys: BTreeSet::new(),
+            unread_sells: BTreeSet::new(),
         }
     }
 
@@ -76,6 +205,643 @@ impl OrderBook {
     pub fn clear_all(&mut self) {
         self.buy_orders.clear();
         self.sell_orders.clear();
+        self.stop_orders.clear();
+    }
+
+    // Empties both sides of the book and releases every resting order's hold back to
+    // the owner's liquid balance. Unlike clear_all, nobody's funds stay locked.
+    pub fn refund_all(&mut self, _balance_book: &mut BalanceBook) -> Result<Vec<String>, Error> {
+        for order in self.buy_orders.iter() {
+            release_buy_hold(_balance_book, &self.precision, order)?;
+        }
+        for order in self.sell_orders.iter() {
+            release_sell_hold(_balance_book, order)?;
+        }
+        let removed = self.resting_ids();
+        self.clear_all();
+        Ok(removed)
+    }
+
+    // Removes resting orders that expired before _block and releases their holds. Returns
+    // the IDs of the removed orders.
+    pub fn purge_expired(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        _block: u64,
+    ) -> Result<Vec<String>, Error> {
+        let expired_buy = self.buy_orders.remove_where(|x| x.is_expired(_block));
+        let expired_sell = self.sell_orders.remove_where(|x| x.is_expired(_block));
+        let (expired_stop, live_stop): (Vec<StopOrder>, Vec<StopOrder>) =
+            mem::replace(&mut self.stop_orders, Vec::new())
+                .into_iter()
+                .partition(|x| x.is_expired(_block));
+        self.stop_orders = live_stop;
+
+        for order in expired_buy.iter() {
+            release_buy_hold(_balance_book, &self.precision, order)?;
+        }
+        for order in expired_sell.iter() {
+            release_sell_hold(_balance_book, order)?;
+        }
+        Ok(expired_buy
+            .iter()
+            .map(|x| x.get_id().clone())
+            .chain(expired_sell.iter().map(|x| x.get_id().clone()))
+            .chain(expired_stop.iter().map(|x| x.get_id().clone()))
+            .collect())
+    }
+
+    // The halt in force at _block, if any.
+    pub fn get_halt(&self, _block: u64) -> Option<&Halt> {
+        self.halt.as_ref().filter(|x| x.resumes_at > _block)
+    }
+
+    pub fn is_halted(&self, _block: u64) -> bool {
+        self.get_halt(_block).is_some()
+    }
+
+    pub fn reference_price(&self, _reference: ReferencePrice) -> Option<u64> {
+        match _reference {
+            ReferencePrice::LastTrade => self.last_price,
+            ReferencePrice::PrevClose => self.prev_close,
+        }
+    }
+
+    // Adds the fills' prices to the recent history and drops what has left the _window
+    // blocks ending at _block. The breaker only measures the range of the prices, so a
+    // block keeps just its lowest and highest, and the history stays within two entries a
+    // block of the window.
+    pub fn record_prices(&mut self, _fills: &[Fill], _block: u64, _window: u64) {
+        self.recent_prices
+            .retain(|x| x.0.saturating_add(_window) > _block);
+        let start = self
+            .recent_prices
+            .iter()
+            .position(|x| x.0 == _block)
+            .unwrap_or(self.recent_prices.len());
+        let prices: Vec<u64> = self.recent_prices[start..]
+            .iter()
+            .map(|x| x.1)
+            .chain(_fills.iter().map(|x| x.price))
+            .collect();
+        self.recent_prices.truncate(start);
+        if let (Some(low), Some(high)) = (prices.iter().min(), prices.iter().max()) {
+            self.recent_prices.push((_block, *low));
+            if high > low {
+                self.recent_prices.push((_block, *high));
+            }
+        }
+    }
+
+    // Resting quantity an incoming order limited to _price could trade against.
+    pub fn sell_qty_at_or_below(&self, _price: u64) -> u64 {
+        self.sell_orders.qty_through(_price)
+    }
+
+    pub fn buy_qty_at_or_above(&self, _price: u64) -> u64 {
+        self.buy_orders.qty_through(_price)
+    }
+
+    pub fn own_sell_qty_at_or_below(&self, _price: u64, _owner: &Address) -> u64 {
+        self.sell_orders
+            .iter()
+            .take_while(|x| x.get_price() <= _price)
+            .filter(|x| x.get_addr_ref() == _owner)
+            .map(|x| x.get_qty())
+            .sum()
+    }
+
+    pub fn own_buy_qty_at_or_above(&self, _price: u64, _owner: &Address) -> u64 {
+        self.buy_orders
+            .iter()
+            .take_while(|x| x.get_price() >= _price)
+            .filter(|x| x.get_addr_ref() == _owner)
+            .map(|x| x.get_qty())
+            .sum()
+    }
+
+    pub fn take_self_trades(&mut self) -> Vec<(String, u64)> {
+        mem::replace(&mut self.self_trades, Vec::new())
+    }
+
+    // Applies the incoming buy's self-trade prevention mode against its owner's oldest sell
+    // at the lowest price. Returns what is left of the buy to keep matching, if anything.
+    fn prevent_self_trade_buy(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        mut _order: BuyOrder,
+    ) -> Result<Option<BuyOrder>, Error> {
+        let best = self.sell_orders.best_price();
+        let mut resting = self
+            .sell_orders
+            .remove_first(|x| {
+                Some(x.get_price()) == best && x.get_addr_ref() == _order.get_addr_ref()
+            })
+            .expect("Self-trade prevention was called without an own order at the best price");
+        let (cancel_incoming, removed) = match _order.get_stp() {
+            SelfTradePrevention::CancelNewest => (true, 0),
+            SelfTradePrevention::CancelOldest => (false, resting.get_qty()),
+            SelfTradePrevention::CancelBoth => (true, resting.get_qty()),
+            SelfTradePrevention::Decrement => {
+                let qty = cmp::min(_order.get_qty(), resting.get_qty());
+                _order.dec_qty_by(qty)?;
+                self.self_trades.push((_order.get_id().clone(), qty));
+                (false, qty)
+            }
+        };
+        if removed > 0 {
+            _balance_book.debit_hold_assets(resting.get_addr_ref(), &self.instrument, removed)?;
+            _balance_book.credit_assets(resting.get_addr_ref(), &self.instrument, removed)?;
+            self.self_trades.push((resting.get_id().clone(), removed));
+            resting.dec_qty_by(removed)?;
+        }
+        self.put_back_sell(resting);
+        if cancel_incoming {
+            self.self_trades
+                .push((_order.get_id().clone(), _order.get_qty()));
+            return Ok(None);
+        }
+        match _order.get_qty() {
+            0 => Ok(None),
+            _ => Ok(Some(_order)),
+        }
+    }
+
+    fn prevent_self_trade_sell(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        mut _order: SellOrder,
+    ) -> Result<Option<SellOrder>, Error> {
+        let best = self.buy_orders.best_price();
+        let mut resting = self
+            .buy_orders
+            .remove_first(|x| {
+                Some(x.get_price()) == best && x.get_addr_ref() == _order.get_addr_ref()
+            })
+            .expect("Self-trade prevention was called without an own order at the best price");
+        let (cancel_incoming, removed) = match _order.get_stp() {
+            SelfTradePrevention::CancelNewest => (true, 0),
+            SelfTradePrevention::CancelOldest => (false, resting.get_qty()),
+            SelfTradePrevention::CancelBoth => (true, resting.get_qty()),
+            SelfTradePrevention::Decrement => {
+                let qty = cmp::min(_order.get_qty(), resting.get_qty());
+                _order.dec_qty_by(qty)?;
+                self.self_trades.push((_order.get_id().clone(), qty));
+                (false, qty)
+            }
+        };
+        if removed > 0 {
+            let cash = self.precision.released(
+                resting.get_price(),
+                resting.get_qty(),
+                removed,
+                resting.fee_bps,
+            )?;
+            _balance_book.debit_hold_cash(resting.get_addr_ref(), cash)?;
+            _balance_book.credit_cash(resting.get_addr_ref(), cash)?;
+            self.self_trades.push((resting.get_id().clone(), removed));
+            resting.dec_qty_by(removed)?;
+        }
+        self.put_back_buy(resting);
+        if cancel_incoming {
+            self.self_trades
+                .push((_order.get_id().clone(), _order.get_qty()));
+            return Ok(None);
+        }
+        match _order.get_qty() {
+            0 => Ok(None),
+            _ => Ok(Some(_order)),
+        }
+    }
+
+    // Everyone with an order on the book, waiting stop orders included.
+    pub fn owners(&self) -> BTreeSet<Address> {
+        self.buy_orders
+            .iter()
+            .map(|x| x.get_addr())
+            .chain(self.sell_orders.iter().map(|x| x.get_addr()))
+            .chain(self.stop_orders.iter().map(|x| x.get_addr_ref().clone()))
+            .collect()
+    }
+
+    // (owner, ID) of the orders purge_expired would take off the book at _block, waiting
+    // stops included.
+    pub fn expired_orders(&self, _block: u64) -> Vec<(Address, String)> {
+        self.buy_orders
+            .iter()
+            .filter(|x| x.is_expired(_block))
+            .map(|x| (x.get_addr(), x.get_id().clone()))
+            .chain(
+                self.sell_orders
+                    .iter()
+                    .filter(|x| x.is_expired(_block))
+                    .map(|x| (x.get_addr(), x.get_id().clone())),
+            )
+            .chain(
+                self.stop_orders
+                    .iter()
+                    .filter(|x| x.is_expired(_block))
+                    .map(|x| (x.get_addr_ref().clone(), x.get_id().clone())),
+            )
+            .collect()
+    }
+
+    // (owner, ID) of every order on the book, waiting stops included.
+    pub fn resting_orders(&self) -> Vec<(Address, String)> {
+        self.buy_orders
+            .iter()
+            .map(|x| (x.get_addr(), x.get_id().clone()))
+            .chain(
+                self.sell_orders
+                    .iter()
+                    .map(|x| (x.get_addr(), x.get_id().clone())),
+            )
+            .chain(
+                self.stop_orders
+                    .iter()
+                    .map(|x| (x.get_addr_ref().clone(), x.get_id().clone())),
+            )
+            .collect()
+    }
+
+    // Includes waiting stop orders.
+    pub fn resting_ids(&self) -> Vec<String> {
+        self.buy_orders
+            .iter()
+            .map(|x| x.get_id().clone())
+            .chain(self.sell_orders.iter().map(|x| x.get_id().clone()))
+            .chain(self.stop_orders.iter().map(|x| x.get_id().clone()))
+            .collect()
+    }
+
+    pub fn add_stop(&mut self, _stop: StopOrder) -> Result<(), Error> {
+        if _stop.get_instrument() != &self.instrument {
+            return Err(Error::from(LibError::CustomError {
+                contents: format!(
+                    "Stop order for {} submitted to the {} order book",
+                    _stop.get_instrument(),
+                    self.instrument
+                ),
+            }));
+        }
+        self.stop_orders.push(_stop);
+        Ok(())
+    }
+
+    // Fires the oldest stop the last trade price has reached, and repeats until none is
+    // left. Fills from a triggered order move the last price, so one trade can set off a
+    // cascade; stops fire in the order they were added so every validator gets the same
+    // result. A triggered order is checked as one placed then would be, by the book's own
+    // checks and by _check for the instrument's; one failing them is returned with its
+    // error. So is one that passes them and still fails to execute, after the book and
+    // balances are put back as they were before it, and the cascade carries on without
+    // it. Only stops passing the checks pay for a copy to put back.
+    pub fn trigger_stops(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        _check: &Fn(&StopOrder, &OrderBook) -> Result<(), Error>,
+    ) -> Vec<(StopOrder, Result<Vec<Fill>, Error>)> {
+        let mut fired = Vec::new();
+        loop {
+            let last_price = match self.last_price {
+                Some(v) => v,
+                None => return fired,
+            };
+            let next = self
+                .stop_orders
+                .iter()
+                .position(|x| x.is_triggered(last_price));
+            let stop = match next {
+                Some(i) => self.stop_orders.remove(i),
+                None => return fired,
+            };
+
+            let checked = stop
+                .check(_balance_book, self)
+                .and_then(|_| _check(&stop, self));
+            let result = match checked {
+                Ok(()) => {
+                    let (book, balances) = (self.clone(), _balance_book.clone());
+                    let executed = stop.clone().execute(_balance_book, self);
+                    if executed.is_err() {
+                        *self = book;
+                        *_balance_book = balances;
+                    }
+                    executed
+                }
+                Err(e) => Err(e),
+            };
+            fired.push((stop, result));
+        }
+    }
+
+    // Cancels a resting order by its ID, whichever side of the book it is on, or a waiting
+    // stop order.
+    pub fn cancel_by_id(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        _id: &String,
+        _owner: &Address,
+    ) -> Result<String, Error> {
+        let on_buy_side = self.buy_orders.get(_id).map(|x| x.get_addr());
+        let on_sell_side = self.sell_orders.get(_id).map(|x| x.get_addr());
+        match (on_buy_side, on_sell_side) {
+            (Some(ref owner), _) | (None, Some(ref owner)) if owner != _owner => {
+                Err(Error::from(LibError::PermissionError {
+                    signer: _owner.to_string(),
+                    action: format!("cancel order {} owned by {}", _id, owner),
+                }))
+            }
+            (Some(_), _) => {
+                let cancelled = self
+                    .buy_orders
+                    .remove(_id)
+                    .expect("Indexed buy order is missing");
+                release_buy_hold(_balance_book, &self.precision, &cancelled)
+                    .map(|_| cancelled.get_id().clone())
+            }
+            (None, Some(_)) => {
+                let cancelled = self
+                    .sell_orders
+                    .remove(_id)
+                    .expect("Indexed sell order is missing");
+                release_sell_hold(_balance_book, &cancelled).map(|_| cancelled.get_id().clone())
+            }
+            (None, None) => self.cancel_stop(_id, _owner),
+        }
+    }
+
+    // Stops hold nothing, so there is nothing to release.
+    pub fn cancel_stop(&mut self, _id: &String, _owner: &Address) -> Result<String, Error> {
+        match self.stop_orders.iter().position(|x| x.get_id() == _id) {
+            Some(i) if self.stop_orders[i].get_addr_ref() == _owner => {
+                Ok(self.stop_orders.remove(i).get_id().clone())
+            }
+            Some(i) => Err(Error::from(LibError::PermissionError {
+                signer: _owner.to_string(),
+                action: format!(
+                    "cancel stop order {} owned by {}",
+                    _id,
+                    self.stop_orders[i].get_addr_ref()
+                ),
+            })),
+            None => Err(Error::from(LibError::NExistKeyError {
+                contents: format!("Order with ID {}", _id),
+                structure: String::from("order book"),
+            })),
+        }
+    }
+
+    pub fn cancel_buy(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        _nonce: u64,
+        _owner: &Address,
+    ) -> Result<String, Error> {
+        let found = self
+            .buy_orders
+            .iter()
+            .find(|x| x.get_nonce() == _nonce)
+            .map(|x| x.get_addr());
+        match found {
+            Some(ref owner) if owner == _owner => {
+                let cancelled = self
+                    .buy_orders
+                    .remove_first(|x| x.get_nonce() == _nonce)
+                    .expect("Buy order found by nonce is missing");
+                release_buy_hold(_balance_book, &self.precision, &cancelled)
+                    .map(|_| cancelled.get_id().clone())
+            }
+            Some(owner) => Err(Error::from(LibError::PermissionError {
+                signer: _owner.to_string(),
+                action: format!("cancel buy order {} owned by {}", _nonce, owner),
+            })),
+            None => Err(Error::from(LibError::NExistKeyError {
+                contents: format!("Buy order with nonce {}", _nonce),
+                structure: String::from("order book"),
+            })),
+        }
+    }
+
+    pub fn cancel_sell(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        _nonce: u64,
+        _owner: &Address,
+    ) -> Result<String, Error> {
+        let found = self
+            .sell_orders
+            .iter()
+            .find(|x| x.get_nonce() == _nonce)
+            .map(|x| x.get_addr());
+        match found {
+            Some(ref owner) if owner == _owner => {
+                let cancelled = self
+                    .sell_orders
+                    .remove_first(|x| x.get_nonce() == _nonce)
+                    .expect("Sell order found by nonce is missing");
+                release_sell_hold(_balance_book, &cancelled).map(|_| cancelled.get_id().clone())
+            }
+            Some(owner) => Err(Error::from(LibError::PermissionError {
+                signer: _owner.to_string(),
+                action: format!("cancel sell order {} owned by {}", _nonce, owner),
+            })),
+            None => Err(Error::from(LibError::NExistKeyError {
+                contents: format!("Sell order with nonce {}", _nonce),
+                structure: String::from("order book"),
+            })),
+        }
+    }
+
+    // Amends a resting order in place and adjusts its hold by the difference. An amendment
+    // that would cross a continuous book is refused; cancel the order and submit a new one
+    // instead. Call books cross until their auction is run, so there it is left to that.
+    // Waiting stop orders can't be amended. Returns the order's new price and open quantity.
+    pub fn amend_order(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        _amend: &Amend,
+        _owner: &Address,
+    ) -> Result<(u64, u64), Error> {
+        if _amend.qty == Some(0) || _amend.price == Some(0) {
+            return Err(Error::from(LibError::CustomError {
+                contents: format!(
+                    "Cannot amend order {} to a price or quantity of 0; cancel it instead",
+                    _amend.id
+                ),
+            }));
+        }
+        match self.buy_orders.contains(&_amend.id) {
+            true => {
+                self.amend_buy(_balance_book, _amend, _owner)?;
+                let amended = self.buy_orders.get(&_amend.id);
+                Ok(amended.map_or((0, 0), |x| (x.get_price(), x.get_qty())))
+            }
+            false => {
+                self.amend_sell(_balance_book, _amend, _owner)?;
+                let amended = self.sell_orders.get(&_amend.id);
+                Ok(amended.map_or((0, 0), |x| (x.get_price(), x.get_qty())))
+            }
+        }
+    }
+
+    fn amend_buy(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        _amend: &Amend,
+        _owner: &Address,
+    ) -> Result<(), Error> {
+        let lowest_sell = match self.mode {
+            MarketMode::Continuous => self.sell_orders.peek().map(|x| x.get_price()),
+            MarketMode::Call => None,
+        };
+        let mut order = match self.buy_orders.remove(&_amend.id) {
+            Some(v) => v,
+            None => {
+                return Err(Error::from(LibError::NExistKeyError {
+                    contents: format!("Order with ID {}", _amend.id),
+                    structure: String::from("order book"),
+                }))
+            }
+        };
+        let price = _amend.price.unwrap_or(order.get_price());
+        let qty = _amend.qty.unwrap_or(order.get_qty());
+        let result = match lowest_sell {
+            _ if order.get_addr_ref() != _owner => Err(Error::from(LibError::PermissionError {
+                signer: _owner.to_string(),
+                action: format!(
+                    "amend buy order {} owned by {}",
+                    _amend.id,
+                    order.get_addr()
+                ),
+            })),
+            Some(v) if price != order.get_price() && price >= v => {
+                Err(Error::from(LibError::CustomError {
+                    contents: format!(
+                        "Amending buy order {} to {} would cross the lowest sell at {}",
+                        _amend.id, price, v
+                    ),
+                }))
+            }
+            _ => match (
+                self.precision
+                    .held(order.get_price(), order.get_qty(), order.fee_bps),
+                self.precision.held(price, qty, order.fee_bps),
+            ) {
+                (Ok(from), Ok(to)) => {
+                    _balance_book.adjust_hold_cash(order.get_addr_ref(), from, to)
+                }
+                (Err(e), _) | (_, Err(e)) => Err(Error::from(e)),
+            },
+        };
+        // Losing priority moves the order to the back of its new level, as if just placed.
+        if result.is_ok() {
+            let (price, qty) = (Price::new(price), Quantity::new(qty));
+            let loses_priority = price != order.price || qty > order.qty;
+            order.price = price;
+            order.qty = qty;
+            order.shown = order.shown.min(qty);
+            if loses_priority {
+                order.set_nonce(self.get_nonce());
+                order.refill();
+                self.inc_nonce();
+            }
+        }
+        self.buy_orders.push(order);
+        result.map(|_| ())
+    }
+
+    fn amend_sell(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        _amend: &Amend,
+        _owner: &Address,
+    ) -> Result<(), Error> {
+        let highest_buy = match self.mode {
+            MarketMode::Continuous => self.buy_orders.peek().map(|x| x.get_price()),
+            MarketMode::Call => None,
+        };
+        let mut order = match self.sell_orders.remove(&_amend.id) {
+            Some(v) => v,
+            None => {
+                return Err(Error::from(LibError::NExistKeyError {
+                    contents: format!("Order with ID {}", _amend.id),
+                    structure: String::from("order book"),
+                }))
+            }
+        };
+        let price = _amend.price.unwrap_or(order.get_price());
+        let qty = _amend.qty.unwrap_or(order.get_qty());
+        let result = match highest_buy {
+            _ if order.get_addr_ref() != _owner => Err(Error::from(LibError::PermissionError {
+                signer: _owner.to_string(),
+                action: format!(
+                    "amend sell order {} owned by {}",
+                    _amend.id,
+                    order.get_addr()
+                ),
+            })),
+            Some(v) if price != order.get_price() && price <= v => {
+                Err(Error::from(LibError::CustomError {
+                    contents: format!(
+                        "Amending sell order {} to {} would cross the highest buy at {}",
+                        _amend.id, price, v
+                    ),
+                }))
+            }
+            _ => _balance_book.adjust_hold_assets(
+                order.get_addr_ref(),
+                &self.instrument,
+                order.get_qty(),
+                qty,
+            ),
+        };
+        // Losing priority moves the order to the back of its new level, as if just placed.
+        if result.is_ok() {
+            let (price, qty) = (Price::new(price), Quantity::new(qty));
+            let loses_priority = price != order.price || qty > order.qty;
+            order.price = price;
+            order.qty = qty;
+            order.shown = order.shown.min(qty);
+            if loses_priority {
+                order.set_nonce(self.get_nonce());
+                order.refill();
+                self.inc_nonce();
+            }
+        }
+        self.sell_orders.push(order);
+        result.map(|_| ())
+    }
+
+    // Cancels every resting order on both sides owned by _owner. Returns the IDs of the
+    // removed orders.
+    pub fn cancel_all_for(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        _owner: &Address,
+    ) -> Result<Vec<String>, Error> {
+        let mine_buy = self.buy_orders.remove_where(|x| x.get_addr_ref() == _owner);
+        let mine_sell = self
+            .sell_orders
+            .remove_where(|x| x.get_addr_ref() == _owner);
+        let (mine_stop, other_stop): (Vec<StopOrder>, Vec<StopOrder>) =
+            mem::replace(&mut self.stop_orders, Vec::new())
+                .into_iter()
+                .partition(|x| x.get_addr_ref() == _owner);
+        self.stop_orders = other_stop;
+
+        for order in mine_buy.iter() {
+            release_buy_hold(_balance_book, &self.precision, order)?;
+        }
+        for order in mine_sell.iter() {
+            release_sell_hold(_balance_book, order)?;
+        }
+        Ok(mine_buy
+            .iter()
+            .map(|x| x.get_id().clone())
+            .chain(mine_sell.iter().map(|x| x.get_id().clone()))
+            .chain(mine_stop.iter().map(|x| x.get_id().clone()))
+            .collect())
     }
 
     pub fn insert_buy_order(
@@ -84,13 +850,17 @@ impl OrderBook {
         mut _order: BuyOrder,
         _update_nonce: bool,
     ) -> Result<(), Error> {
-        let order_cash_amt: u64 = _order.get_qty() * _order.get_price();
+        _order.fee_bps = self.fees.hold_bps(_order.get_addr_ref());
+        let order_cash_amt =
+            self.precision
+                .held(_order.get_price(), _order.get_qty(), _order.fee_bps)?;
         _balance_book.debit_cash(&_order.get_addr_ref(), order_cash_amt)?;
         _balance_book.credit_hold_cash(&_order.get_addr_ref(), order_cash_amt)?;
         if (_update_nonce == true) {
             _order.set_nonce(self.get_nonce());
             self.inc_nonce();
         };
+        _order.refill();
         self.buy_orders.push(_order);
         Ok(())
     }
@@ -101,101 +871,312 @@ impl OrderBook {
         mut _order: SellOrder,
         _update_nonce: bool,
     ) -> Result<(), Error> {
-        _balance_book.debit_assets(&_order.get_addr_ref(), _order.get_qty())?;
-        _balance_book.credit_hold_assets(&_order.get_addr_ref(), _order.get_qty())?;
+        _balance_book.debit_assets(&_order.get_addr_ref(), &self.instrument, _order.get_qty())?;
+        _balance_book.credit_hold_assets(
+            &_order.get_addr_ref(),
+            &self.instrument,
+            _order.get_qty(),
+        )?;
         if (_update_nonce == true) {
             _order.set_nonce(self.get_nonce());
             self.inc_nonce();
         };
+        _order.refill();
         self.sell_orders.push(_order);
         Ok(())
     }
 
+    // An iceberg order whose shown quantity was taken goes back on the book with the next
+    // peak from its reserve, behind everything already resting at its price. Its hold
+    // already covers the reserve.
+    fn insert_refilled_buy(&mut self, mut _order: BuyOrder) {
+        _order.set_nonce(self.get_nonce());
+        self.inc_nonce();
+        _order.refill();
+        self.buy_orders.push(_order);
+    }
+
+    fn insert_refilled_sell(&mut self, mut _order: SellOrder) {
+        _order.set_nonce(self.get_nonce());
+        self.inc_nonce();
+        _order.refill();
+        self.sell_orders.push(_order);
+    }
+
+    // The book as _viewer may see it: the reserve of other participants' iceberg orders is
+    // left out, so they appear as ordinary orders for their shown quantity. This only
+    // masks what a client displays. Global state is public and the processor needs each
+    // order's whole quantity to refill its peaks, so the reserve is stored in the clear and
+    // anyone reading the book's levels directly can see it.
+    pub fn view_for(&self, _viewer: Option<&Address>) -> OrderBook {
+        let mut view = self.clone();
+        let hidden = |x: &Address| _viewer != Some(x);
+        view.buy_orders = self
+            .buy_orders
+            .iter()
+            .cloned()
+            .map(|mut x| {
+                if x.peak.is_some() && hidden(x.get_addr_ref()) {
+                    x.qty = Quantity::new(x.visible_qty());
+                    x.peak = None;
+                }
+                x
+            })
+            .collect();
+        view.sell_orders = self
+            .sell_orders
+            .iter()
+            .cloned()
+            .map(|mut x| {
+                if x.peak.is_some() && hidden(x.get_addr_ref()) {
+                    x.qty = Quantity::new(x.visible_qty());
+                    x.peak = None;
+                }
+                x
+            })
+            .collect();
+        view
+    }
+
     // This function will only be called if 'fill_buy' has determined that
-    // the buy order can be filled; IE there exists a sell order at or below the buyer's price
+    // the buy order can be filled; IE there exists a sell order at or below the buyer's price.
+    // Trades execute at the resting order's price; the incoming buyer pays from their cash
+    // and the seller delivers from their hold.
     pub fn fill_or_insert_buy(
         &mut self,
         _balance_book: &mut BalanceBook,
-        _buy_order: BuyOrder,
-    ) -> Result<Option<BuyOrder>, Error> {
-        let lowest_sell = self.sell_orders.pop().expect("Somehow popped a 'none' in 'fill or insert buy' method which should have already checked for that.");
-        let lowest_sell_qty = lowest_sell.get_qty();
-        let buyer_addr_ref = _buy_order.get_addr_ref();
-        let seller_addr_ref = lowest_sell.get_addr_ref();
-
-        match _buy_order.get_qty() >= lowest_sell.get_qty() {
-            true => {
-                let deal_amt = lowest_sell.get_price() * lowest_sell.get_qty();
-                let if_buy_price = _buy_order.get_price() * lowest_sell.get_qty();
-                let ref_from_buyer_hold_cash = if_buy_price - deal_amt;
-                _balance_book.credit_cash(seller_addr_ref, deal_amt)?;
-                _balance_book.debit_cash(buyer_addr_ref, deal_amt)?;
-                _balance_book.credit_assets(buyer_addr_ref, lowest_sell_qty)?;
-                _balance_book.debit_hold_assets(seller_addr_ref, lowest_sell_qty)?;
-                let mut decd_buy_order = _buy_order.clone();
-                decd_buy_order.dec_qty_by(lowest_sell_qty);
-                Ok(Some(decd_buy_order))
-            }
-            false => {
-                let deal_amt = lowest_sell.get_price() * _buy_order.get_qty();
-                _balance_book.credit_cash(seller_addr_ref, deal_amt)?;
-                _balance_book.debit_cash(buyer_addr_ref, deal_amt)?;
-                _balance_book.credit_assets(buyer_addr_ref, _buy_order.get_qty())?;
-                _balance_book.debit_hold_assets(seller_addr_ref, _buy_order.get_qty())?;
-                let mut decd_sell_order = lowest_sell.clone();
-                decd_sell_order.dec_qty_by(_buy_order.get_qty());
-                self.sell_orders.push(decd_sell_order);
-                Ok(None)
-            }
+        mut _buy_order: BuyOrder,
+    ) -> Result<(Vec<Fill>, Option<BuyOrder>), Error> {
+        if self.matching != MatchingAlgorithm::Fifo {
+            return self.share_level_buy(_balance_book, _buy_order);
+        }
+        let mut lowest_sell = self.sell_orders.pop().expect("Somehow popped a 'none' in 'fill or insert buy' method which should have already checked for that.");
+        let price = lowest_sell.get_price();
+        let qty = cmp::min(_buy_order.get_qty(), lowest_sell.visible_qty());
+        let settled = settle(
+            _balance_book,
+            self,
+            (_buy_order.get_addr_ref(), BuyFunding::Liquid),
+            (lowest_sell.get_addr_ref(), SellFunding::Held),
+            Side::Buy,
+            price,
+            qty,
+        )?;
+        let fill = Fill::from_orders(&_buy_order, &lowest_sell, price, qty, settled, Side::Buy);
+        lowest_sell.dec_qty_by(qty)?;
+        self.put_back_sell(lowest_sell);
+        _buy_order.dec_qty_by(qty)?;
+        match _buy_order.get_qty() {
+            0 => Ok((vec![fill], None)),
+            _ => Ok((vec![fill], Some(_buy_order))),
         }
     }
 
+    // An incoming sell trades at each bid's own price. The resting buyer pays from the hold
+    // taken at that price.
     pub fn fill_or_insert_sell(
         &mut self,
         _balance_book: &mut BalanceBook,
-        _sell_order: SellOrder,
-    ) -> Result<Option<SellOrder>, Error> {
-        let highest_buy = self.buy_orders.pop().expect("Somehow popped a 'None' off the buy order bin heap; previous function call should have validated that there's something there");
-        let highest_buy_qty = highest_buy.get_qty();
-        let buyer_addr_ref = highest_buy.get_addr_ref();
-        let seller_addr_ref = _sell_order.get_addr_ref();
+        mut _sell_order: SellOrder,
+    ) -> Result<(Vec<Fill>, Option<SellOrder>), Error> {
+        if self.matching != MatchingAlgorithm::Fifo {
+            return self.share_level_sell(_balance_book, _sell_order);
+        }
+        let mut highest_buy = self.buy_orders.pop().expect("Somehow popped a 'None' off the buy order bin heap; previous function call should have validated that there's something there");
+        let price = highest_buy.get_price();
+        let qty = cmp::min(_sell_order.get_qty(), highest_buy.visible_qty());
+        let settled = settle(
+            _balance_book,
+            self,
+            (
+                highest_buy.get_addr_ref(),
+                BuyFunding::Held(price, highest_buy.get_qty(), highest_buy.fee_bps),
+            ),
+            (_sell_order.get_addr_ref(), SellFunding::Liquid),
+            Side::Sell,
+            price,
+            qty,
+        )?;
+        let fill = Fill::from_orders(&highest_buy, &_sell_order, price, qty, settled, Side::Sell);
+        highest_buy.dec_qty_by(qty)?;
+        self.put_back_buy(highest_buy);
+        _sell_order.dec_qty_by(qty)?;
+        match _sell_order.get_qty() {
+            0 => Ok((vec![fill], None)),
+            _ => Ok((vec![fill], Some(_sell_order))),
+        }
+    }
 
-        match _sell_order.get_qty() >= highest_buy.get_qty() {
-            true => {
-                let deal_amt = _sell_order.get_price() * highest_buy_qty;
-                let if_buy_price = highest_buy.get_price() * highest_buy_qty;
-                let ref_from_buyer_hold_cash = if_buy_price - deal_amt;
-                _balance_book.credit_cash(seller_addr_ref, deal_amt)?;
-                _balance_book.debit_hold_cash(buyer_addr_ref, if_buy_price)?;
-                _balance_book.credit_cash(buyer_addr_ref, ref_from_buyer_hold_cash)?;
-                _balance_book.credit_assets(buyer_addr_ref, highest_buy_qty)?;
-                _balance_book.debit_assets(seller_addr_ref, highest_buy_qty)?;
-                let mut decd_sell_order = _sell_order.clone();
-                decd_sell_order.dec_qty_by(highest_buy_qty);
-                Ok(Some(decd_sell_order))
+    // Shares the incoming buy among the sells at the lowest price as the book's matching
+    // algorithm says, one fill per sell that gets some, oldest first. None of them may be
+    // the buyer's own; fill_buy clears those out of the way first.
+    fn share_level_buy(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        mut _buy_order: BuyOrder,
+    ) -> Result<(Vec<Fill>, Option<BuyOrder>), Error> {
+        let price = self
+            .sell_orders
+            .best_price()
+            .expect("Shared out the sell side's best level while it was empty");
+        let level = self.sell_orders.take_level(price);
+        let shown: Vec<u64> = level.iter().map(|x| x.visible_qty()).collect();
+        let allocs = self.matching.allocate(_buy_order.get_qty(), &shown)?;
+        let mut fills: Vec<Fill> = Vec::new();
+        for (mut resting, qty) in level.into_iter().zip(allocs.into_iter()) {
+            if qty > 0 {
+                let settled = settle(
+                    _balance_book,
+                    self,
+                    (_buy_order.get_addr_ref(), BuyFunding::Liquid),
+                    (resting.get_addr_ref(), SellFunding::Held),
+                    Side::Buy,
+                    price,
+                    qty,
+                )?;
+                fills.push(Fill::from_orders(
+                    &_buy_order,
+                    &resting,
+                    price,
+                    qty,
+                    settled,
+                    Side::Buy,
+                ));
+                resting.dec_qty_by(qty)?;
+                _buy_order.dec_qty_by(qty)?;
             }
-            false => {
-                let deal_amt = _sell_order.get_price() * _sell_order.get_qty();
-                _balance_book.credit_cash(seller_addr_ref, deal_amt)?;
-                _balance_book.debit_hold_cash(buyer_addr_ref, deal_amt)?;
-                _balance_book.credit_assets(buyer_addr_ref, _sell_order.get_qty())?;
-                _balance_book.debit_assets(seller_addr_ref, _sell_order.get_qty())?;
-                let mut decd_buy_order = highest_buy.clone();
-                decd_buy_order.dec_qty_by(_sell_order.get_qty());
-                self.buy_orders.push(decd_buy_order);
-                Ok(None)
+            self.put_back_sell(resting);
+        }
+        match _buy_order.get_qty() {
+            0 => Ok((fills, None)),
+            _ => Ok((fills, Some(_buy_order))),
+        }
+    }
+
+    fn share_level_sell(
+        &mut self,
+        _balance_book: &mut BalanceBook,
+        mut _sell_order: SellOrder,
+    ) -> Result<(Vec<Fill>, Option<SellOrder>), Error> {
+        let price = self
+            .buy_orders
+            .best_price()
+            .expect("Shared out the buy side's best level while it was empty");
+        let level = self.buy_orders.take_level(price);
+        let shown: Vec<u64> = level.iter().map(|x| x.visible_qty()).collect();
+        let allocs = self.matching.allocate(_sell_order.get_qty(), &shown)?;
+        let mut fills: Vec<Fill> = Vec::new();
+        for (mut resting, qty) in level.into_iter().zip(allocs.into_iter()) {
+            if qty > 0 {
+                let settled = settle(
+                    _balance_book,
+                    self,
+                    (
+                        resting.get_addr_ref(),
+                        BuyFunding::Held(price, resting.get_qty(), resting.fee_bps),
+                    ),
+                    (_sell_order.get_addr_ref(), SellFunding::Liquid),
+                    Side::Sell,
+                    price,
+                    qty,
+                )?;
+                fills.push(Fill::from_orders(
+                    &resting,
+                    &_sell_order,
+                    price,
+                    qty,
+                    settled,
+                    Side::Sell,
+                ));
+                resting.dec_qty_by(qty)?;
+                _sell_order.dec_qty_by(qty)?;
             }
+            self.put_back_buy(resting);
+        }
+        match _sell_order.get_qty() {
+            0 => Ok((fills, None)),
+            _ => Ok((fills, Some(_sell_order))),
+        }
+    }
+
+    // What is left of a resting order that has just traded keeps its place, unless its
+    // shown quantity is used up; then it is refilled from its reserve.
+    fn put_back_buy(&mut self, _order: BuyOrder) {
+        match (_order.get_qty(), _order.visible_qty()) {
+            (0, _) => (),
+            (_, 0) => self.insert_refilled_buy(_order),
+            _ => self.buy_orders.push(_order),
+        }
+    }
+
+    fn put_back_sell(&mut self, _order: SellOrder) {
+        match (_order.get_qty(), _order.visible_qty()) {
+            (0, _) => (),
+            (_, 0) => self.insert_refilled_sell(_order),
+            _ => self.sell_orders.push(_order),
         }
     }
 }
 
-pub fn fill_buy(
-    _order_book: &mut OrderBook,
+// A resting buy order holds price * qty of its owner's cash and the most it can be charged
+// in fees on that; a resting sell order holds qty of its owner's assets.
+pub fn release_buy_hold(
     _balance_book: &mut BalanceBook,
-    mut _order: BuyOrder,
+    _precision: &Precision,
+    _order: &BuyOrder,
 ) -> Result<(), Error> {
+    let order_cash_amt = _precision.held(_order.get_price(), _order.get_qty(), _order.fee_bps)?;
+    _balance_book.debit_hold_cash(_order.get_addr_ref(), order_cash_amt)?;
+    _balance_book.credit_cash(_order.get_addr_ref(), order_cash_amt)
+}
+
+pub fn release_sell_hold(_balance_book: &mut BalanceBook, _order: &SellOrder) -> Result<(), Error> {
+    _balance_book.debit_hold_assets(
+        _order.get_addr_ref(),
+        _order.get_instrument(),
+        _order.get_qty(),
+    )?;
+    _balance_book.credit_assets(
+        _order.get_addr_ref(),
+        _order.get_instrument(),
+        _order.get_qty(),
+    )
+}
+
+pub fn check_instrument<T: OrderT>(_order_book: &OrderBook, _order: &T) -> Result<(), Error> {
+    if _order.get_instrument() != &_order_book.instrument {
+        return Err(Error::from(LibError::CustomError {
+            contents: format!(
+                "Order for {} submitted to the {} order book",
+                _order.get_instrument(),
+                _order_book.instrument
+            ),
+        }));
+    }
+    Ok(())
+}
+
+pub fn check_peak<T: OrderT>(_order: &T) -> Result<(), Error> {
+    if _order.get_peak() == Some(0) {
+        return Err(Error::from(LibError::CustomError {
+            contents: String::from("An iceberg order's peak quantity must be greater than 0"),
+        }));
+    }
+    Ok(())
+}
+
+// What fill_buy checks before it touches the book or balances: the order is for this book
+// with a valid peak, and a priced one is covered by its owner's cash at its limit, with the
+// most it can be charged in fees on it. Returns that cash.
+pub fn check_buy(
+    _order_book: &OrderBook,
+    _balance_book: &BalanceBook,
+    _order: &BuyOrder,
+) -> Result<u64, Error> {
+    check_instrument(_order_book, _order)?;
+    check_peak(_order)?;
     let buyer_liq_cash: u64 = match _balance_book.get_by_addr(&_order.get_addr()) {
-        Some(v) => v.cash,
+        Some(v) => v.cash.get(),
         None => {
             return Err(Error::from(LibError::CustomError {
                 contents: String::from("User could not be found."),
@@ -203,45 +1184,119 @@ pub fn fill_buy(
         }
     };
 
-    if buyer_liq_cash < (_order.get_qty() * _order.get_price()) {
-        return Err(Error::from(LibError::CustomError { contents: format!("Cannot place buy order for more assets than you currently have. Tried to buy: {}, have cash: {}\n", _order.get_qty(), buyer_liq_cash)}));
-    };
-    // else
-    let peeked_price = match _order_book.sell_orders.peek() {
-        Some(v) => v.get_price(),
-        _ => {
-            let order_cash_amt: u64 = _order.get_qty() * _order.get_price();
-            _balance_book.debit_cash(&_order.get_addr_ref(), order_cash_amt)?;
-            _balance_book.credit_hold_cash(&_order.get_addr_ref(), order_cash_amt)?;
-            _order.set_nonce(_order_book.get_nonce());
-            _order_book.nonce += 1;
-            _order_book.buy_orders.push(_order);
-            return Ok(());
+    if _order.get_order_type().has_limit() {
+        let cost = _order_book.precision.held(
+            _order.get_price(),
+            _order.get_qty(),
+            _order_book.fees.hold_bps(_order.get_addr_ref()),
+        )?;
+        if buyer_liq_cash < cost {
+            return Err(Error::from(LibError::CustomError { contents: format!("Cannot place buy order for more assets than you currently have. Tried to buy: {}, have cash: {}\n", _order.get_qty(), buyer_liq_cash)}));
         }
-    };
+    }
+    Ok(buyer_liq_cash)
+}
 
-    if _order.get_price() >= peeked_price {
-        match _order_book.fill_or_insert_buy(_balance_book, _order) {
-            Ok(Some(rem_buy)) => {
-                if rem_buy.get_qty() == 0 {
-                    return Ok(());
-                };
-                fill_buy(_order_book, _balance_book, rem_buy)
+// Matches the buy against the sell side a price level (or, under FIFO, a resting order) at
+// a time, or clears its owner's own sells out of its way, until it is filled, stops crossing
+// or is cancelled. What is left of a limit order then rests. Each round starts over with
+// what is left, in a loop rather than by recursion, so that sweeping a deep book takes no
+// more stack than filling against one order.
+pub fn fill_buy(
+    _order_book: &mut OrderBook,
+    _balance_book: &mut BalanceBook,
+    mut _order: BuyOrder,
+) -> Result<Vec<Fill>, Error> {
+    let mut fills: Vec<Fill> = Vec::new();
+    loop {
+        let buyer_liq_cash = check_buy(_order_book, _balance_book, &_order)?;
+        let limited = _order.get_order_type().has_limit();
+        // A fill-or-kill order can only count on other participants' orders, and is killed
+        // by meeting its owner's own unless they are to be cancelled out of its way.
+        if _order.get_order_type() == OrderType::FillOrKill {
+            let own =
+                _order_book.own_sell_qty_at_or_below(_order.get_price(), _order.get_addr_ref());
+            let others = _order_book.sell_qty_at_or_below(_order.get_price()) - own;
+            if others < _order.get_qty()
+                || (own > 0 && _order.get_stp() != SelfTradePrevention::CancelOldest)
+            {
+                return Ok(fills);
             }
-            _ => Ok(()),
         }
-    } else {
-        _order_book.insert_buy_order(_balance_book, _order, true)
+        // else
+        let peeked_price = match _order_book.sell_orders.peek() {
+            Some(v) => v.get_price(),
+            _ if !_order.get_order_type().rests() => return Ok(fills),
+            _ => {
+                _order_book.insert_buy_order(_balance_book, _order, true)?;
+                return Ok(fills);
+            }
+        };
+
+        if limited && _order.get_price() < peeked_price {
+            if _order.get_order_type().rests() {
+                _order_book.insert_buy_order(_balance_book, _order, true)?;
+            }
+            return Ok(fills);
+        }
+        // Under FIFO the incoming order only meets the oldest order at the best price;
+        // otherwise it meets the whole level.
+        let own_sell = match _order_book.matching {
+            MatchingAlgorithm::Fifo => _order_book
+                .sell_orders
+                .peek()
+                .map_or(false, |x| x.get_addr_ref() == _order.get_addr_ref()),
+            _ => _order_book
+                .sell_orders
+                .level(peeked_price)
+                .iter()
+                .any(|x| x.get_addr_ref() == _order.get_addr_ref()),
+        };
+        if own_sell {
+            match _order_book.prevent_self_trade_buy(_balance_book, _order)? {
+                Some(rem_buy) => {
+                    _order = rem_buy;
+                    continue;
+                }
+                None => return Ok(fills),
+            }
+        }
+        // A market buy takes no more than its owner's cash pays for at this price, fee and
+        // all. Prices only rise from here, so whatever that leaves is cancelled.
+        if !limited {
+            let spendable = _order_book
+                .fees
+                .spendable(_order.get_addr_ref(), buyer_liq_cash)?;
+            let affordable = _order_book.precision.affordable(peeked_price, spendable);
+            if affordable == 0 {
+                return Ok(fills);
+            }
+            if affordable < _order.get_qty() {
+                let excess = _order.get_qty() - affordable;
+                _order.dec_qty_by(excess)?;
+            }
+        }
+        let (filled, remainder) = _order_book.fill_or_insert_buy(_balance_book, _order)?;
+        fills.extend(filled);
+        _order_book.last_price = Some(peeked_price);
+        match remainder {
+            Some(rem_buy) => _order = rem_buy,
+            None => return Ok(fills),
+        }
     }
 }
 
-pub fn fill_sell(
-    _order_book: &mut OrderBook,
-    _balance_book: &mut BalanceBook,
-    mut _order: SellOrder,
+// What fill_sell checks before it touches the book or balances: the order is for this
+// book with a valid peak, and its owner has the assets it sells.
+pub fn check_sell(
+    _order_book: &OrderBook,
+    _balance_book: &BalanceBook,
+    _order: &SellOrder,
 ) -> Result<(), Error> {
+    check_instrument(_order_book, _order)?;
+    check_peak(_order)?;
     let seller_liq_assets: u64 = match _balance_book.get_by_addr(&_order.get_addr()) {
-        Some(v) => v.assets,
+        Some(v) => v.assets(&_order_book.instrument),
         None => {
             return Err(Error::from(LibError::CustomError {
                 contents: String::from("User could not be found."),
@@ -252,36 +1307,72 @@ pub fn fill_sell(
     if seller_liq_assets < _order.get_qty() {
         return Err(Error::from(LibError::CustomError { contents: format!("Cannot place sell order for more assets than you currently have. Have: {}, tried to sell: {}\n", seller_liq_assets, _order.get_qty())}));
     };
-    // else
-    /*
-    let peeked_price = match _order_book.buy_orders.peek() {
-        Some(v) => v.get_price(),
-        _ => return _order_book.insert_sell_order(_balance_book, _order, true)
-    };
-    */
-    let peeked_price = match _order_book.buy_orders.peek() {
-        Some(v) => v.get_price(),
-        _ => {
-            _balance_book.debit_assets(&_order.get_addr_ref(), _order.get_qty())?;
-            _balance_book.credit_hold_assets(&_order.get_addr_ref(), _order.get_qty())?;
-            _order.set_nonce(_order_book.get_nonce());
-            _order_book.nonce += 1;
-            _order_book.sell_orders.push(_order);
-            return Ok(());
+    Ok(())
+}
+
+// As fill_buy, against the buy side.
+pub fn fill_sell(
+    _order_book: &mut OrderBook,
+    _balance_book: &mut BalanceBook,
+    mut _order: SellOrder,
+) -> Result<Vec<Fill>, Error> {
+    let mut fills: Vec<Fill> = Vec::new();
+    loop {
+        check_sell(_order_book, _balance_book, &_order)?;
+        if _order.get_order_type() == OrderType::FillOrKill {
+            let own =
+                _order_book.own_buy_qty_at_or_above(_order.get_price(), _order.get_addr_ref());
+            let others = _order_book.buy_qty_at_or_above(_order.get_price()) - own;
+            if others < _order.get_qty()
+                || (own > 0 && _order.get_stp() != SelfTradePrevention::CancelOldest)
+            {
+                return Ok(fills);
+            }
         }
-    };
+        // else
+        let peeked_price = match _order_book.buy_orders.peek() {
+            Some(v) => v.get_price(),
+            _ if !_order.get_order_type().rests() => return Ok(fills),
+            _ => {
+                _order_book.insert_sell_order(_balance_book, _order, true)?;
+                return Ok(fills);
+            }
+        };
 
-    if _order.get_price() <= peeked_price {
-        match _order_book.fill_or_insert_sell(_balance_book, _order) {
-            Ok(Some(rem_sell)) => {
-                if rem_sell.get_qty() == 0 {
-                    return Ok(());
-                };
-                fill_sell(_order_book, _balance_book, rem_sell)
+        if _order.get_order_type().has_limit() && _order.get_price() > peeked_price {
+            if _order.get_order_type().rests() {
+                _order_book.insert_sell_order(_balance_book, _order, true)?;
             }
-            _ => Ok(()),
+            return Ok(fills);
+        }
+        // Under FIFO the incoming order only meets the oldest order at the best price;
+        // otherwise it meets the whole level.
+        let own_buy = match _order_book.matching {
+            MatchingAlgorithm::Fifo => _order_book
+                .buy_orders
+                .peek()
+                .map_or(false, |x| x.get_addr_ref() == _order.get_addr_ref()),
+            _ => _order_book
+                .buy_orders
+                .level(peeked_price)
+                .iter()
+                .any(|x| x.get_addr_ref() == _order.get_addr_ref()),
+        };
+        if own_buy {
+            match _order_book.prevent_self_trade_sell(_balance_book, _order)? {
+                Some(rem_sell) => {
+                    _order = rem_sell;
+                    continue;
+                }
+                None => return Ok(fills),
+            }
+        }
+        let (filled, remainder) = _order_book.fill_or_insert_sell(_balance_book, _order)?;
+        fills.extend(filled);
+        _order_book.last_price = Some(peeked_price);
+        match remainder {
+            Some(rem_sell) => _order = rem_sell,
+            None => return Ok(fills),
         }
-    } else {
-        _order_book.insert_sell_order(_balance_book, _order, true)
     }
 }
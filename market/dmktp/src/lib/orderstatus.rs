@@ -0,0 +1,233 @@
+use address::Address;
+use crypto::digest::Digest;
+use crypto::sha2::Sha512;
+use instrument::default_instrument;
+use order::{OrderT, OrderType, Side};
+use orderbook::Fill;
+use std::collections::BTreeMap;
+use std::fmt;
+use stoporder::StopOrder;
+
+// Order IDs are derived from the signature of the transaction which submitted the order,
+// so every validator assigns the same ID and the submitter can recompute it.
+pub fn order_id(_signature: &str) -> String {
+    let mut hasher = Sha512::new();
+    hasher.input(_signature.as_bytes());
+    let mut digest = hasher.result_str();
+    digest.truncate(32);
+    digest
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Open,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+    Expired,
+    // A stop order waiting for its trigger price.
+    Pending,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderRecord {
+    pub id: String,
+    pub side: Side,
+    #[serde(default = "default_instrument")]
+    pub instrument: String,
+    #[serde(default)]
+    pub order_type: OrderType,
+    pub addr: Address,
+    pub price: u64,
+    pub qty: u64,
+    pub filled: u64,
+    pub status: OrderStatus,
+    #[serde(default)]
+    pub trigger: Option<u64>,
+    #[serde(default)]
+    pub peak: Option<u64>,
+}
+
+// Lifecycle of orders the market has seen, keyed by order ID. Each record is stored at its
+// own address (see addressing::order_status_addr); the processor loads those a transaction
+// may touch, and records it didn't load are left alone.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderStatusBook {
+    pub records: BTreeMap<String, OrderRecord>,
+}
+
+impl fmt::Display for OrderRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Order {}\n    side: {}    type: {}    instrument: {}\n    owner: {}    price: {}\n    qty: {}\n    filled: {}\n    status: {:?}\n",
+            self.id, self.side, self.order_type, self.instrument, self.addr, self.price, self.qty, self.filled, self.status
+        )?;
+        match self.trigger {
+            Some(v) => write!(f, "    stop trigger: {}\n", v),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for OrderStatusBook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.records.values().try_for_each(|x| write!(f, "\n{}", x))
+    }
+}
+
+impl OrderRecord {
+    pub fn from_order<T: OrderT>(_order: &T) -> Self {
+        OrderRecord {
+            id: _order.get_id().clone(),
+            side: _order.side(),
+            instrument: _order.get_instrument().clone(),
+            order_type: _order.get_order_type(),
+            addr: _order.get_addr(),
+            price: _order.get_price(),
+            qty: _order.get_qty(),
+            filled: 0,
+            status: OrderStatus::Open,
+            trigger: None,
+            peak: _order.get_peak(),
+        }
+    }
+
+    pub fn from_stop(_stop: &StopOrder) -> Self {
+        let mut record = match _stop {
+            StopOrder::Buy(_, order) => OrderRecord::from_order(order),
+            StopOrder::Sell(_, order) => OrderRecord::from_order(order),
+        };
+        record.status = OrderStatus::Pending;
+        record.trigger = Some(_stop.get_trigger());
+        record
+    }
+
+    pub fn is_live(&self) -> bool {
+        match self.status {
+            OrderStatus::Open | OrderStatus::PartiallyFilled => true,
+            _ => false,
+        }
+    }
+
+    fn add_fill(&mut self, _qty: u64) {
+        self.filled += _qty;
+        self.status = if self.filled >= self.qty {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        };
+    }
+}
+
+impl OrderStatusBook {
+    pub fn new() -> Self {
+        OrderStatusBook {
+            records: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, _id: &String) -> Option<&OrderRecord> {
+        self.records.get(_id)
+    }
+
+    pub fn for_owner(&self, _addr: &Address) -> Vec<&OrderRecord> {
+        self.records.values().filter(|x| &x.addr == _addr).collect()
+    }
+
+    // Records a newly accepted order and the fills it produced against the book. Whatever
+    // an order that doesn't rest left unfilled was cancelled during matching.
+    pub fn record_order<T: OrderT>(&mut self, _order: &T, _fills: &Vec<Fill>) {
+        self.records
+            .insert(_order.get_id().clone(), OrderRecord::from_order(_order));
+        self.apply_fills(_fills);
+        if !_order.get_order_type().rests() {
+            self.mark_cancelled(&vec![_order.get_id().clone()]);
+        }
+    }
+
+    pub fn record_stop(&mut self, _stop: &StopOrder) {
+        self.records
+            .insert(_stop.get_id().clone(), OrderRecord::from_stop(_stop));
+    }
+
+    // A triggered stop keeps its record, and from then on is tracked like any other order.
+    pub fn record_triggered(&mut self, _stop: &StopOrder, _fills: &Vec<Fill>) {
+        let mut record = OrderRecord::from_stop(_stop);
+        record.status = OrderStatus::Open;
+        self.records.insert(_stop.get_id().clone(), record);
+        self.apply_fills(_fills);
+        if !_stop.get_order_type().rests() {
+            self.mark_cancelled(&vec![_stop.get_id().clone()]);
+        }
+    }
+
+    pub fn record_rejected_stop(&mut self, _stop: &StopOrder) {
+        let mut record = OrderRecord::from_stop(_stop);
+        record.status = OrderStatus::Rejected;
+        self.records.insert(_stop.get_id().clone(), record);
+    }
+
+    pub fn record_rejected<T: OrderT>(&mut self, _order: &T) {
+        let mut record = OrderRecord::from_order(_order);
+        record.status = OrderStatus::Rejected;
+        self.records.insert(_order.get_id().clone(), record);
+    }
+
+    // Fills touch two orders; orders placed before IDs existed have no record and are
+    // skipped.
+    pub fn apply_fills(&mut self, _fills: &Vec<Fill>) {
+        for fill in _fills.iter() {
+            if let Some(record) = self.records.get_mut(&fill.buy_id) {
+                record.add_fill(fill.qty);
+            }
+            if let Some(record) = self.records.get_mut(&fill.sell_id) {
+                record.add_fill(fill.qty);
+            }
+        }
+    }
+
+    // `_open_qty` is what is left on the book after the amendment; the record's qty stays the
+    // total over the order's life.
+    pub fn record_amended(&mut self, _id: &String, _price: u64, _open_qty: u64) {
+        if let Some(record) = self.records.get_mut(_id) {
+            record.price = _price;
+            record.qty = record.filled + _open_qty;
+        }
+    }
+
+    // Applies what self-trade prevention took off orders: (order ID, quantity) pairs. An
+    // order that lost all of its open quantity is cancelled; otherwise its size shrinks.
+    pub fn record_self_trades(&mut self, _removed: &Vec<(String, u64)>) {
+        for (id, qty) in _removed.iter() {
+            if let Some(record) = self.records.get_mut(id) {
+                let open = record.qty - record.filled;
+                if *qty < open {
+                    record.qty -= *qty;
+                } else if record.is_live() {
+                    record.status = OrderStatus::Cancelled;
+                }
+            }
+        }
+    }
+
+    pub fn mark_expired(&mut self, _ids: &Vec<String>) {
+        for id in _ids.iter() {
+            if let Some(record) = self.records.get_mut(id) {
+                record.status = OrderStatus::Expired;
+            }
+        }
+    }
+
+    // Orders that already filled completely keep their Filled status.
+    pub fn mark_cancelled(&mut self, _ids: &Vec<String>) {
+        for id in _ids.iter() {
+            if let Some(record) = self.records.get_mut(id) {
+                if record.is_live() || record.status == OrderStatus::Pending {
+                    record.status = OrderStatus::Cancelled;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use addressing;
+use errors::LibError::{self, CustomError, EncodingError, LengthError, PermissionError};
+use std::collections::BTreeSet;
+use std::fmt;
+
+// Public keys which may submit privileged transactions (clearing state, opening and
+// closing auctions, admin messages). Stored on-chain at addressing::PERMISSIONS so each
+// experiment can have its own operator; addressing::ADMIN is only the bootstrap value
+// used until the first permission message is processed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Permissions {
+    pub admins: BTreeSet<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum PermissionMsg {
+    AddAdmin(String /* public key */),
+    RemoveAdmin(String /* public key */),
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Authorized admin keys:\n")?;
+        self.admins
+            .iter()
+            .try_for_each(|x| write!(f, "    {}\n", x))
+    }
+}
+
+impl Permissions {
+    pub fn new() -> Self {
+        let mut admins = BTreeSet::new();
+        admins.insert(String::from(addressing::ADMIN));
+        Permissions { admins: admins }
+    }
+
+    pub fn is_admin(&self, _pubkey: &str) -> bool {
+        self.admins.contains(_pubkey)
+    }
+
+    pub fn check_admin(&self, _pubkey: &str, _action: &str) -> Result<(), LibError> {
+        match self.is_admin(_pubkey) {
+            true => Ok(()),
+            false => Err(PermissionError {
+                signer: String::from(_pubkey),
+                action: String::from(_action),
+            }),
+        }
+    }
+
+    pub fn apply(&mut self, _msg: PermissionMsg) -> Result<(), LibError> {
+        match _msg {
+            PermissionMsg::AddAdmin(_pubkey) => {
+                check_pubkey(&_pubkey)?;
+                self.admins.insert(_pubkey);
+                Ok(())
+            }
+            PermissionMsg::RemoveAdmin(_pubkey) => {
+                if !self.admins.contains(&_pubkey) {
+                    return Err(CustomError {
+                        contents: format!("{} is not an admin key", _pubkey),
+                    });
+                }
+                // Removing the last admin would lock everyone out of the privileged routes.
+                if self.admins.len() == 1 {
+                    return Err(CustomError {
+                        contents: String::from("Cannot remove the only remaining admin key"),
+                    });
+                }
+                self.admins.remove(&_pubkey);
+                Ok(())
+            }
+        }
+    }
+}
+
+// Signers are identified by their compressed secp256k1 public key, as lowercase hex: 02 or
+// 03 followed by the 32 byte x coordinate. Anything else could never sign a transaction.
+fn check_pubkey(_pubkey: &str) -> Result<(), LibError> {
+    if _pubkey.len() != 66 {
+        return Err(LengthError {
+            field: String::from("Admin public key"),
+            size: 66,
+            got: _pubkey.len(),
+        });
+    }
+    let hex = _pubkey.chars().all(|c| c.is_digit(16) && !c.is_uppercase());
+    if !hex || !(_pubkey.starts_with("02") || _pubkey.starts_with("03")) {
+        return Err(EncodingError {
+            field: String::from("Admin public key"),
+            encoding: String::from("compressed public key (02 or 03 then 64 lowercase hex)"),
+            got: String::from(_pubkey),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod permission_tests {
+    use super::*;
+
+    const OPERATOR: &'static str =
+        "025a96a6b38a4b852182aca678153779e646a2899efb1ebfff57cef3fffc421b16";
+
+    #[test]
+    fn test_add_remove_admin() {
+        let mut perms = Permissions::new();
+        assert!(perms.is_admin(addressing::ADMIN));
+        assert!(!perms.is_admin(OPERATOR));
+
+        perms
+            .apply(PermissionMsg::AddAdmin(String::from(OPERATOR)))
+            .unwrap();
+        assert!(perms.check_admin(OPERATOR, "test").is_ok());
+
+        perms
+            .apply(PermissionMsg::RemoveAdmin(String::from(addressing::ADMIN)))
+            .unwrap();
+        assert!(perms.check_admin(addressing::ADMIN, "test").is_err());
+
+        // the operator is now the only admin and can't be removed
+        assert!(perms
+            .apply(PermissionMsg::RemoveAdmin(String::from(OPERATOR)))
+            .is_err());
+        assert!(perms
+            .apply(PermissionMsg::AddAdmin(String::from("03d8")))
+            .is_err());
+
+        // right length, but not a compressed public key
+        let uncompressed = OPERATOR.replacen("02", "04", 1);
+        let not_hex = OPERATOR.replacen("1b16", "1g16", 1);
+        let upper = OPERATOR.to_uppercase();
+        for key in vec![uncompressed, not_hex, upper] {
+            assert!(perms.apply(PermissionMsg::AddAdmin(key)).is_err());
+        }
+
+        // removing a key that isn't an admin is refused rather than ignored
+        assert!(perms
+            .apply(PermissionMsg::RemoveAdmin(String::from(addressing::ADMIN)))
+            .is_err());
+        assert_eq!(perms.admins.len(), 1);
+    }
+}
@@ -0,0 +1,303 @@
+use order::{BuyOrder, OrderT, SellOrder};
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::collections::{btree_map, vec_deque, BTreeMap, VecDeque};
+use std::iter::FromIterator;
+use std::vec;
+
+// The side of the book an order rests on decides which end of the price range trades
+// first.
+pub trait LevelOrder: OrderT + Clone {
+    const HIGHEST_FIRST: bool;
+}
+
+impl LevelOrder for BuyOrder {
+    const HIGHEST_FIRST: bool = true;
+}
+
+impl LevelOrder for SellOrder {
+    const HIGHEST_FIRST: bool = false;
+}
+
+// One side of an order book. Orders are grouped by price into levels, each a queue in nonce
+// (time) order, so the front of the best level is the order a BinaryHeap with the orders'
+// Ord would pop. An index from order ID to price finds an order without scanning the book;
+// orders without an ID are not indexed.
+//
+// Serializes as the list of orders in priority order, so equal books always encode the same.
+// The BinaryHeap this replaced encoded its orders in heap order, which isn't priority
+// order; a book stored that way still reads back, as reading regroups the orders by price
+// and nonce whatever order they come in, and is encoded in priority order once rewritten.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceLevels<T> {
+    levels: BTreeMap<u64, VecDeque<T>>,
+    index: BTreeMap<String, u64>,
+    len: usize,
+}
+
+// Iterates the orders of a PriceLevels best first.
+pub struct Iter<'a, T: 'a> {
+    levels: vec::IntoIter<&'a VecDeque<T>>,
+    current: Option<vec_deque::Iter<'a, T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            if let Some(ref mut orders) = self.current {
+                if let Some(v) = orders.next() {
+                    return Some(v);
+                }
+            }
+            self.current = Some(self.levels.next()?.iter());
+        }
+    }
+}
+
+impl<T: LevelOrder> Default for PriceLevels<T> {
+    fn default() -> Self {
+        PriceLevels::new()
+    }
+}
+
+impl<T: LevelOrder> FromIterator<T> for PriceLevels<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(_orders: I) -> Self {
+        let mut levels = PriceLevels::new();
+        for order in _orders {
+            levels.push(order);
+        }
+        levels
+    }
+}
+
+impl<T: LevelOrder + Serialize> Serialize for PriceLevels<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de, T: LevelOrder + Deserialize<'de>> Deserialize<'de> for PriceLevels<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<T>::deserialize(deserializer).map(|x| x.into_iter().collect())
+    }
+}
+
+impl<T: LevelOrder> PriceLevels<T> {
+    pub fn new() -> Self {
+        PriceLevels {
+            levels: BTreeMap::new(),
+            index: BTreeMap::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.levels.clear();
+        self.index.clear();
+        self.len = 0;
+    }
+
+    // Prices with orders resting at them, best first.
+    pub fn prices(&self) -> Vec<u64> {
+        let prices = self.levels.keys().cloned();
+        match T::HIGHEST_FIRST {
+            true => prices.rev().collect(),
+            false => prices.collect(),
+        }
+    }
+
+    pub fn best_price(&self) -> Option<u64> {
+        match T::HIGHEST_FIRST {
+            true => self.levels.keys().next_back().cloned(),
+            false => self.levels.keys().next().cloned(),
+        }
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.best_price()
+            .and_then(|x| self.levels.get(&x))
+            .and_then(|x| x.front())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let price = self.best_price()?;
+        self.take(price, 0)
+    }
+
+    // Queues _order behind every order at its price with a lower nonce. New orders go to the
+    // back; an order put back after a partial fill goes to the front, where it came from.
+    pub fn push(&mut self, _order: T) {
+        let price = _order.get_price();
+        let nonce = _order.get_nonce();
+        if !_order.get_id().is_empty() {
+            self.index.insert(_order.get_id().clone(), price);
+        }
+        let queue = self.levels.entry(price).or_insert_with(VecDeque::new);
+        let back = queue.back().map(|x| x.get_nonce());
+        let front = queue.front().map(|x| x.get_nonce());
+        match (front, back) {
+            (_, Some(v)) if nonce < v => {
+                let at = match front {
+                    Some(f) if nonce < f => 0,
+                    _ => queue
+                        .iter()
+                        .position(|x| x.get_nonce() > nonce)
+                        .unwrap_or(queue.len()),
+                };
+                queue.insert(at, _order);
+            }
+            _ => queue.push_back(_order),
+        }
+        self.len += 1;
+    }
+
+    pub fn iter<'a>(&'a self) -> Iter<'a, T> {
+        let levels: Vec<&VecDeque<T>> = match T::HIGHEST_FIRST {
+            true => self.levels.values().rev().collect(),
+            false => self.levels.values().collect(),
+        };
+        Iter {
+            levels: levels.into_iter(),
+            current: None,
+        }
+    }
+
+    // The orders best first.
+    pub fn into_vec(self) -> Vec<T> {
+        let levels: Vec<VecDeque<T>> = match T::HIGHEST_FIRST {
+            true => self.levels.into_iter().rev().map(|x| x.1).collect(),
+            false => self.levels.into_iter().map(|x| x.1).collect(),
+        };
+        levels.into_iter().flat_map(|x| x.into_iter()).collect()
+    }
+
+    // The orders resting at _price, oldest first.
+    pub fn level(&self, _price: u64) -> Vec<&T> {
+        match self.levels.get(&_price) {
+            Some(v) => v.iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Removes and returns every order at _price, oldest first.
+    pub fn take_level(&mut self, _price: u64) -> Vec<T> {
+        let queue = match self.levels.remove(&_price) {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+        for order in queue.iter() {
+            self.unindex(order, _price);
+        }
+        self.len -= queue.len();
+        queue.into_iter().collect()
+    }
+
+    pub fn get(&self, _id: &str) -> Option<&T> {
+        let price = self.index.get(_id)?;
+        self.levels.get(price)?.iter().find(|x| x.get_id() == _id)
+    }
+
+    pub fn contains(&self, _id: &str) -> bool {
+        self.get(_id).is_some()
+    }
+
+    pub fn remove(&mut self, _id: &str) -> Option<T> {
+        let price = *self.index.get(_id)?;
+        let at = self
+            .levels
+            .get(&price)?
+            .iter()
+            .position(|x| x.get_id() == _id)?;
+        self.take(price, at)
+    }
+
+    // Removes the best order satisfying _pred.
+    pub fn remove_first<F: FnMut(&T) -> bool>(&mut self, mut _pred: F) -> Option<T> {
+        for price in self.prices() {
+            let found = self.levels[&price].iter().position(|x| _pred(x));
+            if let Some(at) = found {
+                return self.take(price, at);
+            }
+        }
+        None
+    }
+
+    // Removes every order satisfying _pred and returns them best first.
+    pub fn remove_where<F: FnMut(&T) -> bool>(&mut self, mut _pred: F) -> Vec<T> {
+        let mut removed = Vec::new();
+        for price in self.prices() {
+            let queue = match self.levels.remove(&price) {
+                Some(v) => v,
+                None => continue,
+            };
+            let (taken, kept): (VecDeque<T>, VecDeque<T>) =
+                queue.into_iter().partition(|x| _pred(x));
+            if !kept.is_empty() {
+                self.levels.insert(price, kept);
+            }
+            for order in taken.into_iter() {
+                self.unindex(&order, price);
+                self.len -= 1;
+                removed.push(order);
+            }
+        }
+        removed
+    }
+
+    // Total quantity resting at _price or better, i.e. what an incoming order limited to
+    // _price could trade against. Totals that would overflow are capped.
+    pub fn qty_through(&self, _price: u64) -> u64 {
+        let through: btree_map::Range<u64, VecDeque<T>> = match T::HIGHEST_FIRST {
+            true => self.levels.range(_price..),
+            false => self.levels.range(..=_price),
+        };
+        through
+            .flat_map(|x| x.1.iter())
+            .fold(0, |acc: u64, x| acc.saturating_add(x.get_qty()))
+    }
+
+    // (price, total quantity, number of orders) for each level, best first.
+    pub fn depth(&self) -> Vec<(u64, u64, usize)> {
+        self.prices()
+            .into_iter()
+            .map(|x| {
+                let queue = &self.levels[&x];
+                let qty = queue
+                    .iter()
+                    .fold(0, |acc: u64, o| acc.saturating_add(o.get_qty()));
+                (x, qty, queue.len())
+            })
+            .collect()
+    }
+
+    fn take(&mut self, _price: u64, _at: usize) -> Option<T> {
+        let (order, emptied) = {
+            let queue = self.levels.get_mut(&_price)?;
+            let order = queue.remove(_at)?;
+            (order, queue.is_empty())
+        };
+        if emptied {
+            self.levels.remove(&_price);
+        }
+        self.unindex(&order, _price);
+        self.len -= 1;
+        Some(order)
+    }
+
+    // Leaves the index alone if another order with the same ID was pushed since.
+    fn unindex(&mut self, _order: &T, _price: u64) {
+        if self.index.get(_order.get_id().as_str()) == Some(&_price) {
+            self.index.remove(_order.get_id().as_str());
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use errors::LibError;
+use fees::MAX_BPS;
+use std::fmt;
+use std::str::FromStr;
+
+// What a price band is measured from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferencePrice {
+    // The book's most recent fill.
+    LastTrade,
+    // The last fill as of the most recent market step, i.e. the previous period's close.
+    PrevClose,
+}
+
+impl fmt::Display for ReferencePrice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReferencePrice::LastTrade => write!(f, "last"),
+            ReferencePrice::PrevClose => write!(f, "close"),
+        }
+    }
+}
+
+impl FromStr for ReferencePrice {
+    type Err = LibError;
+
+    fn from_str(_s: &str) -> Result<Self, LibError> {
+        match _s {
+            "last" => Ok(ReferencePrice::LastTrade),
+            "close" => Ok(ReferencePrice::PrevClose),
+            _ => Err(LibError::CustomError {
+                contents: format!("Unknown reference price {}; expected last or close", _s),
+            }),
+        }
+    }
+}
+
+// Orders priced more than `width_bps` basis points either side of the reference price are
+// rejected. Until the instrument has traded there is no reference and no band.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PriceBand {
+    pub reference: ReferencePrice,
+    pub width_bps: u64,
+}
+
+// The longest circuit breaker window, in blocks. Each book keeps the prices of its window,
+// so this bounds what it stores.
+pub const MAX_BREAKER_WINDOW: u64 = 1_000;
+
+// Halts matching on an instrument for `halt_blocks` blocks once its trades within the last
+// `window` blocks (the current one included) span more than `move_bps` basis points of the
+// lowest of them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CircuitBreaker {
+    pub move_bps: u64,
+    pub window: u64,
+    #[serde(rename = "halt_steps")]
+    pub halt_blocks: u64,
+}
+
+// A tripped circuit breaker, kept on the instrument's book. Trading resumes with block
+// `resumes_at`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Halt {
+    pub instrument: String,
+    // The trade price that tripped the breaker.
+    pub price: u64,
+    // The block the breaker tripped in.
+    #[serde(rename = "step")]
+    pub block: u64,
+    pub resumes_at: u64,
+}
+
+impl fmt::Display for PriceBand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "band {} bps around the {} price",
+            self.width_bps, self.reference
+        )
+    }
+}
+
+impl fmt::Display for CircuitBreaker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "halt {} blocks on a {} bps move within {} blocks",
+            self.halt_blocks, self.move_bps, self.window
+        )
+    }
+}
+
+impl fmt::Display for Halt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} halted at block {} after trading at {}; resumes at block {}",
+            self.instrument, self.block, self.price, self.resumes_at
+        )
+    }
+}
+
+impl PriceBand {
+    // The lowest and highest prices the band allows around _reference.
+    pub fn limits(&self, _reference: u64) -> (u64, u64) {
+        let width = _reference.saturating_mul(self.width_bps) / MAX_BPS;
+        (
+            _reference.saturating_sub(width),
+            _reference.saturating_add(width),
+        )
+    }
+
+    pub fn check(&self, _price: u64, _reference: Option<u64>) -> Result<(), LibError> {
+        let (low, high) = match _reference {
+            Some(v) => self.limits(v),
+            None => return Ok(()),
+        };
+        if _price < low || _price > high {
+            return Err(LibError::PriceBandError {
+                price: _price,
+                low: low,
+                high: high,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl CircuitBreaker {
+    pub fn check(&self) -> Result<(), LibError> {
+        if self.move_bps == 0 {
+            return Err(LibError::EmptyZeroError {
+                field: String::from("Circuit breaker move"),
+            });
+        } else if self.window == 0 {
+            return Err(LibError::EmptyZeroError {
+                field: String::from("Circuit breaker window"),
+            });
+        } else if self.window > MAX_BREAKER_WINDOW {
+            return Err(LibError::CustomError {
+                contents: format!(
+                    "Circuit breaker window of {} blocks is longer than the {} allowed",
+                    self.window, MAX_BREAKER_WINDOW
+                ),
+            });
+        } else if self.halt_blocks == 0 {
+            return Err(LibError::EmptyZeroError {
+                field: String::from("Circuit breaker halt length"),
+            });
+        }
+        Ok(())
+    }
+
+    // Whether the (block, price) history of trades within the window has moved too far.
+    pub fn is_tripped(&self, _history: &[(u64, u64)]) -> bool {
+        let low = _history.iter().map(|x| x.1).min();
+        let high = _history.iter().map(|x| x.1).max();
+        match (low, high) {
+            (Some(low), Some(high)) => {
+                (high - low).saturating_mul(MAX_BPS) > low.saturating_mul(self.move_bps)
+            }
+            _ => false,
+        }
+    }
+}
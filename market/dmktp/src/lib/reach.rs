@@ -0,0 +1,245 @@
+use address::Address;
+use order::{OrderT, Side};
+use orderbook::OrderBook;
+use pricelevels::{LevelOrder, PriceLevels};
+use std::collections::BTreeSet;
+use stoporder::StopOrder;
+
+// What an order may touch when it is matched against a book: the resting orders it may
+// trade with or cancel out of its way, and the waiting stops its fills may set off, along
+// with what those may touch in turn. Working this out before matching lets the processor
+// read just the levels and accounts the match needs, and the client declare just those.
+//
+// It errs towards too much. Whole levels are taken, every order counts for its full
+// quantity and the orders of anyone taking part for none, and a stop is taken to fire once
+// any price that may trade crosses its trigger.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reach {
+    // Prices of the levels reached on each side, best first.
+    pub buy_levels: Vec<u64>,
+    pub sell_levels: Vec<u64>,
+    // Levels the order and the stops may rest on, on their own side.
+    pub rests: Vec<(Side, u64)>,
+    // (owner, ID) of each resting order on those levels and each stop that may fire.
+    pub orders: Vec<(Address, String)>,
+    // Stored levels the walk came to that the book hasn't read in (see
+    // getset::get_book_header). Until there are none the rest is incomplete; the caller
+    // reads them and works the reach out again.
+    pub unread: Vec<(Side, u64)>,
+}
+
+impl Reach {
+    pub fn owners(&self) -> BTreeSet<Address> {
+        self.orders.iter().map(|x| x.0.clone()).collect()
+    }
+
+    // Every level reached or rested on, by the side of the book it is on.
+    pub fn levels(&self) -> BTreeSet<(Side, u64)> {
+        self.buy_levels
+            .iter()
+            .map(|x| (Side::Buy, *x))
+            .chain(self.sell_levels.iter().map(|x| (Side::Sell, *x)))
+            .chain(self.rests.iter().cloned())
+            .collect()
+    }
+}
+
+// An order taking from the book. A market order has no limit, and only a limit order
+// rests.
+struct Taker<'a> {
+    side: Side,
+    owner: &'a Address,
+    qty: u64,
+    limit: Option<u64>,
+    rests: bool,
+}
+
+fn taker<T: OrderT>(_order: &T) -> Taker {
+    Taker {
+        side: _order.side(),
+        owner: _order.get_addr_ref(),
+        qty: _order.get_qty(),
+        limit: match _order.get_order_type().has_limit() {
+            true => Some(_order.get_price()),
+            false => None,
+        },
+        rests: _order.get_order_type().rests(),
+    }
+}
+
+fn stop_taker(_stop: &StopOrder) -> Taker {
+    match _stop {
+        StopOrder::Buy(_, order) => taker(order),
+        StopOrder::Sell(_, order) => taker(order),
+    }
+}
+
+// _order is taken to be matched at _block, after the book's expired orders are purged.
+pub fn reach<T: OrderT>(_ob: &OrderBook, _order: &T, _block: u64) -> Reach {
+    reach_at(_ob, _order, _block, false)
+}
+
+// What _order may reach at _block or any block after, for a client that doesn't know which
+// block it will be processed in: every order that has an expiry is taken to have gone.
+pub fn reach_from<T: OrderT>(_ob: &OrderBook, _order: &T, _block: u64) -> Reach {
+    reach_at(_ob, _order, _block, true)
+}
+
+fn reach_at<T: OrderT>(_ob: &OrderBook, _order: &T, _block: u64, _later: bool) -> Reach {
+    let stops: Vec<&StopOrder> = _ob
+        .stop_orders
+        .iter()
+        .filter(|x| !x.is_expired(_block))
+        .collect();
+    // Each stop that may fire takes from the book too, reaching further and maybe setting
+    // off more; this only grows, so it settles.
+    let mut fired: Vec<bool> = vec![false; stops.len()];
+    loop {
+        let mut takers = vec![taker(_order)];
+        for (stop, _) in stops.iter().zip(fired.iter()).filter(|x| *x.1) {
+            takers.push(stop_taker(stop));
+        }
+        let owners: BTreeSet<&Address> = takers.iter().map(|x| x.owner).collect();
+        let (buy_levels, unread_buy) = walk(
+            &_ob.buy_orders,
+            &_ob.unread_buys,
+            &takers,
+            Side::Sell,
+            &owners,
+            _block,
+            _later,
+        );
+        let (sell_levels, unread_sell) = walk(
+            &_ob.sell_orders,
+            &_ob.unread_sells,
+            &takers,
+            Side::Buy,
+            &owners,
+            _block,
+            _later,
+        );
+
+        // Trades happen at resting prices, the takers' own limits included once they rest.
+        let mut prices: Vec<u64> = buy_levels
+            .iter()
+            .chain(sell_levels.iter())
+            .cloned()
+            .collect();
+        prices.extend(_ob.last_price);
+        prices.extend(takers.iter().filter_map(|x| x.limit));
+        let (low, high) = (prices.iter().min(), prices.iter().max());
+        let crossed = |x: &StopOrder| low.into_iter().chain(high).any(|v| x.is_triggered(*v));
+        let now: Vec<bool> = stops.iter().map(|x| crossed(*x)).collect();
+        if now != fired {
+            fired = now;
+            continue;
+        }
+
+        let mut unread: Vec<(Side, u64)> = unread_buy
+            .map(|x| (Side::Buy, x))
+            .into_iter()
+            .chain(unread_sell.map(|x| (Side::Sell, x)))
+            .collect();
+        let rests: BTreeSet<(Side, u64)> = takers
+            .iter()
+            .filter(|x| x.rests)
+            .filter_map(|x| x.limit.map(|v| (x.side, v)))
+            .collect();
+        let mut reach = Reach {
+            buy_levels: buy_levels,
+            sell_levels: sell_levels,
+            rests: rests.into_iter().collect(),
+            orders: Vec::new(),
+            unread: Vec::new(),
+        };
+        for (side, price) in reach.levels().into_iter() {
+            let (level, unread_level) = match side {
+                Side::Buy => (
+                    orders_at(&_ob.buy_orders, price),
+                    _ob.unread_buys.contains(&price),
+                ),
+                Side::Sell => (
+                    orders_at(&_ob.sell_orders, price),
+                    _ob.unread_sells.contains(&price),
+                ),
+            };
+            reach.orders.extend(level);
+            if unread_level && !unread.contains(&(side, price)) {
+                unread.push((side, price));
+            }
+        }
+        for (stop, _) in stops.iter().zip(fired.iter()).filter(|x| *x.1) {
+            reach
+                .orders
+                .push((stop.get_addr_ref().clone(), stop.get_id().clone()));
+        }
+        reach.unread = unread;
+        return reach;
+    }
+}
+
+fn orders_at<T: LevelOrder>(_levels: &PriceLevels<T>, _price: u64) -> Vec<(Address, String)> {
+    _levels
+        .level(_price)
+        .iter()
+        .map(|x| (x.get_addr(), x.get_id().clone()))
+        .collect()
+}
+
+// Prices of the levels of _levels the takers on _side may reach, best first: through the
+// level where the live orders of everyone else cover all they take, and no further than
+// the loosest of their limits. The walk stops at the first of the _unread levels it comes
+// to, which is returned as well. Orders that may have expired, by _block or if _later by
+// some block after it, cover nothing.
+fn walk<T: LevelOrder>(
+    _levels: &PriceLevels<T>,
+    _unread: &BTreeSet<u64>,
+    _takers: &[Taker],
+    _side: Side,
+    _owners: &BTreeSet<&Address>,
+    _block: u64,
+    _later: bool,
+) -> (Vec<u64>, Option<u64>) {
+    let live = |x: &T| match _later {
+        true => x.get_expires().is_none(),
+        false => !x.is_expired(_block),
+    };
+    let takers: Vec<&Taker> = _takers.iter().filter(|x| x.side == _side).collect();
+    let qty = takers
+        .iter()
+        .fold(0, |acc: u64, x| acc.saturating_add(x.qty));
+    let limits: Option<Vec<u64>> = takers.iter().map(|x| x.limit).collect();
+    let limit = match (_side, limits) {
+        (_, None) => None,
+        (Side::Buy, Some(v)) => v.into_iter().max(),
+        (Side::Sell, Some(v)) => v.into_iter().min(),
+    };
+    let mut prices: Vec<u64> = _levels.prices();
+    prices.extend(_unread.iter().cloned());
+    prices.sort();
+    if T::HIGHEST_FIRST {
+        prices.reverse();
+    }
+    let mut covered: u64 = 0;
+    let mut reached = Vec::new();
+    for price in prices {
+        let crosses = match (_side, limit) {
+            (_, None) => true,
+            (Side::Buy, Some(v)) => price <= v,
+            (Side::Sell, Some(v)) => price >= v,
+        };
+        if covered >= qty || !crosses {
+            break;
+        }
+        if _unread.contains(&price) {
+            return (reached, Some(price));
+        }
+        covered = _levels
+            .level(price)
+            .iter()
+            .filter(|x| live(*x) && !_owners.contains(x.get_addr_ref()))
+            .fold(covered, |acc, x| acc.saturating_add(x.get_qty()));
+        reached.push(price);
+    }
+    (reached, None)
+}
@@ -1,17 +1,75 @@
 use sawtooth_sdk::processor::handler::{ApplyError, TransactionContext};
+use failure::Error;
+use std::collections::BTreeSet;
 
-use auction::{Auction, AuctionList, Bid};
+use address::Address;
+use adminmsg::AdminMsg;
+use amend::Amend;
+use amount::Quantity;
+use auction::{Auction, AuctionList, AuctionResult, Bid};
 use balancebook::BalanceBook;
+use callauction;
+use cancel::Cancel;
+use errors::LibError;
+use events::MarketEvent;
+use fees::FeeSchedule;
 use getset::FamilyState;
-use order::{BuyOrder, OrderT, SellOrder};
-use orderbook::OrderBook;
+use instrument::{default_instrument, Instrument, InstrumentRegistry, MarketMode, DEFAULT_INSTRUMENT};
+use marketstatus::MarketStatus;
+use order::{BuyOrder, OrderT, SellOrder, Side};
+use orderbook::{Fill, OrderBook};
+use orderstatus::{self, OrderRecord, OrderStatusBook};
+use permissions::PermissionMsg;
+use pricelimits::Halt;
+use reach;
 use sealedbid::{SealedAuction, SealedAuctionList, SealedBid, UnsealedBid};
+use settlement::Snapshot;
+use stoporder::StopOrder;
+use tradeledger::{Trade, TradeLedger};
 use useracct::UserAccount;
+use validation;
 
 pub const CAPXTEN: u64 = 10_000_000_000;
 
-pub fn acct_route(_fstate: &mut FamilyState, _acct: UserAccount) -> Result<(), ApplyError> {
-    let mut bb = _fstate.get_balancebook()?;
+// Rejects the transaction outright (rather than failing it internally) when the
+// address in the payload doesn't belong to the signer.
+fn bind_signer(_payload_addr: &mut Address, _signer: &Address) -> Result<(), ApplyError> {
+    match validation::bind_to_signer(_payload_addr, _signer) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(ApplyError::InvalidTransaction(format!("{}", e))),
+    }
+}
+
+// Used by the handler to gate the variants flagged by Wrapper::requires_admin
+pub fn admin_check(_fstate: &mut FamilyState, _signer_pubkey: &String) -> Result<(), ApplyError> {
+    let permissions = _fstate.get_permissions()?;
+    match permissions.check_admin(_signer_pubkey, "submit privileged market transactions") {
+        Ok(_) => Ok(()),
+        Err(e) => Err(ApplyError::InvalidTransaction(format!("{}", e))),
+    }
+}
+
+pub fn pm_route(_fstate: &mut FamilyState, _msg: PermissionMsg) -> Result<(), ApplyError> {
+    let mut permissions = _fstate.get_permissions()?;
+    match permissions.apply(_msg) {
+        Ok(_) => (),
+        Err(e) => return Err(ApplyError::InvalidTransaction(format!("{}", e))),
+    }
+    _fstate.set_permissions(permissions)
+}
+
+pub fn acct_route(
+    _fstate: &mut FamilyState,
+    mut _acct: UserAccount,
+    _signer: &Address,
+    _signer_pubkey: &String,
+) -> Result<(), ApplyError> {
+    // Admins are allowed to register accounts on behalf of participants.
+    if !_fstate.get_permissions()?.is_admin(_signer_pubkey) {
+        bind_signer(&mut _acct.addr, _signer)?;
+    }
+    let mut bb = _fstate.get_balancebook_for(&[_acct.addr.clone()])?;
+    let expected = seeded(snapshot(&bb, &[])?, &_acct);
     match bb.insert_seeded(_acct) {
         Ok(v) => (),
         Err(e) => {
@@ -21,57 +79,641 @@ pub fn acct_route(_fstate: &mut FamilyState, _acct: UserAccount) -> Result<(), A
             )))
         }
     }
+    check_all_holds(&expected, &snapshot(&bb, &[])?)?;
     _fstate.set_balancebook(bb)
 }
 
-pub fn bo_route(_fstate: &mut FamilyState, _buyorder: BuyOrder) -> Result<(), ApplyError> {
-    let mut bb = _fstate.get_balancebook()?;
-    let mut ob = _fstate.get_orderbook()?;
-    match _buyorder.execute(&mut bb, &mut ob) {
-        Ok(v) => (),
+fn emit_events(_fstate: &mut FamilyState, _events: Vec<MarketEvent>) -> Result<(), ApplyError> {
+    for event in _events.into_iter() {
+        _fstate.add_event(event)?;
+    }
+    Ok(())
+}
+
+fn to_event(_event: Result<MarketEvent, Error>) -> Result<MarketEvent, ApplyError> {
+    match _event {
+        Ok(v) => Ok(v),
+        Err(e) => Err(ApplyError::InternalError(format!(
+            "Error building market event in routes: {:?}\n",
+            e
+        ))),
+    }
+}
+
+// One market/trade event per fill, plus market/order_rested if part of the order is left
+// on the book.
+fn order_events(
+    _status: &OrderStatusBook,
+    _id: &String,
+    _trades: &Vec<Trade>,
+) -> Result<Vec<MarketEvent>, ApplyError> {
+    let mut events = Vec::new();
+    for trade in _trades.iter() {
+        events.push(to_event(MarketEvent::trade(trade))?);
+    }
+    if let Some(record) = _status.get(_id) {
+        if record.is_live() {
+            events.push(to_event(MarketEvent::order_rested(record))?);
+        }
+    }
+    Ok(events)
+}
+
+// Accounts live at their own addresses, so a route loads the ones it may touch up front;
+// the fee treasury is among them whenever fills may be charged.
+fn get_accounts(
+    _fstate: &mut FamilyState,
+    _addrs: BTreeSet<Address>,
+    _fees: Option<&FeeSchedule>,
+) -> Result<BalanceBook, ApplyError> {
+    let mut addrs = _addrs;
+    addrs.extend(_fees.and_then(|x| x.treasury.clone()));
+    _fstate.get_balancebook_for(&addrs.into_iter().collect::<Vec<Address>>())
+}
+
+fn snapshot(_bb: &BalanceBook, _books: &[&OrderBook]) -> Result<Snapshot, ApplyError> {
+    match Snapshot::of(_bb, _books) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(ApplyError::InternalError(format!(
+            "{} in routes -> snapshot\n",
+            e
+        ))),
+    }
+}
+
+// Settling may only move value between accounts, and each account's holds must change by
+// just what its resting orders hold on _books and never fall short of it; a transaction
+// breaking any of these is rejected.
+fn check_settlement(
+    _before: &Snapshot,
+    _bb: &BalanceBook,
+    _books: &[&OrderBook],
+) -> Result<(), ApplyError> {
+    check_snapshots(_before, &snapshot(_bb, _books)?)
+}
+
+fn check_snapshots(_before: &Snapshot, _after: &Snapshot) -> Result<(), ApplyError> {
+    match _before.check(_after) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(ApplyError::InvalidTransaction(format!(
+            "{} in routes -> check_settlement\n",
+            e
+        ))),
+    }
+}
+
+// As check_snapshots, for routes whose snapshots count every book and every open auction's
+// bids, so that each hold must also come to just what they hold.
+fn check_all_holds(_before: &Snapshot, _after: &Snapshot) -> Result<(), ApplyError> {
+    check_snapshots(_before, _after)?;
+    match _after.check_complete() {
+        Ok(()) => Ok(()),
+        Err(e) => Err(ApplyError::InvalidTransaction(format!(
+            "{} in routes -> check_all_holds\n",
+            e
+        ))),
+    }
+}
+
+// What _before comes to once _acct is opened with its seed balances, the only cash and
+// assets it brings in. Nothing rests or bids for a new account, so it may hold none of them.
+fn seeded(_before: Snapshot, _acct: &UserAccount) -> Snapshot {
+    let cash = _acct.cash.get() as i128 + _acct.hold_cash.get() as i128;
+    _acct.holdings.iter().fold(
+        _before.issue(cash, DEFAULT_INSTRUMENT, 0),
+        |acc, (k, v)| acc.issue(0, k, v.assets.get() as i128 + v.hold_assets.get() as i128),
+    )
+}
+
+// The instrument's trading rules and price band, which every order is held to when it is
+// placed, and a stop-limit again when it triggers.
+fn check_limits<T: OrderT>(_order: &T, _ob: &OrderBook, _listing: &Instrument) -> Result<(), Error> {
+    _listing.rules.check_order(_order, &_ob.precision)?;
+    // Market orders have no price to hold to the band; the circuit breaker still halts the
+    // book if they move it too far.
+    match _listing.band {
+        Some(band) if _order.get_order_type().has_limit() => {
+            band.check(_order.get_price(), _ob.reference_price(band.reference))?
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+fn check_stop(_stop: &StopOrder, _ob: &OrderBook, _listing: &Instrument) -> Result<(), Error> {
+    match *_stop {
+        StopOrder::Buy(_, ref order) => check_limits(order, _ob, _listing),
+        StopOrder::Sell(_, ref order) => check_limits(order, _ob, _listing),
+    }
+}
+
+// Expired resting orders are purged before an incoming order is matched, so it never
+// trades against them; _block is the number of the block the order is in. Returns the
+// purged order IDs along with the fills; orders for a call-mode book only rest, so they
+// have none.
+fn match_order<T: OrderT>(
+    _order: T,
+    _bb: &mut BalanceBook,
+    _ob: &mut OrderBook,
+    _block: u64,
+    _listing: &Instrument,
+) -> Result<(Vec<String>, Vec<Fill>), Error> {
+    if let Some(v) = _ob.get_halt(_block) {
+        return Err(Error::from(LibError::HaltedError {
+            instrument: v.instrument.clone(),
+            resumes_at: v.resumes_at,
+        }));
+    }
+    check_limits(&_order, _ob, _listing)?;
+    if _order.is_expired(_block) {
+        return Err(Error::from(LibError::CustomError {
+            contents: format!(
+                "Order was good till block {:?} but this is block {}",
+                _order.get_expires(),
+                _block
+            ),
+        }));
+    }
+    let expired = _ob.purge_expired(_bb, _block)?;
+    let fills = match _listing.mode {
+        MarketMode::Continuous => _order.execute(_bb, _ob)?,
+        MarketMode::Call => callauction::submit(_order, _bb, _ob).map(|_| Vec::new())?,
+    };
+    Ok((expired, fills))
+}
+
+// Executes the stops set off by an order's fills and records them like any other order.
+// One that fails is rejected on its own; it never fails the order that set it off.
+fn fire_stops(
+    _ob: &mut OrderBook,
+    _bb: &mut BalanceBook,
+    _status: &mut OrderStatusBook,
+    _ledger: &mut TradeLedger,
+    _listing: &Instrument,
+    _block: u64,
+) -> Result<(Vec<MarketEvent>, Vec<Fill>), ApplyError> {
+    let mut executed = Vec::new();
+    let mut all_fills = Vec::new();
+    let fired = _ob.trigger_stops(_bb, &|stop, ob| check_stop(stop, ob, _listing));
+    for (stop, result) in fired.into_iter() {
+        match result {
+            Ok(fills) => {
+                _status.record_triggered(&stop, &fills);
+                let trades = _ledger.record_fills(&fills, _block);
+                executed.push((stop.get_id().clone(), trades));
+                all_fills.extend(fills);
+            }
+            Err(e) => {
+                info!(
+                    "Rejecting triggered stop order {} in routes: {:?}\n",
+                    stop.get_id(),
+                    e
+                );
+                _status.record_rejected_stop(&stop);
+            }
+        }
+    }
+    // Only once every triggered stop has a record can self-trade prevention be applied to
+    // them.
+    _status.record_self_trades(&_ob.take_self_trades());
+    let mut events = Vec::new();
+    for (id, trades) in executed.iter() {
+        events.extend(order_events(_status, id, trades)?);
+    }
+    Ok((events, all_fills))
+}
+
+// Adds the fills to the instrument's circuit breaker window and halts the instrument if
+// they have moved the price too far. Returns the halt if one started.
+fn check_breaker(
+    _ob: &mut OrderBook,
+    _listing: &Instrument,
+    _fills: &[Fill],
+    _block: u64,
+) -> Result<Option<Halt>, ApplyError> {
+    let breaker = match _listing.breaker {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    _ob.record_prices(_fills, _block, breaker.window);
+    if _fills.is_empty() || !breaker.is_tripped(&_ob.recent_prices) {
+        return Ok(None);
+    }
+    let resumes_at = match _block.checked_add(breaker.halt_blocks) {
+        Some(v) => v,
+        None => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Circuit breaker on {} would halt it past the last block in routes -> check_breaker\n",
+                _listing.symbol
+            )))
+        }
+    };
+    // The move that tripped the breaker shouldn't count against trading once it resumes.
+    _ob.recent_prices.clear();
+    let halt = Halt {
+        instrument: _listing.symbol.clone(),
+        price: _fills[_fills.len() - 1].price,
+        block: _block,
+        resumes_at: resumes_at,
+    };
+    _ob.halt = Some(halt.clone());
+    Ok(Some(halt))
+}
+
+// What follows an order's fills on a continuous book: the circuit breaker is checked and,
+// unless it trips, the stops the fills set off are fired, their own fills being checked in
+// turn. A halted instrument's stops keep waiting.
+fn after_fills(
+    _ob: &mut OrderBook,
+    _bb: &mut BalanceBook,
+    _status: &mut OrderStatusBook,
+    _ledger: &mut TradeLedger,
+    _listing: &Instrument,
+    _fills: &[Fill],
+    _block: u64,
+) -> Result<Vec<MarketEvent>, ApplyError> {
+    let mut events = Vec::new();
+    if _listing.mode != MarketMode::Continuous {
+        return Ok(events);
+    }
+    if let Some(halt) = check_breaker(_ob, _listing, _fills, _block)? {
+        events.push(to_event(MarketEvent::trading_halted(&halt))?);
+        return Ok(events);
+    }
+    let (stop_events, stop_fills) = fire_stops(_ob, _bb, _status, _ledger, _listing, _block)?;
+    events.extend(stop_events);
+    if let Some(halt) = check_breaker(_ob, _listing, &stop_fills, _block)? {
+        events.push(to_event(MarketEvent::trading_halted(&halt))?);
+    }
+    Ok(events)
+}
+
+// A rejected order still gets a status record so the submitter can see what happened
+// to it; the balance book and order book are left untouched.
+fn trade_route<T: OrderT + Clone>(
+    _fstate: &mut FamilyState,
+    _order: T,
+    _listing: &Instrument,
+    _signer: &Address,
+) -> Result<(), ApplyError> {
+    let mut ob = _fstate.get_book_header(_order.get_instrument())?;
+    let mut ledger = TradeLedger::starting_at(ob.trades);
+    ob.fees = _fstate.get_fees()?;
+    let block = _fstate.get_block_num()?;
+    // Only the levels the order may reach are read, the walk over the book stopping at
+    // each one it comes to that hasn't been; the expired orders purged are those on them.
+    let reached = loop {
+        let reached = reach::reach(&ob, &_order, block);
+        if reached.unread.is_empty() {
+            break reached;
+        }
+        for (side, price) in reached.unread.iter() {
+            _fstate.read_level(&mut ob, *side, *price)?;
+        }
+    };
+    let mut accounts = reached.owners();
+    accounts.insert(_signer.clone());
+    let mut orders = reached.orders;
+    orders.extend(ob.expired_orders(block));
+    orders.push((_signer.clone(), _order.get_id().clone()));
+    let mut status = _fstate.get_orderstatus(&orders)?;
+    let mut bb = get_accounts(_fstate, accounts, Some(&ob.fees))?;
+    let before = snapshot(&bb, &[&ob])?;
+    let events = match match_order(_order.clone(), &mut bb, &mut ob, block, _listing) {
+        Ok((expired, fills)) => {
+            status.mark_expired(&expired);
+            status.record_order(&_order, &fills);
+            status.record_self_trades(&ob.take_self_trades());
+            let trades = ledger.record_fills(&fills, block);
+            let mut events = order_events(&status, _order.get_id(), &trades)?;
+            events.extend(after_fills(
+                &mut ob,
+                &mut bb,
+                &mut status,
+                &mut ledger,
+                _listing,
+                &fills,
+                block,
+            )?);
+            check_settlement(&before, &bb, &[&ob])?;
+            events
+        }
         Err(e) => {
-            return Err(ApplyError::InternalError(format!(
-                "Error calling 'execute' on buy order in routes -> bo_route: {:?}\n",
+            info!(
+                "Rejecting {} order {} in routes -> trade_route: {:?}\n",
+                _order.side(),
+                _order.get_id(),
+                e
+            );
+            status.record_rejected(&_order);
+            return _fstate.set_orderstatus(status);
+        }
+    };
+
+    ob.trades = ledger.next_seq;
+    _fstate.set_balancebook(bb)?;
+    _fstate.set_orderbook(ob)?;
+    _fstate.set_orderstatus(status)?;
+    _fstate.set_tradeledger(ledger)?;
+    emit_events(_fstate, events)
+}
+
+pub fn bo_route(
+    _fstate: &mut FamilyState,
+    mut _buyorder: BuyOrder,
+    _signer: &Address,
+    _signature: &str,
+) -> Result<(), ApplyError> {
+    bind_signer(&mut _buyorder.addr, _signer)?;
+    _buyorder.set_id(orderstatus::order_id(_signature));
+    let listing: Instrument = match _fstate.get_instruments()?.check_listed(_buyorder.get_instrument()) {
+        Ok(v) => v.clone(),
+        Err(e) => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Buy order for an unlisted instrument in routes -> bo_route: {}\n",
+                e
+            )))
+        }
+    };
+    trade_route(_fstate, _buyorder, &listing, _signer)
+}
+
+pub fn so_route(
+    _fstate: &mut FamilyState,
+    mut _sellorder: SellOrder,
+    _signer: &Address,
+    _signature: &str,
+) -> Result<(), ApplyError> {
+    bind_signer(&mut _sellorder.addr, _signer)?;
+    _sellorder.set_id(orderstatus::order_id(_signature));
+    let listing: Instrument = match _fstate.get_instruments()?.check_listed(_sellorder.get_instrument()) {
+        Ok(v) => v.clone(),
+        Err(e) => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Sell order for an unlisted instrument in routes -> so_route: {}\n",
+                e
+            )))
+        }
+    };
+    trade_route(_fstate, _sellorder, &listing, _signer)
+}
+
+// Stop orders only wait on the book; they are matched by fire_stops once a later order's
+// fills reach the trigger.
+pub fn st_route(
+    _fstate: &mut FamilyState,
+    mut _stop: StopOrder,
+    _signer: &Address,
+    _signature: &str,
+) -> Result<(), ApplyError> {
+    bind_signer(_stop.get_addr_mut(), _signer)?;
+    _stop.set_id(orderstatus::order_id(_signature));
+    let instruments = _fstate.get_instruments()?;
+    let precision = instruments.precision(_stop.get_instrument());
+    match instruments.check_listed(_stop.get_instrument()) {
+        Ok(v) if v.mode == MarketMode::Call => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Stop orders can't be placed on {}, which trades in call auctions\n",
+                v.symbol
+            )))
+        }
+        Ok(v) => {
+            let checked = match _stop {
+                StopOrder::Buy(_, ref order) => v.rules.check_order(order, &precision),
+                StopOrder::Sell(_, ref order) => v.rules.check_order(order, &precision),
+            };
+            if let Err(e) = checked.and_then(|_| v.rules.check_price(_stop.get_trigger())) {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "Stop order breaks the trading rules for {} in routes -> st_route: {}\n",
+                    v.symbol, e
+                )));
+            }
+        }
+        Err(e) => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Stop order for an unlisted instrument in routes -> st_route: {}\n",
                 e
             )))
         }
     }
+    let mut ob = _fstate.get_orderbook(_stop.get_instrument())?;
+    // The stop is new, so it has no record to load.
+    let mut status = OrderStatusBook::new();
+    status.record_stop(&_stop);
+    if let Err(e) = ob.add_stop(_stop) {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Error adding stop order in routes -> st_route: {}\n",
+            e
+        )));
+    }
+
+    _fstate.set_orderbook(ob)?;
+    _fstate.set_orderstatus(status)
+}
+
+// The book an order's status record says it is on, with just the levels at _prices on the
+// order's side read, and the best level of the other side if _opposite. An order without a
+// record is looked for on the whole of the default instrument's book, as orders placed
+// before there were records are.
+fn book_for_record(
+    _fstate: &mut FamilyState,
+    _record: Option<&OrderRecord>,
+    _prices: &[u64],
+    _opposite: bool,
+) -> Result<OrderBook, ApplyError> {
+    let record = match _record {
+        Some(v) => v,
+        None => return _fstate.get_orderbook(&default_instrument()),
+    };
+    let mut ob = _fstate.get_book_header(&record.instrument)?;
+    for price in _prices.iter() {
+        _fstate.read_level(&mut ob, record.side, *price)?;
+    }
+    let best = match record.side {
+        Side::Buy => ob.unread_sells.iter().next().map(|x| (Side::Sell, *x)),
+        Side::Sell => ob.unread_buys.iter().next_back().map(|x| (Side::Buy, *x)),
+    };
+    if let (true, Some((side, price))) = (_opposite, best) {
+        _fstate.read_level(&mut ob, side, price)?;
+    }
+    Ok(ob)
+}
+
+// Cancellations always act on the signer's own orders. Cancelling one by ID reads just its
+// level; the others read the whole of each book they act on.
+pub fn cn_route(
+    _fstate: &mut FamilyState,
+    _cancel: Cancel,
+    _signer: &Address,
+) -> Result<(), ApplyError> {
+    let mut bb = _fstate.get_balancebook_for(&[_signer.clone()])?;
+    let mut books: Vec<OrderBook> = match _cancel {
+        Cancel::Order(ref _id) => {
+            let status = _fstate.get_orderstatus(&[(_signer.clone(), _id.clone())])?;
+            let record = status.get(_id);
+            let prices: Vec<u64> = record.iter().map(|x| x.price).collect();
+            vec![book_for_record(_fstate, record, &prices, false)?]
+        }
+        Cancel::Buy(ref _instrument, _) | Cancel::Sell(ref _instrument, _) => {
+            vec![_fstate.get_orderbook(_instrument)?]
+        }
+        Cancel::All => {
+            let mut books = Vec::new();
+            for instrument in _fstate.get_instruments()?.symbols() {
+                books.push(_fstate.get_orderbook(&instrument)?);
+            }
+            books
+        }
+    };
+    let mut cancelled: Vec<String> = Vec::new();
+    for ob in books.iter_mut() {
+        let before = snapshot(&bb, &[ob])?;
+        let cancel_result = match _cancel {
+            Cancel::Order(ref _id) => ob.cancel_by_id(&mut bb, _id, _signer).map(|x| vec![x]),
+            Cancel::Buy(_, _nonce) => ob.cancel_buy(&mut bb, _nonce, _signer).map(|x| vec![x]),
+            Cancel::Sell(_, _nonce) => ob.cancel_sell(&mut bb, _nonce, _signer).map(|x| vec![x]),
+            Cancel::All => ob.cancel_all_for(&mut bb, _signer),
+        };
+        match cancel_result {
+            Ok(ids) => cancelled.extend(ids),
+            Err(e) => {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "Error cancelling order in routes -> cn_route: {}\n",
+                    e
+                )))
+            }
+        }
+        check_settlement(&before, &bb, &[ob])?;
+    }
+    let orders: Vec<(Address, String)> = cancelled.iter().map(|x| (_signer.clone(), x.clone())).collect();
+    let mut status = _fstate.get_orderstatus(&orders)?;
+    status.mark_cancelled(&cancelled);
 
     _fstate.set_balancebook(bb)?;
-    _fstate.set_orderbook(ob)
+    for ob in books {
+        _fstate.set_orderbook(ob)?;
+    }
+    _fstate.set_orderstatus(status)
 }
 
-pub fn so_route(_fstate: &mut FamilyState, _sellorder: SellOrder) -> Result<(), ApplyError> {
-    let mut bb = _fstate.get_balancebook()?;
-    let mut ob = _fstate.get_orderbook()?;
-    match _sellorder.execute(&mut bb, &mut ob) {
-        Ok(v) => (),
+pub fn md_route(
+    _fstate: &mut FamilyState,
+    _amend: Amend,
+    _signer: &Address,
+) -> Result<(), ApplyError> {
+    let mut bb = _fstate.get_balancebook_for(&[_signer.clone()])?;
+    let mut status = _fstate.get_orderstatus(&[(_signer.clone(), _amend.id.clone())])?;
+    // The order's level and the one it moves to are read, and the best of the other side,
+    // which the new price mustn't cross.
+    let mut ob = match status.get(&_amend.id) {
+        Some(v) => {
+            let prices = vec![v.price, _amend.price.unwrap_or(v.price)];
+            book_for_record(_fstate, Some(v), &prices, true)?
+        }
+        None => book_for_record(_fstate, None, &[], true)?,
+    };
+    let instrument = ob.instrument.clone();
+    let current = ob
+        .buy_orders
+        .get(&_amend.id)
+        .map(|x| (x.get_price(), x.get_qty()))
+        .or_else(|| ob.sell_orders.get(&_amend.id).map(|x| (x.get_price(), x.get_qty())));
+    if let (Some(v), Some(listing)) = (current, _fstate.get_instruments()?.get(&instrument)) {
+        let band = match (listing.band, _amend.price) {
+            (Some(band), Some(price)) => band.check(price, ob.reference_price(band.reference)),
+            _ => Ok(()),
+        };
+        if let Err(e) = listing.rules.check_amend(&_amend, v, &ob.precision).and(band) {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Amendment breaks the trading rules for {} in routes -> md_route: {}\n",
+                instrument, e
+            )));
+        }
+    }
+    let before = snapshot(&bb, &[&ob])?;
+    match ob.amend_order(&mut bb, &_amend, _signer) {
+        Ok((price, qty)) => status.record_amended(&_amend.id, price, qty),
         Err(e) => {
-            return Err(ApplyError::InternalError(format!(
-                "Error calling 'execute' on sell order in routes -> so_route: {:?}\n",
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Error amending order in routes -> md_route: {}\n",
                 e
             )))
         }
     }
+    check_settlement(&before, &bb, &[&ob])?;
 
     _fstate.set_balancebook(bb)?;
-    _fstate.set_orderbook(ob)
+    _fstate.set_orderbook(ob)?;
+    _fstate.set_orderstatus(status)
 }
 
 pub fn co_route(_fstate: &mut FamilyState) -> Result<(), ApplyError> {
-    _fstate.set_orderbook(OrderBook::new())
+    clear_orderbooks(_fstate)
+}
+
+// Takes every resting order off every book, cancelled, and returns its hold to its owner.
+// Every book and open auction is loaded, so each owner's holds must come to just what
+// their auction bids hold once it is done.
+fn clear_orderbooks(_fstate: &mut FamilyState) -> Result<(), ApplyError> {
+    let mut books: Vec<OrderBook> = Vec::new();
+    for instrument in _fstate.get_instruments()?.symbols() {
+        books.push(_fstate.get_orderbook(&instrument)?);
+    }
+    let orders: Vec<(Address, String)> = books.iter().flat_map(|x| x.resting_orders()).collect();
+    let mut status = _fstate.get_orderstatus(&orders)?;
+    let owners: BTreeSet<Address> = books.iter().flat_map(|x| x.owners()).collect();
+    let owners: Vec<Address> = owners.into_iter().collect();
+    let mut bb: BalanceBook = _fstate.get_balancebook_for(&owners)?;
+    let bids = _fstate.get_auctionlist()?.held();
+    let before = snapshot(&bb, &books.iter().collect::<Vec<&OrderBook>>())?.with_bids(&bids);
+    for ob in books.iter_mut() {
+        match ob.refund_all(&mut bb) {
+            Ok(ids) => status.mark_cancelled(&ids),
+            Err(e) => {
+                return Err(ApplyError::InternalError(format!(
+                    "error refunding resting orders while clearing orderbook: {:?}\n",
+                    e
+                )))
+            }
+        }
+    }
+    let after = snapshot(&bb, &books.iter().collect::<Vec<&OrderBook>>())?.with_bids(&bids);
+    check_all_holds(&before, &after)?;
+    _fstate.set_balancebook(bb)?;
+    for ob in books {
+        _fstate.set_orderbook(ob)?;
+    }
+    _fstate.set_orderstatus(status)
 }
 
 pub fn cb_route(_fstate: &mut FamilyState) -> Result<(), ApplyError> {
-    _fstate.set_balancebook(BalanceBook::new())
+    _fstate.clear_balancebook()
 }
 
+// Open auctions' high bids are returned to their bidders before the list goes.
 pub fn ca_route(_fstate: &mut FamilyState) -> Result<(), ApplyError> {
-    _fstate.set_auctionlist(AuctionList::new())
+    let bids = _fstate.get_auctionlist()?.held();
+    let bidders: Vec<Address> = bids.iter().map(|x| x.0.clone()).collect();
+    let mut bb: BalanceBook = _fstate.get_balancebook_for(&bidders)?;
+    let before = snapshot(&bb, &[])?.with_bids(&bids);
+    for (bidder, amt) in bids.iter() {
+        if let Err(e) = bb
+            .debit_hold_cash(bidder, *amt)
+            .and_then(|_| bb.credit_cash(bidder, *amt))
+        {
+            return Err(ApplyError::InternalError(format!(
+                "error returning held bids while clearing the auction list: {:?}\n",
+                e
+            )));
+        }
+    }
+    check_snapshots(&before, &snapshot(&bb, &[])?)?;
+    _fstate.set_balancebook(bb)?;
+    _fstate.clear_auctionlist()
 }
 
 pub fn na_route(_fstate: &mut FamilyState, _auction: Auction) -> Result<(), ApplyError> {
-    let mut alist: AuctionList = _fstate.get_auctionlist()?;
+    let mut alist: AuctionList = _fstate.get_auction(_auction.get_serial())?;
     alist.add_auction(_auction);
     _fstate.set_auctionlist(alist)
 }
@@ -81,24 +723,40 @@ pub fn na_route(_fstate: &mut FamilyState, _auction: Auction) -> Result<(), Appl
 //}
 
 pub fn ea_route(_fstate: &mut FamilyState, _serial: u64) -> Result<(), ApplyError> {
-    let mut bb: BalanceBook = _fstate.get_balancebook()?;
-    let mut alist: AuctionList = _fstate.get_auctionlist()?;
-    match alist.end_auction(&mut bb, _serial) {
-        Ok(v) => (),
+    let mut alist: AuctionList = _fstate.get_auction(_serial)?;
+    let bidders: Vec<Address> = alist.contents.values().map(|x| x.get_high_bidder().clone()).collect();
+    let mut bb: BalanceBook = _fstate.get_balancebook_for(&bidders)?;
+    let before = snapshot(&bb, &[])?.with_bids(&alist.held());
+    let result: AuctionResult = match alist.end_auction(&mut bb, _serial) {
+        Ok(v) => v,
         Err(e) => return Err(ApplyError::InternalError(format!("Unable to find auction with serial number corersponding to bid's serial number in bid route: {:?}\n", e)))
-    }
+    };
+    // The winner's held bid pays for what is auctioned, which is issued to them.
+    let expected = match result.winner {
+        Some(_) => before.issue(-(result.price.get() as i128), &result.instrument, result.qty.get() as i128),
+        None => before,
+    };
+    check_snapshots(&expected, &snapshot(&bb, &[])?.with_bids(&alist.held()))?;
 
     _fstate.set_auctionlist(alist)?;
-    _fstate.set_balancebook(bb)
+    _fstate.set_balancebook(bb)?;
+    _fstate.add_event(to_event(MarketEvent::auction_closed(&result))?)
 }
 
-pub fn ab_route(_fstate: &mut FamilyState, _bid: Bid) -> Result<(), ApplyError> {
-    let mut bb: BalanceBook = _fstate.get_balancebook()?;
-    let mut alist: AuctionList = _fstate.get_auctionlist()?;
-    match alist.place_bid(&mut bb, _bid) {
+pub fn ab_route(_fstate: &mut FamilyState, mut _bid: Bid, _signer: &Address) -> Result<(), ApplyError> {
+    bind_signer(&mut _bid.addr, _signer)?;
+    let mut alist: AuctionList = _fstate.get_auction(_bid.auction_serial)?;
+    // The high bidder being outbid gets their held cash back.
+    let mut bidders: Vec<Address> = alist.contents.values().map(|x| x.get_high_bidder().clone()).collect();
+    bidders.push(_bid.addr.clone());
+    let mut bb: BalanceBook = _fstate.get_balancebook_for(&bidders)?;
+    let block = _fstate.get_block_num()?;
+    let before = snapshot(&bb, &[])?.with_bids(&alist.held());
+    match alist.place_bid(&mut bb, _bid, block) {
         Ok(v) => (),
         Err(e) => return Err(ApplyError::InternalError(format!("Unable to find auction with serial number corersponding to bid's serial number in bid route: {:?}\n", e)))
     }
+    check_snapshots(&before, &snapshot(&bb, &[])?.with_bids(&alist.held()))?;
 
     _fstate.set_auctionlist(alist)?;
     _fstate.set_balancebook(bb)
@@ -109,10 +767,13 @@ pub fn ns_route(
     _sealedauction: SealedAuction,
 ) -> Result<(), ApplyError> {
     let cbresult: u64 = _fstate.get_cballotresult()?;
-    let imposed: u64 = (CAPXTEN * cbresult) / 100;
+    let imposed: u64 = match Quantity::new(CAPXTEN).scale(cbresult, 100, "sealed auction cap") {
+        Ok(v) => v.get(),
+        Err(e) => return Err(ApplyError::InvalidTransaction(format!("Voted auction cap is out of range in routes -> ns_route: {}\n", e))),
+    };
     let imposed_cap: (u64, u64) = (cbresult, imposed);
     info!("imposed cap is: {:?}\n", imposed_cap);
-    let auction_amt = _sealedauction.auction_amt;
+    let auction_amt = _sealedauction.auction_amt.get();
     info!("auction amt: {:?}\n", auction_amt);
     if auction_amt > imposed_cap.1 {
         return Err(ApplyError::InternalError(format!(
@@ -120,15 +781,16 @@ pub fn ns_route(
         )));
     } else {
         info!("auction amoutn is okay. amount: {:?}\n Cap: {:?}\n", auction_amt, imposed_cap.1);
-        let mut slist: SealedAuctionList = _fstate.get_sealed_auctionlist()?;
+        let mut slist: SealedAuctionList = _fstate.get_sealed_auction(_sealedauction.serial)?;
         slist.add_sealed_auction(_sealedauction);
         _fstate.set_sealed_auctionlist(slist)
     }
 }
 
 pub fn sb_route(_fstate: &mut FamilyState, _sealedbid: SealedBid) -> Result<(), ApplyError> {
-    let mut slist: SealedAuctionList = _fstate.get_sealed_auctionlist()?;
-    match slist.submit_sealed_bid(_sealedbid) {
+    let mut slist: SealedAuctionList = _fstate.get_sealed_auction(_sealedbid.serial)?;
+    let block = _fstate.get_block_num()?;
+    match slist.submit_sealed_bid(_sealedbid, block) {
         Ok(v) => (),
         Err(e) => {
             return Err(ApplyError::InternalError(format!(
@@ -140,8 +802,15 @@ pub fn sb_route(_fstate: &mut FamilyState, _sealedbid: SealedBid) -> Result<(),
     _fstate.set_sealed_auctionlist(slist)
 }
 
-pub fn ub_route(_fstate: &mut FamilyState, _unsealedbid: UnsealedBid) -> Result<(), ApplyError> {
-    let mut slist: SealedAuctionList = _fstate.get_sealed_auctionlist()?;
+// The signer's address is part of the sealed digest, so binding it here also means
+// nobody can reveal a bid on someone else's behalf.
+pub fn ub_route(
+    _fstate: &mut FamilyState,
+    mut _unsealedbid: UnsealedBid,
+    _signer: &Address,
+) -> Result<(), ApplyError> {
+    bind_signer(&mut _unsealedbid.address, _signer)?;
+    let mut slist: SealedAuctionList = _fstate.get_sealed_auction(_unsealedbid.serial)?;
     match slist.submit_unsealed_bid(_unsealedbid) {
         Ok(v) => (),
         Err(e) => {
@@ -155,22 +824,303 @@ pub fn ub_route(_fstate: &mut FamilyState, _unsealedbid: UnsealedBid) -> Result<
 }
 
 pub fn es_route(_fstate: &mut FamilyState, _serial: u64) -> Result<(), ApplyError> {
-    let mut slist: SealedAuctionList = _fstate.get_sealed_auctionlist()?;
-    let mut bb: BalanceBook = _fstate.get_balancebook()?;
-    match slist.end_auction(&mut bb, _serial) {
-        Ok(v) => (),
+    let mut slist: SealedAuctionList = _fstate.get_sealed_auction(_serial)?;
+    let leaders: Vec<Address> = slist.contents.values().filter_map(|x| x.leader.0.clone()).collect();
+    let mut bb: BalanceBook = _fstate.get_balancebook_for(&leaders)?;
+    let before = snapshot(&bb, &[])?;
+    let result: AuctionResult = match slist.end_auction(&mut bb, _serial) {
+        Ok(v) => v,
         Err(e) => {
             return Err(ApplyError::InternalError(format!(
                 "error ending sealed bid auction: {:?}\n",
                 e
             )))
         }
-    }
+    };
+    // Sealed bids hold nothing; the winner pays the second price from their cash.
+    let expected = match result.winner {
+        Some(_) => before.issue(-(result.price.get() as i128), &result.instrument, result.qty.get() as i128),
+        None => before,
+    };
+    check_snapshots(&expected, &snapshot(&bb, &[])?)?;
 
     _fstate.set_balancebook(bb)?;
-    _fstate.set_sealed_auctionlist(slist)
+    _fstate.set_sealed_auctionlist(slist)?;
+    _fstate.add_event(to_event(MarketEvent::auction_closed(&result))?)
 }
 
 pub fn cs_route(_fstate: &mut FamilyState) -> Result<(), ApplyError> {
-    _fstate.set_sealed_auctionlist(SealedAuctionList::new())
+    _fstate.clear_sealed_auctionlist()
+}
+
+pub fn am_route(_fstate: &mut FamilyState, _msg: AdminMsg) -> Result<(), ApplyError> {
+    match _msg {
+        AdminMsg::AdminCredit(_change) => {
+            let mut bb: BalanceBook = _fstate.get_balancebook_for(&[_change.addr.clone()])?;
+            let expected = snapshot(&bb, &[])?.issue(_change.cash as i128, &_change.instrument, _change.assets as i128);
+            match _change.credit(&mut bb) {
+                Ok(v) => (),
+                Err(e) => {
+                    return Err(ApplyError::InvalidTransaction(format!(
+                        "error applying admin credit: {:?}\n",
+                        e
+                    )))
+                }
+            }
+            check_snapshots(&expected, &snapshot(&bb, &[])?)?;
+            _fstate.set_balancebook(bb)
+        }
+        AdminMsg::AdminDebit(_change) => {
+            let mut bb: BalanceBook = _fstate.get_balancebook_for(&[_change.addr.clone()])?;
+            let expected = snapshot(&bb, &[])?.issue(-(_change.cash as i128), &_change.instrument, -(_change.assets as i128));
+            match _change.debit(&mut bb) {
+                Ok(v) => (),
+                Err(e) => {
+                    return Err(ApplyError::InvalidTransaction(format!(
+                        "error applying admin debit: {:?}\n",
+                        e
+                    )))
+                }
+            }
+            check_snapshots(&expected, &snapshot(&bb, &[])?)?;
+            _fstate.set_balancebook(bb)
+        }
+        AdminMsg::NewUser(_acct) => {
+            let mut bb: BalanceBook = _fstate.get_balancebook_for(&[_acct.addr.clone()])?;
+            let expected = seeded(snapshot(&bb, &[])?, &_acct);
+            match bb.insert_seeded(_acct) {
+                Ok(v) => (),
+                Err(e) => {
+                    return Err(ApplyError::InternalError(format!(
+                        "error inserting new user from admin message: {:?}\n",
+                        e
+                    )))
+                }
+            }
+            check_all_holds(&expected, &snapshot(&bb, &[])?)?;
+            _fstate.set_balancebook(bb)
+        }
+        AdminMsg::EndAuction(_serial) => ea_route(_fstate, _serial),
+        AdminMsg::ClearOrderBook => clear_orderbooks(_fstate),
+        // Ends the trading period, setting each book's previous close, and takes orders
+        // that have expired by this block off every book.
+        AdminMsg::Step => {
+            let mut market: MarketStatus = _fstate.get_marketstatus()?;
+            market.step();
+            let block = _fstate.get_block_num()?;
+            let mut books: Vec<OrderBook> = Vec::new();
+            for instrument in _fstate.get_instruments()?.symbols() {
+                books.push(_fstate.get_orderbook(&instrument)?);
+            }
+            // Only the owners of expiring orders are refunded.
+            let orders: Vec<(Address, String)> = books.iter().flat_map(|x| x.expired_orders(block)).collect();
+            let mut status = _fstate.get_orderstatus(&orders)?;
+            let owners: BTreeSet<Address> = orders.into_iter().map(|x| x.0).collect();
+            let mut bb = get_accounts(_fstate, owners, None)?;
+            let bids = _fstate.get_auctionlist()?.held();
+            let before = snapshot(&bb, &books.iter().collect::<Vec<&OrderBook>>())?.with_bids(&bids);
+            let mut expired: Vec<String> = Vec::new();
+            for ob in books.iter_mut() {
+                ob.prev_close = ob.last_price;
+                match ob.purge_expired(&mut bb, block) {
+                    Ok(ids) => expired.extend(ids),
+                    Err(e) => {
+                        return Err(ApplyError::InternalError(format!(
+                            "error purging expired orders while stepping the market: {:?}\n",
+                            e
+                        )))
+                    }
+                }
+            }
+            let after = snapshot(&bb, &books.iter().collect::<Vec<&OrderBook>>())?.with_bids(&bids);
+            check_all_holds(&before, &after)?;
+            status.mark_expired(&expired);
+            _fstate.set_balancebook(bb)?;
+            for ob in books {
+                _fstate.set_orderbook(ob)?;
+            }
+            _fstate.set_orderstatus(status)?;
+            _fstate.set_marketstatus(market)
+        }
+        AdminMsg::ListInstrument(_instrument) => {
+            let mut instruments: InstrumentRegistry = _fstate.get_instruments()?;
+            match instruments.list(_instrument) {
+                Ok(v) => (),
+                Err(e) => {
+                    return Err(ApplyError::InvalidTransaction(format!(
+                        "error listing instrument: {}\n",
+                        e
+                    )))
+                }
+            }
+            _fstate.set_instruments(instruments)
+        }
+        // A call book may have built up crossing orders, which a continuous book can't hold;
+        // they have to be auctioned off before switching.
+        AdminMsg::SetMarketMode(_instrument, _mode) => {
+            let mut instruments: InstrumentRegistry = _fstate.get_instruments()?;
+            let ob: OrderBook = _fstate.get_orderbook(&_instrument)?;
+            if _mode == MarketMode::Continuous && callauction::clearing_price(&ob).is_some() {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "{} has crossing orders; run its call auction before switching it to continuous trading\n",
+                    _instrument
+                )));
+            }
+            // Nothing would ever fire them, as st_route won't take stops for a call book.
+            if _mode == MarketMode::Call && !ob.stop_orders.is_empty() {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "{} has stop orders waiting; they must be cancelled before it switches to call auctions\n",
+                    _instrument
+                )));
+            }
+            if let Err(e) = instruments.set_mode(&_instrument, _mode) {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "error setting market mode: {}\n",
+                    e
+                )));
+            }
+            _fstate.set_instruments(instruments)
+        }
+        AdminMsg::SetMatching(_instrument, _matching) => {
+            let mut instruments: InstrumentRegistry = _fstate.get_instruments()?;
+            if let Err(e) = instruments.set_matching(&_instrument, _matching) {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "error setting matching algorithm: {}\n",
+                    e
+                )));
+            }
+            _fstate.set_instruments(instruments)
+        }
+        AdminMsg::CallAuction(_instrument) => call_route(_fstate, _instrument),
+        AdminMsg::SetFees(_fees) => {
+            if let Err(e) = _fees.check() {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "error setting fee schedule: {}\n",
+                    e
+                )));
+            }
+            if let Some(ref treasury) = _fees.treasury {
+                if _fstate.get_balancebook_for(&[treasury.clone()])?.get_by_addr(treasury).is_none() {
+                    return Err(ApplyError::InvalidTransaction(format!(
+                        "error setting fee schedule: treasury {} has no account\n",
+                        treasury
+                    )));
+                }
+            }
+            _fstate.set_fees(_fees)
+        }
+        AdminMsg::SetPriceBand(_instrument, _band) => {
+            let mut instruments: InstrumentRegistry = _fstate.get_instruments()?;
+            if let Err(e) = instruments.set_band(&_instrument, _band) {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "error setting price band: {}\n",
+                    e
+                )));
+            }
+            _fstate.set_instruments(instruments)
+        }
+        AdminMsg::SetCircuitBreaker(_instrument, _breaker) => {
+            let mut instruments: InstrumentRegistry = _fstate.get_instruments()?;
+            if let Err(e) = instruments.set_breaker(&_instrument, _breaker) {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "error setting circuit breaker: {}\n",
+                    e
+                )));
+            }
+            _fstate.set_instruments(instruments)
+        }
+        AdminMsg::SetTradingRules(_instrument, _rules) => {
+            let mut instruments: InstrumentRegistry = _fstate.get_instruments()?;
+            if let Err(e) = instruments.set_rules(&_instrument, _rules) {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "error setting trading rules: {}\n",
+                    e
+                )));
+            }
+            _fstate.set_instruments(instruments)
+        }
+        // Balances, holds and prices are kept in the smallest unit of cash and aren't
+        // rescaled, so the places can't change once any cash is held or any order rests.
+        AdminMsg::SetCashPlaces(_places) => {
+            let addrs = _fstate.get_account_addrs()?;
+            let bb: BalanceBook = _fstate.get_balancebook_for(&addrs)?;
+            if let Some(acct) = bb.balance_book.values().find(|x| x.cash.get() > 0 || x.hold_cash.get() > 0) {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "Cash places can't change once cash is held; {} holds some\n",
+                    acct.addr.to_string_ref()
+                )));
+            }
+            let mut instruments: InstrumentRegistry = _fstate.get_instruments()?;
+            for instrument in instruments.symbols() {
+                if !_fstate.get_orderbook(&instrument)?.resting_orders().is_empty() {
+                    return Err(ApplyError::InvalidTransaction(format!(
+                        "Cash places can't change while orders rest on the {} book\n",
+                        instrument
+                    )));
+                }
+            }
+            if let Err(e) = instruments.set_cash_places(_places) {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "error setting cash places: {}\n",
+                    e
+                )));
+            }
+            _fstate.set_instruments(instruments)
+        }
+    }
+}
+
+// Expired orders are purged first so they take no part in the auction.
+pub fn call_route(_fstate: &mut FamilyState, _instrument: String) -> Result<(), ApplyError> {
+    match _fstate.get_instruments()?.check_listed(&_instrument) {
+        Ok(v) if v.mode == MarketMode::Call => (),
+        Ok(_) => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "{} trades continuously; it has no call auction to run\n",
+                _instrument
+            )))
+        }
+        Err(e) => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Call auction for an unlisted instrument in routes -> call_route: {}\n",
+                e
+            )))
+        }
+    }
+    let mut ob = _fstate.get_orderbook(&_instrument)?;
+    ob.fees = _fstate.get_fees()?;
+    let mut status = _fstate.get_orderstatus(&ob.resting_orders())?;
+    let mut ledger = TradeLedger::starting_at(ob.trades);
+    let block = _fstate.get_block_num()?;
+    // Any order on the book may take part in the auction.
+    let mut bb = get_accounts(_fstate, ob.owners(), Some(&ob.fees))?;
+    let before = snapshot(&bb, &[&ob])?;
+    let cleared = ob.purge_expired(&mut bb, block).and_then(|expired| {
+        callauction::clear(&mut ob, &mut bb).map(|fills| (expired, fills))
+    });
+    let fills = match cleared {
+        Ok((expired, fills)) => {
+            status.mark_expired(&expired);
+            fills
+        }
+        Err(e) => {
+            return Err(ApplyError::InternalError(format!(
+                "error running call auction in routes -> call_route: {:?}\n",
+                e
+            )))
+        }
+    };
+    check_settlement(&before, &bb, &[&ob])?;
+    status.apply_fills(&fills);
+    let mut events = Vec::new();
+    for trade in ledger.record_fills(&fills, block).iter() {
+        events.push(to_event(MarketEvent::trade(trade))?);
+    }
+
+    ob.trades = ledger.next_seq;
+    _fstate.set_balancebook(bb)?;
+    _fstate.set_orderbook(ob)?;
+    _fstate.set_orderstatus(status)?;
+    _fstate.set_tradeledger(ledger)?;
+    emit_events(_fstate, events)
 }
@@ -0,0 +1,175 @@
+use amend::Amend;
+use decimal::Precision;
+use errors::LibError;
+use order::OrderT;
+use std::fmt;
+
+// Per-instrument limits on the orders that can be placed, kept on the instrument's
+// registry entry. Prices must be a positive multiple of `tick_size` and quantities a
+// multiple of `lot_size` between `min_qty` and `max_qty`. The notional value of an order,
+// the cash it comes to at the instrument's precision, must fit in a u64 and be at most
+// `max_notional`. Orders are checked before any balance is touched.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TradingRules {
+    pub tick_size: u64,
+    pub lot_size: u64,
+    pub min_qty: u64,
+    pub max_qty: Option<u64>,
+    pub max_notional: Option<u64>,
+}
+
+impl Default for TradingRules {
+    fn default() -> Self {
+        TradingRules {
+            tick_size: 1,
+            lot_size: 1,
+            min_qty: 1,
+            max_qty: None,
+            max_notional: None,
+        }
+    }
+}
+
+impl fmt::Display for TradingRules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "tick {}, lot {}, qty {}..{}, notional up to {}",
+            self.tick_size,
+            self.lot_size,
+            self.min_qty,
+            self.max_qty
+                .map_or(String::from("unlimited"), |x| x.to_string()),
+            self.max_notional
+                .map_or(String::from("unlimited"), |x| x.to_string())
+        )
+    }
+}
+
+impl TradingRules {
+    // Checks that the rules themselves make sense before they are stored.
+    pub fn check(&self) -> Result<(), LibError> {
+        if self.tick_size == 0 {
+            return Err(LibError::EmptyZeroError {
+                field: String::from("Tick size"),
+            });
+        } else if self.lot_size == 0 {
+            return Err(LibError::EmptyZeroError {
+                field: String::from("Lot size"),
+            });
+        } else if self.min_qty == 0 {
+            return Err(LibError::EmptyZeroError {
+                field: String::from("Minimum quantity"),
+            });
+        }
+        match self.max_qty {
+            Some(v) if v < self.min_qty => Err(LibError::CustomError {
+                contents: format!(
+                    "Maximum quantity {} is below the minimum quantity {}",
+                    v, self.min_qty
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_price(&self, _price: u64) -> Result<(), LibError> {
+        if _price == 0 || _price % self.tick_size != 0 {
+            return Err(LibError::TickSizeError {
+                price: _price,
+                tick_size: self.tick_size,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn check_qty(&self, _qty: u64) -> Result<(), LibError> {
+        if _qty == 0 {
+            return Err(LibError::EmptyZeroError {
+                field: String::from("Order quantity"),
+            });
+        } else if _qty % self.lot_size != 0 {
+            return Err(LibError::LotSizeError {
+                qty: _qty,
+                lot_size: self.lot_size,
+            });
+        } else if _qty < self.min_qty {
+            return Err(LibError::MinQtyError {
+                qty: _qty,
+                min_qty: self.min_qty,
+            });
+        }
+        match self.max_qty {
+            Some(v) if _qty > v => Err(LibError::MaxQtyError {
+                qty: _qty,
+                max_qty: v,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_notional(
+        &self,
+        _price: u64,
+        _qty: u64,
+        _precision: &Precision,
+    ) -> Result<(), LibError> {
+        let notional = _precision.notional(_price, _qty)?;
+        match self.max_notional {
+            Some(v) if notional > v => Err(LibError::NotionalError {
+                notional: notional,
+                max_notional: v,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // A market order has no price, so only its quantity is checked.
+    pub fn check_order<T: OrderT>(
+        &self,
+        _order: &T,
+        _precision: &Precision,
+    ) -> Result<(), LibError> {
+        let priced = _order.get_order_type().has_limit();
+        if priced {
+            self.check_price(_order.get_price())?;
+        }
+        self.check_qty(_order.get_qty())?;
+        // An iceberg's peak is shown in whole lots.
+        match _order.get_peak() {
+            Some(v) if v % self.lot_size != 0 => {
+                return Err(LibError::LotSizeError {
+                    qty: v,
+                    lot_size: self.lot_size,
+                })
+            }
+            _ => (),
+        }
+        match priced {
+            true => self.check_notional(_order.get_price(), _order.get_qty(), _precision),
+            false => Ok(()),
+        }
+    }
+
+    // Only what an amendment changes is checked against the tick and lot rules, since a
+    // partly filled order may already be below the minimum quantity. `_current` is the
+    // order's (price, open quantity) on the book.
+    pub fn check_amend(
+        &self,
+        _amend: &Amend,
+        _current: (u64, u64),
+        _precision: &Precision,
+    ) -> Result<(), LibError> {
+        if let Some(price) = _amend.price {
+            self.check_price(price)?;
+        }
+        if let Some(qty) = _amend.qty {
+            self.check_qty(qty)?;
+        }
+        self.check_notional(
+            _amend.price.unwrap_or(_current.0),
+            _amend.qty.unwrap_or(_current.1),
+            _precision,
+        )
+    }
+}
@@ -1,10 +1,13 @@
 use address::Address;
 use addressing;
+use amount::{Amount, Quantity};
+use auction::AuctionResult;
 use balancebook::BalanceBook;
 use crypto::digest::Digest;
 use crypto::sha2::Sha512;
 use errors::LibError;
 use failure::Error;
+use instrument::default_instrument;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::fmt;
@@ -91,7 +94,7 @@ impl UnsealedBid {
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct SealedAuctionList {
     pub contents: BTreeMap<u64, SealedAuction>,
-    pub total_auctioned: u64,
+    pub total_auctioned: Quantity,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -99,12 +102,14 @@ pub struct SealedAuction {
     pub serial: u64,
     pub description: String,
     pub is_open: bool,
-    pub auction_amt: u64,
-    pub leader: (Option<Address>, u64),
-    pub second_price: Option<u64>,
+    pub auction_amt: Quantity,
+    pub leader: (Option<Address>, Amount),
+    pub second_price: Option<Amount>,
     pub bid_pool: BTreeMap<String, Option<UnsealedBid>>,
-    pub price_pool: BTreeMap<u64, ()>,
+    pub price_pool: BTreeMap<Amount, ()>,
     pub end_date: u64,
+    #[serde(default = "default_instrument")]
+    pub instrument: String,
 }
 
 impl fmt::Display for SealedAuction {
@@ -116,11 +121,16 @@ impl fmt::Display for SealedAuction {
         write!(f, "serial: {} \n", self.serial)?;
         write!(f, "description: {} \n", self.description)?;
         write!(f, "is open: {} \n", self.is_open)?;
-        write!(f, "auction qty: {} \n", self.auction_amt)?;
-        write!(f, "leading bid: {}, {} \n", leader0, self.leader.1)?;
-        write!(f, "second price: {:?}\n", self.second_price)?;
+        write!(f, "auction qty: {} \n", self.auction_amt.get())?;
+        write!(f, "leading bid: {}, {} \n", leader0, self.leader.1.get())?;
+        write!(
+            f,
+            "second price: {:?}\n",
+            self.second_price.map(Amount::get)
+        )?;
         write!(f, "bid pool: {:#?}\n", self.bid_pool)?;
-        write!(f, "end_date: {}\n", self.end_date)
+        write!(f, "end_date: {}\n", self.end_date)?;
+        write!(f, "instrument: {}\n", self.instrument)
     }
 }
 
@@ -136,7 +146,7 @@ impl SealedAuctionList {
     pub fn new() -> Self {
         SealedAuctionList {
             contents: BTreeMap::new(),
-            total_auctioned: 0,
+            total_auctioned: Quantity::default(),
         }
     }
 
@@ -145,16 +155,26 @@ impl SealedAuctionList {
     }
 
     pub fn get_total_auctioned(&self) -> u64 {
-        self.total_auctioned
+        self.total_auctioned.get()
     }
 
-    pub fn submit_sealed_bid(&mut self, _sealed: SealedBid) -> Result<(), Error> {
+    // Sealed bids are accepted through end_date, a block number; reveals
+    // are still accepted afterwards, until the auction is ended.
+    pub fn submit_sealed_bid(&mut self, _sealed: SealedBid, _block: u64) -> Result<(), Error> {
         match self.contents.get_mut(&_sealed.serial) {
             None => {
                 return Err(Error::from(LibError::CustomError {
                     contents: String::from("No auction found with that serial number."),
                 }))
             }
+            Some(ref v) if _block > v.end_date => {
+                return Err(Error::from(LibError::CustomError {
+                    contents: format!(
+                        "Bidding closed at block {}; this is block {}",
+                        v.end_date, _block
+                    ),
+                }))
+            }
             Some(v) => v.bid_pool.insert(_sealed.digest, None),
         };
 
@@ -178,7 +198,7 @@ impl SealedAuctionList {
         &mut self,
         _balance_book: &mut BalanceBook,
         _serial: u64,
-    ) -> Result<(), Error> {
+    ) -> Result<AuctionResult, Error> {
         let target_auction = match self.contents.get_mut(&_serial) {
             Some(v) => v,
             None => {
@@ -189,7 +209,14 @@ impl SealedAuctionList {
         };
 
         target_auction.end_auction(_balance_book)?;
-        Ok(())
+        Ok(AuctionResult {
+            serial: _serial,
+            sealed: true,
+            winner: target_auction.leader.0.clone(),
+            price: target_auction.second_price.unwrap_or_default(),
+            qty: target_auction.auction_amt,
+            instrument: target_auction.instrument.clone(),
+        })
     }
 }
 
@@ -201,17 +228,19 @@ impl SealedAuction {
         _start_open: bool,
         _amt: u64,
         _end_date: u64,
+        _instrument: String,
     ) -> Self {
         SealedAuction {
             serial: _serial,
             description: _description,
             is_open: _start_open,
-            auction_amt: _amt,
-            leader: (None, 0),
+            auction_amt: Quantity::new(_amt),
+            leader: (None, Amount::default()),
             second_price: None,
             bid_pool: BTreeMap::new(),
             price_pool: BTreeMap::new(),
             end_date: _end_date,
+            instrument: _instrument,
         }
     }
 
@@ -227,10 +256,11 @@ impl SealedAuction {
             }
             true => {
                 let current_leader = self.leader.clone();
-                if _unsealed.price > current_leader.1 {
-                    self.leader = (Some(_unsealed.clone().address), _unsealed.price)
+                let price = Amount::new(_unsealed.price);
+                if price > current_leader.1 {
+                    self.leader = (Some(_unsealed.clone().address), price)
                 };
-                self.price_pool.insert(_unsealed.price, ());
+                self.price_pool.insert(price, ());
                 self.bid_pool.insert(hash_of_unsealed, Some(_unsealed));
             }
         }
@@ -261,8 +291,8 @@ impl SealedAuction {
         self.second_price = Some(second_price.clone());
 
         let winning_addr = self.leader.0.clone().expect("Got 'none' in end auction -> define winning addr prior to balancebook changes. This unwrap should have been checked");
-        _balance_book.debit_cash(&winning_addr, *second_price)?;
-        _balance_book.credit_assets(&winning_addr, self.auction_amt)?;
+        _balance_book.debit_cash(&winning_addr, second_price.get())?;
+        _balance_book.credit_assets(&winning_addr, &self.instrument, self.auction_amt.get())?;
 
         Ok(())
     }
@@ -0,0 +1,273 @@
+use address::Address;
+use amount::Amount;
+use balancebook::BalanceBook;
+use errors::LibError;
+use failure::Error;
+use order::{OrderT, Side};
+use orderbook::OrderBook;
+use std::cmp;
+use std::collections::BTreeMap;
+
+// Where each side of a trade pays from. An incoming order was never held, so its owner
+// pays from their liquid balances; a resting order pays from the hold taken when it was
+// rested.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuyFunding {
+    Liquid,
+    // The price the buy rests at, the quantity still open on it and the fee rate its hold
+    // covers. Its hold was taken at that price, so when it trades below it, or pays less
+    // than the most it could in fees, the difference goes back to the buyer's cash.
+    Held(u64, u64, u64),
+}
+
+// A sell's hold is of the assets it sells, whatever it trades at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SellFunding {
+    Liquid,
+    Held,
+}
+
+// What a trade came to: the cash the buyer paid the seller, and each side's fee on it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settled {
+    pub cash: Amount,
+    pub buyer_fee: Amount,
+    pub seller_fee: Amount,
+}
+
+// Moves _qty of _ob's instrument from seller to buyer and its notional at _price the other
+// way, each side paying as its funding says, and charges both sides' fees in cash by _ob's
+// fee schedule. A resting buy pays its fee out of its hold, which covers it (see
+// FeeSchedule::hold_bps), and anything left over of the hold is freed.
+pub fn settle(
+    _bb: &mut BalanceBook,
+    _ob: &OrderBook,
+    _buyer: (&Address, BuyFunding),
+    _seller: (&Address, SellFunding),
+    _aggressor: Side,
+    _price: u64,
+    _qty: u64,
+) -> Result<Settled, Error> {
+    let (buyer, buyer_funding) = _buyer;
+    let (seller, seller_funding) = _seller;
+    let cash = Amount::new(_ob.precision.notional(_price, _qty)?);
+    let (buyer_fee, seller_fee) = _ob.fees.fees_on(buyer, seller, _aggressor, cash.get())?;
+    let (mut buyer_fee, seller_fee) = (Amount::new(buyer_fee), Amount::new(seller_fee));
+    match buyer_funding {
+        BuyFunding::Liquid => _bb.debit_cash(buyer, cash.add(buyer_fee, "buyer cost")?.get())?,
+        BuyFunding::Held(limit, open, fee_bps) => {
+            if limit < _price {
+                return Err(Error::from(LibError::CustomError {
+                    contents: format!("A buy held at {} can't settle a trade at {}", limit, _price),
+                }));
+            }
+            let released = _ob.precision.released(limit, open, _qty, fee_bps)?;
+            let left = Amount::new(released).sub(cash, "buy hold refund")?;
+            buyer_fee = cmp::min(buyer_fee, left);
+            _bb.debit_hold_cash(buyer, released)?;
+            _bb.credit_cash(buyer, left.sub(buyer_fee, "buy hold refund")?.get())?;
+        }
+    }
+    match seller_funding {
+        SellFunding::Liquid => _bb.debit_assets(seller, &_ob.instrument, _qty)?,
+        SellFunding::Held => _bb.debit_hold_assets(seller, &_ob.instrument, _qty)?,
+    }
+    _bb.credit_assets(buyer, &_ob.instrument, _qty)?;
+    _bb.credit_cash(seller, cash.sub(seller_fee, "seller fee")?.get())?;
+    if let Some(ref treasury) = _ob.fees.treasury {
+        _bb.credit_cash(treasury, buyer_fee.add(seller_fee, "trading fees")?.get())?;
+    }
+    Ok(Settled {
+        cash: cash,
+        buyer_fee: buyer_fee,
+        seller_fee: seller_fee,
+    })
+}
+
+// What the settlement invariants are checked on: the balances of the accounts in a balance
+// book and the holds of their resting orders on some books and their bids in some
+// auctions. The book may hold only some of the market's accounts; value only ever moves
+// between the accounts a transaction loads, or comes in or goes out as issued. Totals are
+// kept in i128, which no sum of u64 balances can overflow.
+//
+// Holds are checked by how they change: each change to a hold must be the same change to
+// what the loaded orders and bids hold, so a transaction can't leave a hold that nothing
+// accounts for, or take away one that something still needs. They are checked by what they
+// come to as well. A hold balance can never be less than what the loaded orders and bids
+// hold, as anything not loaded only adds to it; and a transaction that loads every book and
+// every auction bid (see check_complete) requires each to come to exactly that.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    // cash + hold_cash over every account
+    cash: i128,
+    // assets + hold_assets over every account, by instrument
+    assets: BTreeMap<String, i128>,
+    // Each account's hold_cash and what its resting buys and auction bids hold
+    held_cash: BTreeMap<Address, (u128, u128)>,
+    // Each account's hold_assets and what its resting sells hold, by instrument
+    held_assets: BTreeMap<(Address, String), (u128, u128)>,
+}
+
+impl Snapshot {
+    pub fn of(_bb: &BalanceBook, _books: &[&OrderBook]) -> Result<Snapshot, Error> {
+        let mut snapshot = Snapshot {
+            cash: 0,
+            assets: BTreeMap::new(),
+            held_cash: BTreeMap::new(),
+            held_assets: BTreeMap::new(),
+        };
+        for acct in _bb.balance_book.values() {
+            snapshot.cash += acct.cash.get() as i128 + acct.hold_cash.get() as i128;
+            snapshot
+                .held_cash
+                .insert(acct.addr.clone(), (acct.hold_cash.get() as u128, 0));
+            for (instrument, holding) in acct.holdings.iter() {
+                *snapshot.assets.entry(instrument.clone()).or_insert(0) +=
+                    holding.assets.get() as i128 + holding.hold_assets.get() as i128;
+            }
+        }
+        for ob in _books.iter() {
+            for order in ob.buy_orders.iter() {
+                if let Some(v) = snapshot.held_cash.get_mut(order.get_addr_ref()) {
+                    v.1 += ob
+                        .precision
+                        .held(order.get_price(), order.get_qty(), order.fee_bps)?
+                        as u128;
+                }
+            }
+            for acct in _bb.balance_book.values() {
+                let key = (acct.addr.clone(), ob.instrument.clone());
+                snapshot
+                    .held_assets
+                    .insert(key, (acct.hold_assets(&ob.instrument) as u128, 0));
+            }
+            for order in ob.sell_orders.iter() {
+                let key = (order.get_addr(), ob.instrument.clone());
+                if let Some(v) = snapshot.held_assets.get_mut(&key) {
+                    v.1 += order.get_qty() as u128;
+                }
+            }
+        }
+        Ok(snapshot)
+    }
+
+    // Counts (bidder, amount) of each auction bid holding cash as held by it.
+    pub fn with_bids(mut self, _bids: &[(Address, u64)]) -> Snapshot {
+        for (addr, amt) in _bids.iter() {
+            if let Some(v) = self.held_cash.get_mut(addr) {
+                v.1 += *amt as u128;
+            }
+        }
+        self
+    }
+
+    // What _self should come to once _cash and _assets of _instrument are brought into the
+    // accounts from outside them, or taken out when negative, as admin credits and debits
+    // and auction sales do.
+    pub fn issue(mut self, _cash: i128, _instrument: &str, _assets: i128) -> Snapshot {
+        self.cash += _cash;
+        *self.assets.entry(String::from(_instrument)).or_insert(0) += _assets;
+        self
+    }
+
+    // Checks that settling took _self to _after without creating or destroying any cash or
+    // assets beyond what was issued, and that every change to an account's holds was a
+    // change to what its counted orders and bids hold. Holds for anything not counted, like
+    // other books' orders, are left as they were. Only accounts in both snapshots are
+    // compared.
+    pub fn check(&self, _after: &Snapshot) -> Result<(), Error> {
+        let violation = |contents: String| {
+            Err(Error::from(LibError::CustomError {
+                contents: format!("Settlement invariant violated: {}", contents),
+            }))
+        };
+        if self.cash != _after.cash {
+            return violation(format!(
+                "total cash went from {} to {}",
+                self.cash, _after.cash
+            ));
+        }
+        for instrument in self.assets.keys().chain(_after.assets.keys()) {
+            let before = self.assets.get(instrument).cloned().unwrap_or(0);
+            let after = _after.assets.get(instrument).cloned().unwrap_or(0);
+            if before != after {
+                return violation(format!(
+                    "total {} went from {} to {}",
+                    instrument, before, after
+                ));
+            }
+        }
+        for (addr, before) in self.held_cash.iter() {
+            if let Some(after) = _after.held_cash.get(addr) {
+                if !holds_follow_orders(*before, *after) {
+                    return violation(format!(
+                        "{}'s held cash went from {} to {} while its orders' holds went from {} to {}",
+                        addr.to_string(),
+                        before.0,
+                        after.0,
+                        before.1,
+                        after.1
+                    ));
+                }
+            }
+        }
+        for (key, before) in self.held_assets.iter() {
+            if let Some(after) = _after.held_assets.get(key) {
+                if !holds_follow_orders(*before, *after) {
+                    return violation(format!(
+                        "{}'s held {} went from {} to {} while its orders' holds went from {} to {}",
+                        key.0.to_string(),
+                        key.1,
+                        before.0,
+                        after.0,
+                        before.1,
+                        after.1
+                    ));
+                }
+            }
+        }
+        _after.check_covered(|x, y| x >= y)
+    }
+
+    // Checks that every hold comes to just what the counted orders and bids hold, for a
+    // snapshot that counts all of them: every book, and the bids of every open auction.
+    pub fn check_complete(&self) -> Result<(), Error> {
+        self.check_covered(|x, y| x == y)
+    }
+
+    // Checks each (hold balance, held by orders) pair with _ok.
+    fn check_covered(&self, _ok: fn(u128, u128) -> bool) -> Result<(), Error> {
+        for (addr, held) in self.held_cash.iter() {
+            if !_ok(held.0, held.1) {
+                return Err(Error::from(LibError::CustomError {
+                    contents: format!(
+                        "Settlement invariant violated: {}'s held cash is {} while its orders and bids hold {}",
+                        addr.to_string(),
+                        held.0,
+                        held.1
+                    ),
+                }));
+            }
+        }
+        for (key, held) in self.held_assets.iter() {
+            if !_ok(held.0, held.1) {
+                return Err(Error::from(LibError::CustomError {
+                    contents: format!(
+                        "Settlement invariant violated: {}'s held {} is {} while its orders hold {}",
+                        key.0.to_string(),
+                        key.1,
+                        held.0,
+                        held.1
+                    ),
+                }));
+            }
+        }
+        Ok(())
+    }
+}
+
+// (hold balance, held by orders) before and after; the two must have changed by the same
+// amount.
+fn holds_follow_orders(_before: (u128, u128), _after: (u128, u128)) -> bool {
+    _after.0 + _before.1 == _before.0 + _after.1
+}
@@ -0,0 +1,107 @@
+use address::Address;
+use balancebook::BalanceBook;
+use failure::Error;
+use order::{BuyOrder, OrderT, OrderType, SellOrder};
+use orderbook::{check_buy, check_sell, Fill, OrderBook};
+use std::fmt;
+
+// A conditional order kept off the book until the last trade price reaches its trigger:
+// at or above it for a buy, at or below it for a sell. The wrapped order's type decides
+// what it becomes once triggered; a Market order makes this a stop, a Limit order a
+// stop-limit.
+//
+// No hold is taken while a stop waits. Funds are checked when it triggers, and a
+// triggered order that can't be paid for is rejected.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum StopOrder {
+    Buy(u64 /* trigger */, BuyOrder),
+    Sell(u64 /* trigger */, SellOrder),
+}
+
+impl fmt::Display for StopOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopOrder::Buy(trigger, order) => write!(f, "Stop at {}\n{}", trigger, order),
+            StopOrder::Sell(trigger, order) => write!(f, "Stop at {}\n{}", trigger, order),
+        }
+    }
+}
+
+impl StopOrder {
+    pub fn get_trigger(&self) -> u64 {
+        match self {
+            StopOrder::Buy(trigger, _) | StopOrder::Sell(trigger, _) => *trigger,
+        }
+    }
+
+    pub fn get_id(&self) -> &String {
+        match self {
+            StopOrder::Buy(_, order) => order.get_id(),
+            StopOrder::Sell(_, order) => order.get_id(),
+        }
+    }
+
+    pub fn set_id(&mut self, _id: String) {
+        match self {
+            StopOrder::Buy(_, order) => order.set_id(_id),
+            StopOrder::Sell(_, order) => order.set_id(_id),
+        }
+    }
+
+    pub fn get_addr_ref(&self) -> &Address {
+        match self {
+            StopOrder::Buy(_, order) => order.get_addr_ref(),
+            StopOrder::Sell(_, order) => order.get_addr_ref(),
+        }
+    }
+
+    pub fn get_addr_mut(&mut self) -> &mut Address {
+        match self {
+            StopOrder::Buy(_, order) => &mut order.addr,
+            StopOrder::Sell(_, order) => &mut order.addr,
+        }
+    }
+
+    pub fn get_instrument(&self) -> &String {
+        match self {
+            StopOrder::Buy(_, order) => order.get_instrument(),
+            StopOrder::Sell(_, order) => order.get_instrument(),
+        }
+    }
+
+    pub fn get_order_type(&self) -> OrderType {
+        match self {
+            StopOrder::Buy(_, order) => order.get_order_type(),
+            StopOrder::Sell(_, order) => order.get_order_type(),
+        }
+    }
+
+    pub fn is_expired(&self, _block: u64) -> bool {
+        match self {
+            StopOrder::Buy(_, order) => order.is_expired(_block),
+            StopOrder::Sell(_, order) => order.is_expired(_block),
+        }
+    }
+
+    pub fn is_triggered(&self, _last_price: u64) -> bool {
+        match self {
+            StopOrder::Buy(trigger, _) => _last_price >= *trigger,
+            StopOrder::Sell(trigger, _) => _last_price <= *trigger,
+        }
+    }
+
+    // What executing the triggered order checks before it touches anything.
+    pub fn check(&self, _bb: &BalanceBook, _ob: &OrderBook) -> Result<(), Error> {
+        match *self {
+            StopOrder::Buy(_, ref order) => check_buy(_ob, _bb, order).map(|_| ()),
+            StopOrder::Sell(_, ref order) => check_sell(_ob, _bb, order),
+        }
+    }
+
+    pub fn execute(self, _bb: &mut BalanceBook, _ob: &mut OrderBook) -> Result<Vec<Fill>, Error> {
+        match self {
+            StopOrder::Buy(_, order) => order.execute(_bb, _ob),
+            StopOrder::Sell(_, order) => order.execute(_bb, _ob),
+        }
+    }
+}
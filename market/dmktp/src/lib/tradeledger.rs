@@ -0,0 +1,167 @@
+use address::Address;
+use instrument::default_instrument;
+use order::Side;
+use orderbook::Fill;
+use std::fmt;
+
+pub const CSV_HEADER: &'static str =
+    "seq,block,instrument,buy_id,sell_id,buyer,seller,price,qty,aggressor,buyer_fee,seller_fee";
+
+// A fill as it is kept on the trade tape. `block` is the number of the block the fill
+// executed in, and `seq` its position on its instrument's tape. Each trade is stored at its
+// own address (see addressing::trade_addr).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub seq: u64,
+    // Stored under its old name; trades recorded before it was a block number have the
+    // market step instead.
+    #[serde(rename = "step")]
+    pub block: u64,
+    #[serde(default = "default_instrument")]
+    pub instrument: String,
+    pub buy_id: String,
+    pub sell_id: String,
+    pub buyer: Address,
+    pub seller: Address,
+    pub price: u64,
+    pub qty: u64,
+    pub aggressor: Side,
+    // Both fees are in cash.
+    #[serde(default)]
+    pub buyer_fee: u64,
+    #[serde(default)]
+    pub seller_fee: u64,
+}
+
+// A run of the trade tape, oldest first. The processor records a transaction's trades on
+// one that starts where the book's tape left off; the ledger stored at TRADELEDGER holds
+// the trades of a market that predates each trade having its own address.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TradeLedger {
+    pub trades: Vec<Trade>,
+    // Sequence number of the next trade recorded.
+    #[serde(default)]
+    pub next_seq: u64,
+}
+
+impl fmt::Display for Trade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Trade {} (block {})\n    {} {} @ {}, aggressor: {}\n    buyer: {}    buy order: {}    fee: {}\n    seller: {}    sell order: {}    fee: {}\n",
+            self.seq,
+            self.block,
+            self.qty,
+            self.instrument,
+            self.price,
+            self.aggressor,
+            self.buyer,
+            self.buy_id,
+            self.buyer_fee,
+            self.seller,
+            self.sell_id,
+            self.seller_fee
+        )
+    }
+}
+
+impl fmt::Display for TradeLedger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.trades.iter().try_for_each(|x| write!(f, "\n{}", x))
+    }
+}
+
+impl Trade {
+    pub fn from_fill(_fill: &Fill, _seq: u64, _block: u64) -> Self {
+        Trade {
+            seq: _seq,
+            block: _block,
+            instrument: _fill.instrument.clone(),
+            buy_id: _fill.buy_id.clone(),
+            sell_id: _fill.sell_id.clone(),
+            buyer: _fill.buyer.clone(),
+            seller: _fill.seller.clone(),
+            price: _fill.price,
+            qty: _fill.qty,
+            aggressor: _fill.aggressor,
+            buyer_fee: _fill.buyer_fee,
+            seller_fee: _fill.seller_fee,
+        }
+    }
+
+    pub fn to_csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.seq,
+            self.block,
+            self.instrument,
+            self.buy_id,
+            self.sell_id,
+            self.buyer.to_string(),
+            self.seller.to_string(),
+            self.price,
+            self.qty,
+            self.aggressor,
+            self.buyer_fee,
+            self.seller_fee
+        )
+    }
+}
+
+impl TradeLedger {
+    pub fn new() -> Self {
+        TradeLedger::starting_at(0)
+    }
+
+    pub fn starting_at(_seq: u64) -> Self {
+        TradeLedger {
+            trades: Vec::new(),
+            next_seq: _seq,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.trades.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trades.is_empty()
+    }
+
+    // Returns the trades just appended to the tape.
+    pub fn record_fills(&mut self, _fills: &Vec<Fill>, _block: u64) -> Vec<Trade> {
+        let first = self.trades.len();
+        for fill in _fills.iter() {
+            self.trades
+                .push(Trade::from_fill(fill, self.next_seq, _block));
+            self.next_seq += 1;
+        }
+        self.trades[first..].to_vec()
+    }
+
+    pub fn for_account(&self, _addr: &Address) -> Vec<&Trade> {
+        self.trades
+            .iter()
+            .filter(|x| &x.buyer == _addr || &x.seller == _addr)
+            .collect()
+    }
+
+    pub fn since_block(&self, _block: u64) -> Vec<&Trade> {
+        self.trades.iter().filter(|x| x.block >= _block).collect()
+    }
+
+    // Total fees paid into the treasury, in cash.
+    pub fn fees_collected(&self) -> u64 {
+        self.trades.iter().map(|x| x.buyer_fee + x.seller_fee).sum()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(CSV_HEADER);
+        csv.push('\n');
+        for trade in self.trades.iter() {
+            csv.push_str(&trade.to_csv_row());
+            csv.push('\n');
+        }
+        csv
+    }
+}
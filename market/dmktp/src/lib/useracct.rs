@@ -1,31 +1,109 @@
 use failure::Error;
+use serde::de::{self, Deserialize, Deserializer};
+use std::collections::BTreeMap;
 use std::fmt;
 
 use address::Address;
+use amount::{Amount, Quantity};
+use decimal::Decimal;
 use errors::LibError::{self, CustomError, EmptyZeroError, EncodingError};
+use instrument::{default_instrument, InstrumentRegistry, DEFAULT_INSTRUMENT};
 use wrapper::Wrapper;
 //use creditdebit::{ Credit, Debit, BalanceChange };
 //use balancebook::{ BalanceBook };
 //use serwrapper::SW;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Hash, Eq)]
+#[derive(Serialize, Debug, PartialEq, Clone, Hash, Eq)]
 pub struct UserAccount {
     pub addr: Address,
     pub name: String,
-    pub cash: u64,
-    pub assets: u64,
-    pub hold_cash: u64,
-    pub hold_assets: u64,
+    pub cash: Amount,
+    pub hold_cash: Amount,
+    // Asset balances keyed by instrument symbol
+    pub holdings: BTreeMap<String, Holding>,
+}
+
+// An account as stored, either as above or as it was before instruments, with a single
+// `assets`/`hold_assets` pair. Those were balances of the one instrument there was, so
+// they are read into its holding.
+#[derive(Deserialize)]
+struct StoredAccount {
+    addr: Address,
+    name: String,
+    cash: Amount,
+    hold_cash: Amount,
+    #[serde(default)]
+    holdings: BTreeMap<String, Holding>,
+    #[serde(default)]
+    assets: Quantity,
+    #[serde(default)]
+    hold_assets: Quantity,
+}
+
+impl<'de> Deserialize<'de> for UserAccount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stored = StoredAccount::deserialize(deserializer)?;
+        let mut holdings = stored.holdings;
+        if stored.assets.get() > 0 || stored.hold_assets.get() > 0 {
+            let holding = holdings
+                .entry(String::from(DEFAULT_INSTRUMENT))
+                .or_insert_with(Holding::default);
+            holding.assets = holding
+                .assets
+                .add(stored.assets, "stored account assets")
+                .map_err(de::Error::custom)?;
+            holding.hold_assets = holding
+                .hold_assets
+                .add(stored.hold_assets, "stored account held assets")
+                .map_err(de::Error::custom)?;
+        }
+        Ok(UserAccount {
+            addr: stored.addr,
+            name: stored.name,
+            cash: stored.cash,
+            hold_cash: stored.hold_cash,
+            holdings: holdings,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Hash, Eq, Default)]
+pub struct Holding {
+    pub assets: Quantity,
+    pub hold_assets: Quantity,
 }
 
 impl fmt::Display for UserAccount {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}: {}    cash (liquid): {}\n    assets (liquid): {}\n    cash (held): {}\n    assets(held): {}", 
-        self.name, self.addr, self.cash, self.assets, self.hold_cash, self.hold_assets)
+        write!(f, "{}", self.show(&InstrumentRegistry::new()))
     }
 }
 
 impl UserAccount {
+    // The account with its balances as decimals, at the places _instruments gives cash and
+    // each instrument.
+    pub fn show(&self, _instruments: &InstrumentRegistry) -> String {
+        let cash = |x: u64| Decimal::new(x, _instruments.cash_places);
+        let mut shown = format!(
+            "{}: {}    cash (liquid): {}\n    cash (held): {}",
+            self.name,
+            self.addr,
+            cash(self.cash.get()),
+            cash(self.hold_cash.get())
+        );
+        for (k, v) in self.holdings.iter() {
+            let precision = _instruments.precision(k);
+            shown += &format!(
+                "\n    {} (liquid): {}\n    {} (held): {}",
+                k,
+                precision.qty(v.assets.get()),
+                k,
+                precision.qty(v.hold_assets.get())
+            );
+        }
+        shown
+    }
+
     pub fn from_vals(_name: String, _addr: String) -> Result<Self, LibError> {
         let address: Address = Address::from_string(_addr)?;
 
@@ -56,15 +134,15 @@ impl UserAccount {
             Ok(UserAccount {
                 addr: address,
                 name: _name,
-                cash: 0,
-                assets: 0,
-                hold_cash: 0,
-                hold_assets: 0,
+                cash: Amount::default(),
+                hold_cash: Amount::default(),
+                holdings: BTreeMap::new(),
             })
             //
         }
     }
 
+    // Seed assets are credited in the default instrument.
     pub fn new_seeded(
         _name: String,
         _addr: String,
@@ -97,18 +175,35 @@ impl UserAccount {
         //
         } else {
             //
+            let mut holdings = BTreeMap::new();
+            holdings.insert(
+                default_instrument(),
+                Holding {
+                    assets: Quantity::new(_seed_assets),
+                    hold_assets: Quantity::default(),
+                },
+            );
             Ok(UserAccount {
                 addr: address,
                 name: _name,
-                cash: _seed_cash,
-                assets: _seed_assets,
-                hold_cash: 0,
-                hold_assets: 0,
+                cash: Amount::new(_seed_cash),
+                hold_cash: Amount::default(),
+                holdings: holdings,
             })
             //
         }
     }
 
+    pub fn assets(&self, _instrument: &str) -> u64 {
+        self.holdings.get(_instrument).map_or(0, |x| x.assets.get())
+    }
+
+    pub fn hold_assets(&self, _instrument: &str) -> u64 {
+        self.holdings
+            .get(_instrument)
+            .map_or(0, |x| x.hold_assets.get())
+    }
+
     pub fn as_wrapper(self) -> Wrapper {
         Wrapper::Ua(self)
     }
@@ -129,3 +224,45 @@ impl UserAccount {
         self.addr.contents.clone()
     }
 }
+
+#[cfg(test)]
+mod useracct_tests {
+    use super::*;
+    use serde_cbor;
+
+    // UserAccount as it was stored before instruments.
+    #[derive(Serialize)]
+    struct BaselineAccount {
+        addr: Address,
+        name: String,
+        cash: u64,
+        assets: u64,
+        hold_cash: u64,
+        hold_assets: u64,
+    }
+
+    #[test]
+    fn test_decode_baseline_account() {
+        let addr = Address::from_string(String::from("6d2ca0")).unwrap();
+        let stored = BaselineAccount {
+            addr: addr.clone(),
+            name: String::from("alice"),
+            cash: 10_000,
+            assets: 1_000,
+            hold_cash: 50,
+            hold_assets: 5,
+        };
+        let acct: UserAccount =
+            serde_cbor::from_slice(&serde_cbor::to_vec(&stored).unwrap()).unwrap();
+        assert_eq!(acct.addr, addr);
+        assert_eq!((acct.cash.get(), acct.hold_cash.get()), (10_000, 50));
+        assert_eq!(acct.assets(DEFAULT_INSTRUMENT), 1_000);
+        assert_eq!(acct.hold_assets(DEFAULT_INSTRUMENT), 5);
+        assert_eq!(acct.holdings.len(), 1);
+
+        // and an account stored since reads back as it was written
+        let again: UserAccount =
+            serde_cbor::from_slice(&serde_cbor::to_vec(&acct).unwrap()).unwrap();
+        assert_eq!(again, acct);
+    }
+}
@@ -1,5 +1,5 @@
 use address::Address;
-use errors::LibError;
+use errors::LibError::{self, SignerMismatchError};
 use failure::Error;
 
 /*
@@ -11,3 +11,42 @@ pub fn sender_eq_pubkey(_msg_pubkey: String, _check: String) -> Result<(), Error
     }
 } 
 */
+
+// Binds the acting address of an order/bid/account to whoever signed the transaction.
+// An empty payload address is filled in with the signer's address; any other address
+// has to match the signer exactly.
+pub fn bind_to_signer(_payload_addr: &mut Address, _signer: &Address) -> Result<(), LibError> {
+    if _payload_addr.is_empty() {
+        *_payload_addr = _signer.clone();
+        Ok(())
+    } else if _payload_addr == _signer {
+        Ok(())
+    } else {
+        Err(SignerMismatchError {
+            payload: _payload_addr.to_string(),
+            signer: _signer.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod validation_tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_to_signer() {
+        let signer = Address::from_string(String::from("1111111111111111111111111111111111111111")).unwrap();
+        let other = Address::from_string(String::from("2222222222222222222222222222222222222222")).unwrap();
+
+        let mut empty = Address::empty();
+        assert!(bind_to_signer(&mut empty, &signer).is_ok());
+        assert_eq!(empty, signer);
+
+        let mut same = signer.clone();
+        assert!(bind_to_signer(&mut same, &signer).is_ok());
+
+        let mut forged = other.clone();
+        assert!(bind_to_signer(&mut forged, &signer).is_err());
+        assert_eq!(forged, other);
+    }
+}
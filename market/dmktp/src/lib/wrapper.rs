@@ -1,20 +1,32 @@
 use failure::Error;
 use sawtooth_sdk::messages::transaction::Transaction;
 use serde_cbor;
+use std::collections::BTreeSet;
 use std::sync::{Mutex, MutexGuard};
 
+use address::Address;
 use addressing::{
-    self, AUCTIONLIST, BALANCEBOOK, CBALLOTBOXRESULT, FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION,
-    ORDERBOOK, SEALEDAUCTIONLIST,
+    self, ACCOUNTINDEX, ACCOUNT_PREFIX, AUCTIONLIST, AUCTION_PREFIX, BALANCEBOOK, BLOCKINFO_CONFIG,
+    CBALLOTBOXRESULT, FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION, FEESCHEDULE, INSTRUMENTS,
+    MARKETSTATUS, ORDERBOOK_PREFIX, ORDERSTATUS, ORDERSTATUS_PREFIX, PERMISSIONS,
+    SEALEDAUCTIONLIST, SEALED_AUCTION_PREFIX,
 };
 use adminmsg::AdminMsg;
+use amend::Amend;
 use auction::{Auction, Bid};
 use balancebook::BalanceBook;
+use cancel::Cancel;
 use errors::LibError;
+use fees::FeeSchedule;
 use getset::FamilyState;
-use order::{BuyOrder, OrderT, SellOrder};
+use instrument::DEFAULT_INSTRUMENT;
+use order::{BuyOrder, OrderT, SellOrder, Side};
 use orderbook::OrderBook;
+use orderstatus::OrderRecord;
+use permissions::PermissionMsg;
+use reach;
 use sealedbid::{SealedAuction, SealedBid, UnsealedBid};
+use stoporder::StopOrder;
 use swth_cli_libv2::clireq::{CliRequest, ClientRequest, FamilyMeta};
 use swth_cli_libv2::errors::CliError;
 use useracct::UserAccount;
@@ -24,6 +36,9 @@ pub enum Wrapper {
     Ua(UserAccount),
     Bo(BuyOrder),
     So(SellOrder),
+    St(StopOrder),
+    Cn(Cancel),
+    Md(Amend),
     Am(AdminMsg),
     Na(Auction),
     Ns(SealedAuction),
@@ -36,12 +51,184 @@ pub enum Wrapper {
     Cb,
     Ca,
     Cs,
+    Pm(PermissionMsg),
     //    AuctionBid(Bid),
     //    BuyOrderVec(Vec<BuyOrder>),
     //    SellOrderVec(Vec<SellOrder>),
     //    UserAccountVec(Vec<UserAccount>),
 }
 
+impl Wrapper {
+    // Declares just _touches, rather than all a transaction of its kind might touch.
+    pub fn to_cli_request_touching(&self, _touches: &Touches) -> Result<CliRequest, Error> {
+        let inputs: BTreeSet<String> = _touches.inputs.union(&_touches.outputs).cloned().collect();
+        Ok(CliRequest {
+            cbor_payload: serde_cbor::to_vec(&self)?,
+            family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
+            input_addrs: inputs.into_iter().collect(),
+            output_addrs: _touches.outputs.iter().cloned().collect(),
+        })
+    }
+
+    // Variants which may only be submitted by a key in the on-chain permission list.
+    pub fn requires_admin(&self) -> bool {
+        match self {
+            Wrapper::Co
+            | Wrapper::Cb
+            | Wrapper::Ca
+            | Wrapper::Cs
+            | Wrapper::Na(_)
+            | Wrapper::Ns(_)
+            | Wrapper::Ea(_)
+            | Wrapper::Es(_)
+            | Wrapper::Pm(_)
+            | Wrapper::Am(_) => true,
+            _ => false,
+        }
+    }
+}
+
+// An account's address, or every account's when the payload leaves the address for the
+// processor to fill in from the signer.
+fn account_addr(_addr: &Address) -> String {
+    match _addr.is_empty() {
+        true => String::from(ACCOUNT_PREFIX),
+        false => addressing::account_addr(_addr),
+    }
+}
+
+// What a transaction on the books touches, as the client works it out from the state it
+// last read. Declared with Wrapper::to_cli_request_touching, it keeps an order, cancel or
+// amendment off other books and the accounts and records of those it doesn't trade with,
+// so the validator can apply such transactions in parallel. Should the state have moved on
+// by the time one is processed, so that it touches something else, it is rejected and can
+// be resubmitted.
+//
+// The fee treasury's account is written by every order that may pay a fee, so while fees
+// are charged, orders on different books still go one at a time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Touches {
+    // Written, and so read as well.
+    pub outputs: BTreeSet<String>,
+    // Only read.
+    pub inputs: BTreeSet<String>,
+}
+
+impl Touches {
+    // The signer's account and status records, whose new orders' IDs come from the
+    // transaction's signature and so are declared by the signer's prefix. Accounts and
+    // records may still be in the legacy books.
+    fn new(_signer: &Address) -> Self {
+        let mut touches = Touches {
+            outputs: BTreeSet::new(),
+            inputs: [INSTRUMENTS, BALANCEBOOK, ORDERSTATUS]
+                .iter()
+                .map(|x| String::from(*x))
+                .collect(),
+        };
+        touches.outputs.insert(account_addr(_signer));
+        touches
+            .outputs
+            .insert(addressing::order_status_prefix(_signer));
+        touches
+    }
+
+    fn add_levels(&mut self, _instrument: &str, _levels: &[(Side, u64)]) {
+        self.outputs.insert(addressing::orderbook_addr(_instrument));
+        for (side, price) in _levels.iter() {
+            self.outputs
+                .insert(addressing::level_addr(_instrument, *side, *price));
+        }
+    }
+
+    fn add_book(&mut self, _instrument: &str) {
+        self.outputs.extend(with_book(_instrument, &[]));
+    }
+
+    // _order matched against _ob, as the client last read it, at _block or a block after:
+    // the levels it may reach and rest on, their orders' owners and records, and those of
+    // the waiting stops, any of which may expire or fire first.
+    pub fn order<T: OrderT>(_ob: &OrderBook, _order: &T, _block: u64, _fees: &FeeSchedule) -> Self {
+        let reach = reach::reach_from(_ob, _order, _block);
+        let mut touches = Touches::new(_order.get_addr_ref());
+        touches.inputs.insert(String::from(FEESCHEDULE));
+        touches.inputs.insert(String::from(BLOCKINFO_CONFIG));
+        let levels: Vec<(Side, u64)> = reach.levels().into_iter().collect();
+        touches.add_levels(&_ob.instrument, &levels);
+        touches
+            .outputs
+            .insert(addressing::trades_prefix(&_ob.instrument));
+        for owner in reach.owners().iter().chain(_fees.treasury.iter()) {
+            touches.outputs.insert(addressing::account_addr(owner));
+        }
+        let stops = _ob
+            .stop_orders
+            .iter()
+            .map(|x| (x.get_addr_ref().clone(), x.get_id().clone()));
+        for (owner, id) in reach.orders.iter().cloned().chain(stops) {
+            touches
+                .outputs
+                .insert(addressing::order_status_addr(&owner, &id));
+        }
+        touches
+    }
+
+    // A cancel of the signer's orders. Cancelling one by ID touches just the level its
+    // _record has it on; without a record it may be anywhere on the default book.
+    pub fn cancel(_cancel: &Cancel, _record: Option<&OrderRecord>, _signer: &Address) -> Self {
+        let mut touches = Touches::new(_signer);
+        match (_cancel, _record) {
+            (Cancel::Order(_), Some(v)) => touches.add_levels(&v.instrument, &[(v.side, v.price)]),
+            (Cancel::Order(_), None) => touches.add_book(DEFAULT_INSTRUMENT),
+            (Cancel::Buy(_instrument, _), _) | (Cancel::Sell(_instrument, _), _) => {
+                touches.add_book(_instrument)
+            }
+            (Cancel::All, _) => {
+                touches.outputs.insert(String::from(ORDERBOOK_PREFIX));
+            }
+        }
+        touches
+    }
+
+    // An amendment of _record's order on _ob: the level it is on, the one it moves to and
+    // the best of the other side, which it mustn't cross.
+    pub fn amend(
+        _ob: &OrderBook,
+        _record: Option<&OrderRecord>,
+        _amend: &Amend,
+        _signer: &Address,
+    ) -> Self {
+        let mut touches = Touches::new(_signer);
+        let record = match _record {
+            Some(v) => v,
+            None => {
+                touches.add_book(DEFAULT_INSTRUMENT);
+                return touches;
+            }
+        };
+        let mut levels = vec![
+            (record.side, record.price),
+            (record.side, _amend.price.unwrap_or(record.price)),
+        ];
+        let best = match record.side {
+            Side::Buy => _ob.sell_orders.best_price().map(|x| (Side::Sell, x)),
+            Side::Sell => _ob.buy_orders.best_price().map(|x| (Side::Buy, x)),
+        };
+        levels.extend(best);
+        touches.add_levels(&record.instrument, &levels);
+        touches
+    }
+}
+
+// _addrs along with a book's own address, its price levels' and its trades'.
+fn with_book(_instrument: &str, _addrs: &[&str]) -> Vec<String> {
+    let mut addrs: Vec<String> = _addrs.iter().map(|x| String::from(*x)).collect();
+    addrs.push(addressing::orderbook_addr(_instrument));
+    addrs.push(addressing::levels_prefix(_instrument));
+    addrs.push(addressing::trades_prefix(_instrument));
+    addrs
+}
+
 impl ClientRequest for Wrapper {
     fn to_cli_request(&self) -> Result<CliRequest, Error> {
         let serialized = serde_cbor::to_vec(&self)?;
@@ -49,91 +236,352 @@ impl ClientRequest for Wrapper {
             Wrapper::Ua(_acct) => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![String::from(ORDERBOOK), String::from(BALANCEBOOK)],
-                output_addrs: vec![String::from(ORDERBOOK), String::from(BALANCEBOOK)],
+                input_addrs: vec![
+                    String::from(PERMISSIONS),
+                    account_addr(&_acct.addr),
+                    String::from(BALANCEBOOK),
+                    String::from(ACCOUNTINDEX),
+                ],
+                output_addrs: vec![account_addr(&_acct.addr), String::from(ACCOUNTINDEX)],
             }),
+            // Without the state the client last read to work out what an order touches (see
+            // Touches), it declares every account and status record.
             Wrapper::Bo(_buyorder) => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![String::from(BALANCEBOOK), String::from(ORDERBOOK)],
-                output_addrs: vec![String::from(BALANCEBOOK), String::from(ORDERBOOK)],
+                input_addrs: with_book(
+                    _buyorder.get_instrument(),
+                    &[
+                        ACCOUNT_PREFIX,
+                        BALANCEBOOK,
+                        ORDERSTATUS,
+                        ORDERSTATUS_PREFIX,
+                        INSTRUMENTS,
+                        FEESCHEDULE,
+                        BLOCKINFO_CONFIG,
+                    ],
+                ),
+                output_addrs: with_book(
+                    _buyorder.get_instrument(),
+                    &[ACCOUNT_PREFIX, ORDERSTATUS_PREFIX],
+                ),
             }),
             Wrapper::So(_sellorder) => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![String::from(BALANCEBOOK), String::from(ORDERBOOK)],
-                output_addrs: vec![String::from(BALANCEBOOK), String::from(ORDERBOOK)],
+                input_addrs: with_book(
+                    _sellorder.get_instrument(),
+                    &[
+                        ACCOUNT_PREFIX,
+                        BALANCEBOOK,
+                        ORDERSTATUS,
+                        ORDERSTATUS_PREFIX,
+                        INSTRUMENTS,
+                        FEESCHEDULE,
+                        BLOCKINFO_CONFIG,
+                    ],
+                ),
+                output_addrs: with_book(
+                    _sellorder.get_instrument(),
+                    &[ACCOUNT_PREFIX, ORDERSTATUS_PREFIX],
+                ),
+            }),
+            Wrapper::St(_stop) => Ok(CliRequest {
+                cbor_payload: serialized,
+                family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
+                input_addrs: with_book(_stop.get_instrument(), &[INSTRUMENTS]),
+                output_addrs: {
+                    let mut outputs = with_book(_stop.get_instrument(), &[]);
+                    outputs.push(addressing::order_status_prefix(_stop.get_addr_ref()));
+                    outputs
+                },
+            }),
+            // The book(s) a cancel touches are only known once the order status book or
+            // instrument registry has been read, so it declares every book.
+            Wrapper::Cn(_cancel) => Ok(CliRequest {
+                cbor_payload: serialized,
+                family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
+                input_addrs: vec![
+                    String::from(ACCOUNT_PREFIX),
+                    String::from(BALANCEBOOK),
+                    String::from(ORDERBOOK_PREFIX),
+                    String::from(ORDERSTATUS),
+                    String::from(ORDERSTATUS_PREFIX),
+                    String::from(INSTRUMENTS),
+                ],
+                output_addrs: vec![
+                    String::from(ACCOUNT_PREFIX),
+                    String::from(ORDERBOOK_PREFIX),
+                    String::from(ORDERSTATUS_PREFIX),
+                ],
+            }),
+            Wrapper::Md(_amend) => Ok(CliRequest {
+                cbor_payload: serialized,
+                family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
+                input_addrs: vec![
+                    String::from(ACCOUNT_PREFIX),
+                    String::from(BALANCEBOOK),
+                    String::from(ORDERBOOK_PREFIX),
+                    String::from(ORDERSTATUS),
+                    String::from(ORDERSTATUS_PREFIX),
+                    String::from(INSTRUMENTS),
+                ],
+                output_addrs: vec![
+                    String::from(ACCOUNT_PREFIX),
+                    String::from(ORDERBOOK_PREFIX),
+                    String::from(ORDERSTATUS_PREFIX),
+                ],
             }),
             Wrapper::Co => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![String::from(ORDERBOOK)],
-                output_addrs: vec![String::from(ORDERBOOK)],
+                input_addrs: vec![
+                    String::from(PERMISSIONS),
+                    String::from(ORDERBOOK_PREFIX),
+                    String::from(ORDERSTATUS),
+                    String::from(ORDERSTATUS_PREFIX),
+                    String::from(INSTRUMENTS),
+                    String::from(ACCOUNT_PREFIX),
+                    String::from(BALANCEBOOK),
+                    String::from(AUCTIONLIST),
+                    String::from(AUCTION_PREFIX),
+                ],
+                output_addrs: vec![
+                    String::from(ORDERBOOK_PREFIX),
+                    String::from(ORDERSTATUS_PREFIX),
+                    String::from(ACCOUNT_PREFIX),
+                ],
             }),
             Wrapper::Cb => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![String::from(BALANCEBOOK)],
-                output_addrs: vec![String::from(BALANCEBOOK)],
+                input_addrs: vec![
+                    String::from(PERMISSIONS),
+                    String::from(ACCOUNT_PREFIX),
+                    String::from(ACCOUNTINDEX),
+                    String::from(BALANCEBOOK),
+                ],
+                output_addrs: vec![
+                    String::from(ACCOUNT_PREFIX),
+                    String::from(ACCOUNTINDEX),
+                    String::from(BALANCEBOOK),
+                ],
+            }),
+            Wrapper::Ca => Ok(CliRequest {
+                cbor_payload: serialized,
+                family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
+                input_addrs: vec![
+                    String::from(PERMISSIONS),
+                    String::from(AUCTIONLIST),
+                    String::from(AUCTION_PREFIX),
+                    String::from(ACCOUNT_PREFIX),
+                    String::from(BALANCEBOOK),
+                ],
+                output_addrs: vec![
+                    String::from(AUCTIONLIST),
+                    String::from(AUCTION_PREFIX),
+                    String::from(ACCOUNT_PREFIX),
+                ],
             }),
             Wrapper::Na(_auction) => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![String::from(AUCTIONLIST), String::from(BALANCEBOOK)],
-                output_addrs: vec![String::from(AUCTIONLIST), String::from(BALANCEBOOK)],
+                input_addrs: vec![
+                    String::from(PERMISSIONS),
+                    String::from(AUCTIONLIST),
+                    addressing::auction_addr(_auction.get_serial()),
+                ],
+                output_addrs: vec![
+                    String::from(AUCTIONLIST),
+                    addressing::auction_addr(_auction.get_serial()),
+                ],
             }),
             Wrapper::Ab(_bid) => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![String::from(AUCTIONLIST), String::from(BALANCEBOOK)],
-                output_addrs: vec![String::from(AUCTIONLIST), String::from(BALANCEBOOK)],
+                input_addrs: vec![
+                    String::from(AUCTIONLIST),
+                    addressing::auction_addr(_bid.auction_serial),
+                    String::from(ACCOUNT_PREFIX),
+                    String::from(BALANCEBOOK),
+                    String::from(BLOCKINFO_CONFIG),
+                ],
+                output_addrs: vec![
+                    addressing::auction_addr(_bid.auction_serial),
+                    String::from(ACCOUNT_PREFIX),
+                ],
             }),
             Wrapper::Ea(_serial) => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![String::from(AUCTIONLIST), String::from(BALANCEBOOK)],
-                output_addrs: vec![String::from(AUCTIONLIST), String::from(BALANCEBOOK)],
+                input_addrs: vec![
+                    String::from(PERMISSIONS),
+                    String::from(AUCTIONLIST),
+                    addressing::auction_addr(*_serial),
+                    String::from(ACCOUNT_PREFIX),
+                    String::from(BALANCEBOOK),
+                ],
+                output_addrs: vec![
+                    String::from(AUCTIONLIST),
+                    addressing::auction_addr(*_serial),
+                    String::from(ACCOUNT_PREFIX),
+                ],
             }),
             Wrapper::Ns(_sealedauction) => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
                 input_addrs: vec![
+                    String::from(PERMISSIONS),
                     String::from(SEALEDAUCTIONLIST),
+                    addressing::sealed_auction_addr(_sealedauction.serial),
                     String::from(CBALLOTBOXRESULT),
                 ],
-                output_addrs: vec![String::from(SEALEDAUCTIONLIST)],
+                output_addrs: vec![
+                    String::from(SEALEDAUCTIONLIST),
+                    addressing::sealed_auction_addr(_sealedauction.serial),
+                ],
             }),
             Wrapper::Sb(_sealedbid) => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![String::from(SEALEDAUCTIONLIST)],
-                output_addrs: vec![String::from(SEALEDAUCTIONLIST)],
+                input_addrs: vec![
+                    String::from(SEALEDAUCTIONLIST),
+                    addressing::sealed_auction_addr(_sealedbid.serial),
+                    String::from(BLOCKINFO_CONFIG),
+                ],
+                output_addrs: vec![addressing::sealed_auction_addr(_sealedbid.serial)],
             }),
             Wrapper::Ub(_unsealedbid) => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![String::from(SEALEDAUCTIONLIST)],
-                output_addrs: vec![String::from(SEALEDAUCTIONLIST)],
+                input_addrs: vec![
+                    String::from(SEALEDAUCTIONLIST),
+                    addressing::sealed_auction_addr(_unsealedbid.serial),
+                ],
+                output_addrs: vec![addressing::sealed_auction_addr(_unsealedbid.serial)],
             }),
             Wrapper::Es(_serial) => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![String::from(SEALEDAUCTIONLIST), String::from(BALANCEBOOK)],
-                output_addrs: vec![String::from(SEALEDAUCTIONLIST), String::from(BALANCEBOOK)],
+                input_addrs: vec![
+                    String::from(PERMISSIONS),
+                    String::from(SEALEDAUCTIONLIST),
+                    addressing::sealed_auction_addr(*_serial),
+                    String::from(ACCOUNT_PREFIX),
+                    String::from(BALANCEBOOK),
+                ],
+                output_addrs: vec![
+                    String::from(SEALEDAUCTIONLIST),
+                    addressing::sealed_auction_addr(*_serial),
+                    String::from(ACCOUNT_PREFIX),
+                ],
             }),
             Wrapper::Cs => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![String::from(SEALEDAUCTIONLIST)],
-                output_addrs: vec![String::from(SEALEDAUCTIONLIST)],
+                input_addrs: vec![
+                    String::from(PERMISSIONS),
+                    String::from(SEALEDAUCTIONLIST),
+                    String::from(SEALED_AUCTION_PREFIX),
+                ],
+                output_addrs: vec![
+                    String::from(SEALEDAUCTIONLIST),
+                    String::from(SEALED_AUCTION_PREFIX),
+                ],
             }),
-
-            _ => Ok(CliRequest {
+            Wrapper::Pm(_msg) => Ok(CliRequest {
                 cbor_payload: serialized,
                 family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
-                input_addrs: vec![],
-                output_addrs: vec![],
+                input_addrs: vec![String::from(PERMISSIONS)],
+                output_addrs: vec![String::from(PERMISSIONS)],
             }),
+            Wrapper::Am(_adminmsg) => {
+                let touched: Vec<String> = match _adminmsg {
+                    AdminMsg::AdminCredit(_change) | AdminMsg::AdminDebit(_change) => {
+                        vec![account_addr(&_change.addr)]
+                    }
+                    AdminMsg::NewUser(_acct) => {
+                        vec![account_addr(&_acct.addr), String::from(ACCOUNTINDEX)]
+                    }
+                    AdminMsg::EndAuction(_serial) => {
+                        vec![
+                            String::from(AUCTIONLIST),
+                            addressing::auction_addr(*_serial),
+                            String::from(ACCOUNT_PREFIX),
+                        ]
+                    }
+                    AdminMsg::ClearOrderBook => {
+                        vec![
+                            String::from(ORDERBOOK_PREFIX),
+                            String::from(ACCOUNT_PREFIX),
+                            String::from(ORDERSTATUS_PREFIX),
+                        ]
+                    }
+                    AdminMsg::Step => {
+                        vec![
+                            String::from(MARKETSTATUS),
+                            String::from(ORDERBOOK_PREFIX),
+                            String::from(ACCOUNT_PREFIX),
+                            String::from(ORDERSTATUS_PREFIX),
+                        ]
+                    }
+                    AdminMsg::ListInstrument(_)
+                    | AdminMsg::SetMarketMode(_, _)
+                    | AdminMsg::SetMatching(_, _)
+                    | AdminMsg::SetTradingRules(_, _)
+                    | AdminMsg::SetPriceBand(_, _)
+                    | AdminMsg::SetCircuitBreaker(_, _)
+                    | AdminMsg::SetCashPlaces(_) => vec![String::from(INSTRUMENTS)],
+                    AdminMsg::SetFees(_) => vec![String::from(FEESCHEDULE)],
+                    AdminMsg::CallAuction(_instrument) => {
+                        with_book(_instrument, &[ACCOUNT_PREFIX, ORDERSTATUS_PREFIX])
+                    }
+                };
+                // Accounts and order status records may still be in the legacy books.
+                let mut inputs = vec![
+                    String::from(PERMISSIONS),
+                    String::from(BALANCEBOOK),
+                    String::from(ORDERSTATUS),
+                ];
+                match _adminmsg {
+                    // Open auctions' bids are counted among the holds.
+                    AdminMsg::ClearOrderBook => {
+                        inputs.push(String::from(INSTRUMENTS));
+                        inputs.push(String::from(AUCTIONLIST));
+                        inputs.push(String::from(AUCTION_PREFIX));
+                    }
+                    AdminMsg::Step => {
+                        inputs.push(String::from(INSTRUMENTS));
+                        inputs.push(String::from(BLOCKINFO_CONFIG));
+                        inputs.push(String::from(AUCTIONLIST));
+                        inputs.push(String::from(AUCTION_PREFIX));
+                    }
+                    AdminMsg::SetMarketMode(_instrument, _) => {
+                        inputs.extend(with_book(_instrument, &[]))
+                    }
+                    AdminMsg::CallAuction(_) => {
+                        inputs.push(String::from(INSTRUMENTS));
+                        inputs.push(String::from(FEESCHEDULE));
+                        inputs.push(String::from(BLOCKINFO_CONFIG));
+                    }
+                    AdminMsg::SetFees(_fees) => {
+                        inputs.extend(_fees.treasury.as_ref().map(|x| account_addr(x)))
+                    }
+                    AdminMsg::SetCashPlaces(_) => {
+                        inputs.push(String::from(ACCOUNTINDEX));
+                        inputs.push(String::from(ACCOUNT_PREFIX));
+                        inputs.push(String::from(ORDERBOOK_PREFIX));
+                    }
+                    _ => (),
+                }
+                inputs.extend(touched.iter().cloned());
+                Ok(CliRequest {
+                    cbor_payload: serialized,
+                    family_meta: FamilyMeta::from(FAMILY_NAME, FAMILY_PREFIX, FAMILY_VERSION),
+                    input_addrs: inputs,
+                    output_addrs: touched,
+                })
+            }
         }
     }
 }
@@ -0,0 +1,1499 @@
+extern crate dmktp;
+extern crate failure;
+extern crate rand;
+extern crate serde_cbor;
+extern crate swth_cli_libv2;
+
+use dmktp::address::Address;
+use dmktp::addressing;
+use dmktp::adminmsg::BalanceChange;
+use dmktp::amend::Amend;
+use dmktp::amount::{Amount, Quantity};
+use dmktp::auction::{Auction, AuctionList, Bid};
+use dmktp::balancebook::BalanceBook;
+use dmktp::callauction;
+use dmktp::decimal::{Decimal, Precision};
+use dmktp::errors::LibError;
+use dmktp::events::{self, MarketEvent};
+use dmktp::fees::{FeeSchedule, FeeTier};
+use dmktp::instrument::{default_instrument, InstrumentRegistry, MarketMode, DEFAULT_INSTRUMENT};
+use dmktp::marketstatus::MarketStatus;
+use dmktp::matching::{self, MatchingAlgorithm};
+use dmktp::order::{BuyOrder, OrderT, OrderType, SelfTradePrevention, SellOrder, Side};
+use dmktp::orderbook::{fill_buy, fill_sell, OrderBook};
+use dmktp::orderstatus::{OrderStatus, OrderStatusBook};
+use dmktp::pricelevels::PriceLevels;
+use dmktp::pricelimits::{CircuitBreaker, Halt, PriceBand, ReferencePrice, MAX_BREAKER_WINDOW};
+use dmktp::reach;
+use dmktp::rules::TradingRules;
+use dmktp::settlement::Snapshot;
+use dmktp::stoporder::StopOrder;
+use dmktp::tradeledger::TradeLedger;
+use dmktp::useracct::UserAccount;
+use dmktp::wrapper::Wrapper;
+use rand::prng::XorShiftRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeSet, BinaryHeap};
+use swth_cli_libv2::clireq::ClientRequest;
+
+mod common;
+
+pub fn mk_addr1() -> Address {
+    Address::from_string(String::from("1111111111111111111111111111111111111111")).unwrap()
+}
+pub fn mk_addr2() -> Address {
+    Address::from_string(String::from("2222222222222222222222222222222222222222")).unwrap()
+}
+
+pub fn mk_balancebook() -> BalanceBook {
+    let mut bb: BalanceBook = BalanceBook::new();
+    bb.insert_seeded(
+        UserAccount::new_seeded(String::from("Alex"), mk_addr1().to_string(), 10_000, 1_000)
+            .unwrap(),
+    ).unwrap();
+    bb.insert_seeded(
+        UserAccount::new_seeded(String::from("Bob"), mk_addr2().to_string(), 10_000, 1_000)
+            .unwrap(),
+    ).unwrap();
+    bb
+}
+
+#[test]
+fn refund_all_releases_holds() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+
+    fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 10, 50, 0)).unwrap();
+    fill_sell(
+        &mut ob,
+        &mut bb,
+        SellOrder::from_vals(mk_addr2(), 20, 30, 0),
+    ).unwrap();
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get(), 500);
+    assert_eq!(
+        bb.get_by_addr(&mk_addr2())
+            .unwrap()
+            .hold_assets(DEFAULT_INSTRUMENT),
+        30
+    );
+
+    ob.refund_all(&mut bb).unwrap();
+    assert!(ob.is_buy_empty() && ob.is_sell_empty());
+    assert_eq!(bb, mk_balancebook());
+}
+
+#[test]
+fn admin_balance_change() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    let change = BalanceChange::from_vals(mk_addr1(), 100, 5, default_instrument());
+
+    change.credit(&mut bb).unwrap();
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().cash.get(), 10_100);
+    assert_eq!(
+        bb.get_by_addr(&mk_addr1())
+            .unwrap()
+            .assets(DEFAULT_INSTRUMENT),
+        1_005
+    );
+
+    change.debit(&mut bb).unwrap();
+    assert_eq!(bb, mk_balancebook());
+
+    let overdraw = BalanceChange::from_vals(mk_addr1(), 20_000, 0, default_instrument());
+    assert!(overdraw.debit(&mut bb).is_err());
+
+    // holds are left to the orders that took them
+    fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 10, 50, 0)).unwrap();
+    let held = bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get();
+    BalanceChange::from_vals(mk_addr1(), 100, 0, default_instrument())
+        .debit(&mut bb)
+        .unwrap();
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get(), held);
+    assert_eq!(
+        bb.get_by_addr(&mk_addr1()).unwrap().cash.get(),
+        10_000 - 500 - 100
+    );
+}
+
+#[test]
+fn cancel_releases_hold() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+
+    fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 10, 50, 0)).unwrap();
+    fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 11, 10, 0)).unwrap();
+    fill_sell(
+        &mut ob,
+        &mut bb,
+        SellOrder::from_vals(mk_addr2(), 20, 30, 0),
+    ).unwrap();
+
+    // only the owner may cancel
+    assert!(ob.cancel_buy(&mut bb, 0, &mk_addr2()).is_err());
+    assert!(ob.cancel_sell(&mut bb, 7, &mk_addr2()).is_err());
+    assert_eq!(ob.buy_cardinality(), 2);
+
+    ob.cancel_buy(&mut bb, 0, &mk_addr1()).unwrap();
+    assert_eq!(ob.buy_cardinality(), 1);
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get(), 110);
+
+    assert_eq!(ob.cancel_all_for(&mut bb, &mk_addr1()).unwrap().len(), 1);
+    assert_eq!(ob.cancel_all_for(&mut bb, &mk_addr2()).unwrap().len(), 1);
+    assert_eq!(bb, mk_balancebook());
+}
+
+#[test]
+fn order_status_lifecycle() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    let mut status = OrderStatusBook::new();
+
+    let mut buy = BuyOrder::from_vals(mk_addr1(), 10, 50, 0);
+    buy.set_id(String::from("buy1"));
+    let fills = fill_buy(&mut ob, &mut bb, buy.clone()).unwrap();
+    status.record_order(&buy, &fills);
+
+    let mut sell = SellOrder::from_vals(mk_addr2(), 10, 20, 0);
+    sell.set_id(String::from("sell1"));
+    let fills = fill_sell(&mut ob, &mut bb, sell.clone()).unwrap();
+    assert_eq!(fills.len(), 1);
+    status.record_order(&sell, &fills);
+
+    let buy_record = status.get(&String::from("buy1")).unwrap().clone();
+    assert_eq!(buy_record.filled, 20);
+    assert_eq!(buy_record.status, OrderStatus::PartiallyFilled);
+    assert_eq!(
+        status.get(&String::from("sell1")).unwrap().status,
+        OrderStatus::Filled
+    );
+
+    assert!(ob
+        .cancel_by_id(&mut bb, &String::from("buy1"), &mk_addr2())
+        .is_err());
+    let cancelled = ob
+        .cancel_by_id(&mut bb, &String::from("buy1"), &mk_addr1())
+        .unwrap();
+    status.mark_cancelled(&vec![cancelled]);
+    assert_eq!(
+        status.get(&String::from("buy1")).unwrap().status,
+        OrderStatus::Cancelled
+    );
+    assert_eq!(status.for_owner(&mk_addr1()).len(), 1);
+}
+
+#[test]
+fn fills_recorded_on_trade_ledger() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    let mut ledger = TradeLedger::new();
+
+    let fills = fill_sell(&mut ob, &mut bb, SellOrder::from_vals(mk_addr2(), 10, 5, 0)).unwrap();
+    ledger.record_fills(&fills, 0);
+    let fills = fill_sell(&mut ob, &mut bb, SellOrder::from_vals(mk_addr2(), 12, 5, 0)).unwrap();
+    ledger.record_fills(&fills, 0);
+    assert!(ledger.is_empty());
+
+    let fills = fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 12, 8, 0)).unwrap();
+    ledger.record_fills(&fills, 3);
+    assert_eq!(ledger.len(), 2);
+    assert_eq!(ledger.trades[0].price, 10);
+    assert_eq!(ledger.trades[1].qty, 3);
+    assert_eq!(ledger.trades[1].seq, 1);
+    assert_eq!(ledger.since_block(3).len(), 2);
+    assert_eq!(ledger.for_account(&mk_addr2()).len(), 2);
+    // The next transaction's trades carry on from where the book's tape left off.
+    let mut next = TradeLedger::starting_at(ledger.next_seq);
+    let fills = fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 12, 2, 0)).unwrap();
+    assert_eq!(next.record_fills(&fills, 4)[0].seq, 2);
+
+    let csv = ledger.to_csv();
+    assert_eq!(csv.lines().count(), 3);
+    assert!(csv.lines().nth(1).unwrap().ends_with(",10,5,buy,0,0"));
+}
+
+#[test]
+fn trade_events_carry_filterable_attributes() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    let mut ledger = TradeLedger::new();
+
+    fill_sell(&mut ob, &mut bb, SellOrder::from_vals(mk_addr2(), 10, 5, 0)).unwrap();
+    let fills = fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 10, 5, 0)).unwrap();
+    let trades = ledger.record_fills(&fills, 2);
+    let event = MarketEvent::trade(&trades[0]).unwrap();
+
+    assert_eq!(event.event_type, events::TRADE);
+    assert_eq!(event.get_attribute("block"), Some(&String::from("2")));
+    assert!(event.matches(&vec![(String::from("buyer"), mk_addr1().to_string())]));
+    assert!(!event.matches(&vec![(String::from("seller"), mk_addr1().to_string())]));
+}
+
+#[test]
+fn instruments_keep_separate_books_and_balances() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    let mut gold = OrderBook::for_instrument(String::from("GOLD"));
+    BalanceChange::from_vals(mk_addr2(), 0, 40, String::from("GOLD"))
+        .credit(&mut bb)
+        .unwrap();
+
+    let mut sell = SellOrder::from_vals(mk_addr2(), 10, 40, 0);
+    sell.set_instrument(String::from("GOLD"));
+    // an order only trades on its own instrument's book
+    assert!(fill_sell(&mut ob, &mut bb, sell.clone()).is_err());
+    fill_sell(&mut gold, &mut bb, sell).unwrap();
+    fill_sell(&mut ob, &mut bb, SellOrder::from_vals(mk_addr2(), 10, 5, 0)).unwrap();
+
+    let mut buy = BuyOrder::from_vals(mk_addr1(), 10, 25, 0);
+    buy.set_instrument(String::from("GOLD"));
+    let fills = fill_buy(&mut gold, &mut bb, buy).unwrap();
+    assert_eq!(fills[0].instrument, "GOLD");
+    assert_eq!(ob.sell_cardinality(), 1);
+
+    let buyer = bb.get_by_addr(&mk_addr1()).unwrap();
+    assert_eq!(buyer.assets("GOLD"), 25);
+    assert_eq!(buyer.assets(DEFAULT_INSTRUMENT), 1_000);
+    let seller = bb.get_by_addr(&mk_addr2()).unwrap();
+    assert_eq!(seller.hold_assets("GOLD"), 15);
+    assert_eq!(seller.hold_assets(DEFAULT_INSTRUMENT), 5);
+}
+
+#[test]
+fn market_ioc_and_fok_orders_never_rest() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    let mut status = OrderStatusBook::new();
+
+    fill_sell(&mut ob, &mut bb, SellOrder::from_vals(mk_addr2(), 10, 5, 0)).unwrap();
+    fill_sell(&mut ob, &mut bb, SellOrder::from_vals(mk_addr2(), 12, 5, 0)).unwrap();
+
+    // not enough at or below 11 to fill 8, so nothing executes
+    let mut fok = BuyOrder::from_vals(mk_addr1(), 11, 8, 0);
+    fok.set_order_type(OrderType::FillOrKill);
+    assert!(fill_buy(&mut ob, &mut bb, fok).unwrap().is_empty());
+    assert_eq!(ob.sell_cardinality(), 2);
+
+    // a market order has no limit and sweeps through both levels
+    let mut market = BuyOrder::from_vals(mk_addr1(), 0, 8, 0);
+    market.set_order_type(OrderType::Market);
+    market.set_id(String::from("mkt"));
+    let fills = fill_buy(&mut ob, &mut bb, market.clone()).unwrap();
+    status.record_order(&market, &fills);
+    let taken: Vec<(u64, u64)> = fills.iter().map(|x| (x.price, x.qty)).collect();
+    assert_eq!(taken, vec![(10, 5), (12, 3)]);
+    assert!(ob.is_buy_empty());
+    let record = status.get(&String::from("mkt")).unwrap();
+    assert_eq!((record.filled, record.status), (8, OrderStatus::Filled));
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().cash.get(), 10_000 - 86);
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get(), 0);
+
+    // but buys no more than the buyer's cash pays for; the rest is cancelled
+    fill_sell(
+        &mut ob,
+        &mut bb,
+        SellOrder::from_vals(mk_addr2(), 50, 300, 0),
+    ).unwrap();
+    let mut market = BuyOrder::from_vals(mk_addr1(), 0, 1_000, 0);
+    market.set_order_type(OrderType::Market);
+    market.set_id(String::from("capped"));
+    let fills = fill_buy(&mut ob, &mut bb, market.clone()).unwrap();
+    status.record_order(&market, &fills);
+    let taken: Vec<(u64, u64)> = fills.iter().map(|x| (x.price, x.qty)).collect();
+    assert_eq!(taken, vec![(12, 2), (50, 197)]);
+    let record = status.get(&String::from("capped")).unwrap();
+    assert_eq!(
+        (record.filled, record.status),
+        (199, OrderStatus::Cancelled)
+    );
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().cash.get(), 40);
+    assert_eq!(ob.sell_cardinality(), 1);
+
+    // an IOC sell trades at the resting bid's price, not its own limit
+    fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 9, 4, 0)).unwrap();
+    let mut ioc = SellOrder::from_vals(mk_addr2(), 1, 6, 0);
+    ioc.set_order_type(OrderType::ImmediateOrCancel);
+    let fills = fill_sell(&mut ob, &mut bb, ioc).unwrap();
+    assert_eq!((fills[0].price, fills[0].qty), (9, 4));
+    assert_eq!(ob.sell_cardinality(), 1);
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get(), 0);
+}
+
+// One fill per resting order, 100k of them over a hundred levels, on a thread with a
+// small stack: matching goes round a loop rather than taking a stack frame per fill.
+#[test]
+fn market_orders_sweep_a_deep_book() {
+    const DEPTH: u64 = 100_000;
+    let sweep = || {
+        let mut bb = BalanceBook::new();
+        bb.insert_seeded(
+            UserAccount::new_seeded(String::from("Alex"), mk_addr1().to_string(), 20_000_000, 0)
+                .unwrap(),
+        ).unwrap();
+        bb.insert_seeded(
+            UserAccount::new_seeded(String::from("Bob"), mk_addr2().to_string(), 0, DEPTH).unwrap(),
+        ).unwrap();
+        let mut ob = OrderBook::new();
+        for n in 0..DEPTH {
+            let sell = SellOrder::from_vals(mk_addr2(), 1 + n % 100, 1, 0);
+            ob.insert_sell_order(&mut bb, sell, true).unwrap();
+        }
+        let mut market = BuyOrder::from_vals(mk_addr1(), 0, DEPTH, 0);
+        market.set_order_type(OrderType::Market);
+        let fills = fill_buy(&mut ob, &mut bb, market).unwrap();
+        assert_eq!(fills.len() as u64, DEPTH);
+        assert_eq!((fills[0].price, fills[fills.len() - 1].price), (1, 100));
+        assert_eq!(ob.sell_cardinality(), 0);
+        assert_eq!(bb.get_by_addr(&mk_addr2()).unwrap().cash.get(), 5_050_000);
+
+        // and back the other way, the buyer now resting the bids
+        for n in 0..DEPTH {
+            let buy = BuyOrder::from_vals(mk_addr1(), 1 + n % 100, 1, 0);
+            ob.insert_buy_order(&mut bb, buy, true).unwrap();
+        }
+        let mut market = SellOrder::from_vals(mk_addr2(), 0, DEPTH, 0);
+        market.set_order_type(OrderType::Market);
+        bb.credit_assets(&mk_addr2(), DEFAULT_INSTRUMENT, DEPTH)
+            .unwrap();
+        let fills = fill_sell(&mut ob, &mut bb, market).unwrap();
+        assert_eq!(fills.len() as u64, DEPTH);
+        assert!(ob.is_buy_empty());
+    };
+    std::thread::Builder::new()
+        .stack_size(256 * 1024)
+        .spawn(sweep)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn expired_orders_are_purged_and_refunded() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+
+    let mut stale = BuyOrder::from_vals(mk_addr1(), 10, 50, 0);
+    stale.set_id(String::from("stale"));
+    stale.set_expires(Some(2));
+    fill_buy(&mut ob, &mut bb, stale.clone()).unwrap();
+    fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 9, 10, 0)).unwrap();
+
+    assert!(!stale.is_expired(2));
+    assert!(ob.purge_expired(&mut bb, 2).unwrap().is_empty());
+    assert_eq!(
+        ob.expired_orders(3),
+        vec![(mk_addr1(), String::from("stale"))]
+    );
+    assert_eq!(
+        ob.purge_expired(&mut bb, 3).unwrap(),
+        vec![String::from("stale")]
+    );
+    assert_eq!(ob.buy_cardinality(), 1);
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get(), 90);
+}
+
+#[test]
+fn stops_trigger_on_last_trade_price() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    let mut status = OrderStatusBook::new();
+
+    let mut stop_loss = SellOrder::from_vals(mk_addr2(), 1, 10, 0);
+    stop_loss.set_order_type(OrderType::Market);
+    let mut stop = StopOrder::Sell(9, stop_loss);
+    stop.set_id(String::from("stop"));
+    status.record_stop(&stop);
+    ob.add_stop(stop).unwrap();
+    assert_eq!(
+        status.get(&String::from("stop")).unwrap().status,
+        OrderStatus::Pending
+    );
+
+    fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 8, 20, 0)).unwrap();
+    assert!(ob.trigger_stops(&mut bb, &|_, _| Ok(())).is_empty());
+
+    // a trade at 8 is at or below the trigger, so the stop sells into the remaining bid
+    fill_sell(&mut ob, &mut bb, SellOrder::from_vals(mk_addr2(), 8, 5, 0)).unwrap();
+    let fired = ob.trigger_stops(&mut bb, &|_, _| Ok(()));
+    assert_eq!(fired.len(), 1);
+    let (ref stop, ref result) = fired[0];
+    let fills = result.as_ref().unwrap();
+    assert_eq!((fills[0].price, fills[0].qty), (8, 10));
+    status.record_triggered(stop, fills);
+    assert_eq!(
+        status.get(&String::from("stop")).unwrap().status,
+        OrderStatus::Filled
+    );
+    assert!(ob.stop_orders.is_empty());
+
+    // a stop its owner can no longer pay for is rejected without touching the book
+    let mut unfunded = StopOrder::Buy(8, BuyOrder::from_vals(mk_addr1(), 9, 10_000, 0));
+    unfunded.set_id(String::from("unfunded"));
+    ob.add_stop(unfunded).unwrap();
+    let (book, balances) = (ob.clone(), bb.clone());
+    let fired = ob.trigger_stops(&mut bb, &|_, _| Ok(()));
+    assert_eq!(fired.len(), 1);
+    assert!(fired[0].1.is_err());
+    assert_eq!(
+        (ob.buy_orders, ob.sell_orders),
+        (book.buy_orders, book.sell_orders)
+    );
+    assert_eq!(bb, balances);
+}
+
+#[test]
+fn failed_stops_are_put_back_and_the_cascade_goes_on() {
+    let mut full = mk_balancebook();
+    let mut ob = OrderBook::new();
+    fill_sell(
+        &mut ob,
+        &mut full,
+        SellOrder::from_vals(mk_addr2(), 10, 5, 0),
+    ).unwrap();
+    ob.last_price = Some(10);
+
+    // the buy is funded, but the seller it reaches isn't loaded, so it fails to settle
+    let mut fails = StopOrder::Buy(10, BuyOrder::from_vals(mk_addr1(), 10, 5, 0));
+    fails.set_id(String::from("fails"));
+    let mut off_tick = StopOrder::Sell(10, SellOrder::from_vals(mk_addr1(), 21, 5, 0));
+    off_tick.set_id(String::from("off tick"));
+    let mut rests = StopOrder::Sell(10, SellOrder::from_vals(mk_addr1(), 20, 5, 0));
+    rests.set_id(String::from("rests"));
+    for stop in vec![fails, off_tick, rests].into_iter() {
+        ob.add_stop(stop).unwrap();
+    }
+    let mut bb = BalanceBook::new();
+    bb.insert_seeded(full.get_by_addr(&mk_addr1()).unwrap().clone())
+        .unwrap();
+
+    // the instrument's rules are checked as the stops trigger
+    let rules = TradingRules {
+        tick_size: 5,
+        lot_size: 1,
+        min_qty: 1,
+        max_qty: None,
+        max_notional: None,
+    };
+    let (book, balances) = (ob.clone(), bb.clone());
+    let fired = ob.trigger_stops(&mut bb, &|stop, _| match *stop {
+        StopOrder::Buy(_, ref order) => Ok(rules.check_order(order, &Precision::default())?),
+        StopOrder::Sell(_, ref order) => Ok(rules.check_order(order, &Precision::default())?),
+    });
+    let results: Vec<(&String, bool)> = fired.iter().map(|x| (x.0.get_id(), x.1.is_ok())).collect();
+    assert_eq!(
+        results,
+        vec![
+            (&String::from("fails"), false),
+            (&String::from("off tick"), false),
+            (&String::from("rests"), true),
+        ]
+    );
+    // only the stop that went through left a mark
+    assert_eq!(ob.buy_orders, book.buy_orders);
+    let asks: Vec<(u64, u64)> = ob
+        .sell_orders
+        .iter()
+        .map(|x| (x.get_price(), x.get_qty()))
+        .collect();
+    assert_eq!(asks, vec![(10, 5), (20, 5)]);
+    let (before, after) = (
+        balances.get_by_addr(&mk_addr1()).unwrap(),
+        bb.get_by_addr(&mk_addr1()).unwrap(),
+    );
+    assert_eq!(after.cash, before.cash);
+    assert_eq!(after.hold_assets(DEFAULT_INSTRUMENT), 5);
+    assert!(ob.stop_orders.is_empty());
+}
+
+#[test]
+fn amend_adjusts_hold_and_priority() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+
+    let mut first = BuyOrder::from_vals(mk_addr1(), 10, 50, 0);
+    first.set_id(String::from("first"));
+    let mut second = BuyOrder::from_vals(mk_addr2(), 10, 50, 0);
+    second.set_id(String::from("second"));
+    fill_buy(&mut ob, &mut bb, first).unwrap();
+    fill_buy(&mut ob, &mut bb, second).unwrap();
+
+    // a smaller quantity keeps the order at the front of the queue
+    let reduce = Amend::from_vals(String::from("first"), None, Some(20));
+    assert_eq!(
+        ob.amend_order(&mut bb, &reduce, &mk_addr1()).unwrap(),
+        (10, 20)
+    );
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get(), 200);
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().cash.get(), 9_800);
+    assert_eq!(ob.buy_orders.peek().unwrap().get_id(), "first");
+
+    // a larger one sends it to the back
+    let increase = Amend::from_vals(String::from("first"), None, Some(30));
+    ob.amend_order(&mut bb, &increase, &mk_addr1()).unwrap();
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get(), 300);
+    assert_eq!(ob.buy_orders.peek().unwrap().get_id(), "second");
+
+    let not_owner = Amend::from_vals(String::from("second"), Some(11), None);
+    assert!(ob.amend_order(&mut bb, &not_owner, &mk_addr1()).is_err());
+    let too_large = Amend::from_vals(String::from("first"), Some(1_000), None);
+    assert!(ob.amend_order(&mut bb, &too_large, &mk_addr1()).is_err());
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get(), 300);
+
+    fill_sell(&mut ob, &mut bb, SellOrder::from_vals(mk_addr2(), 12, 5, 0)).unwrap();
+    let crossing = Amend::from_vals(String::from("first"), Some(12), None);
+    assert!(ob.amend_order(&mut bb, &crossing, &mk_addr1()).is_err());
+    // a call book crosses until its auction runs
+    ob.mode = MarketMode::Call;
+    assert_eq!(
+        ob.amend_order(&mut bb, &crossing, &mk_addr1()).unwrap(),
+        (12, 30)
+    );
+
+    ob.refund_all(&mut bb).unwrap();
+    assert_eq!(bb, mk_balancebook());
+}
+
+#[test]
+fn iceberg_orders_refill_from_reserve() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+
+    let mut iceberg = SellOrder::from_vals(mk_addr2(), 10, 25, 0);
+    iceberg.set_id(String::from("iceberg"));
+    iceberg.set_peak(Some(10));
+    fill_sell(&mut ob, &mut bb, iceberg).unwrap();
+    let mut plain = SellOrder::from_vals(mk_addr2(), 10, 5, 0);
+    plain.set_id(String::from("plain"));
+    fill_sell(&mut ob, &mut bb, plain).unwrap();
+    assert_eq!(
+        bb.get_by_addr(&mk_addr2())
+            .unwrap()
+            .hold_assets(DEFAULT_INSTRUMENT),
+        30
+    );
+
+    // others only see the shown peak
+    let view = ob.view_for(Some(&mk_addr1()));
+    let shown: Vec<u64> = view.sell_orders.iter().map(|x| x.get_qty()).collect();
+    assert_eq!(shown.iter().sum::<u64>(), 15);
+    let own = ob.view_for(Some(&mk_addr2()));
+    assert_eq!(own.sell_orders.iter().map(|x| x.get_qty()).sum::<u64>(), 30);
+
+    // taking the shown peak refills it behind the plain order at the same price
+    let fills = fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 10, 12, 0)).unwrap();
+    let taken: Vec<(&str, u64)> = fills.iter().map(|x| (x.sell_id.as_str(), x.qty)).collect();
+    assert_eq!(taken, vec![("iceberg", 10), ("plain", 2)]);
+
+    let fills = fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 10, 18, 0)).unwrap();
+    let taken: Vec<(&str, u64)> = fills.iter().map(|x| (x.sell_id.as_str(), x.qty)).collect();
+    assert_eq!(taken, vec![("plain", 3), ("iceberg", 10), ("iceberg", 5)]);
+    assert!(ob.is_sell_empty());
+    assert_eq!(
+        bb.get_by_addr(&mk_addr2())
+            .unwrap()
+            .hold_assets(DEFAULT_INSTRUMENT),
+        0
+    );
+}
+
+#[test]
+fn call_auction_clears_at_one_price_with_pro_rata_rationing() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+
+    for &(price, qty) in [(9, 10), (10, 5)].iter() {
+        callauction::submit(
+            SellOrder::from_vals(mk_addr2(), price, qty, 0),
+            &mut bb,
+            &mut ob,
+        ).unwrap();
+    }
+    for &(price, qty) in [(11, 7), (11, 11), (8, 5)].iter() {
+        callauction::submit(
+            BuyOrder::from_vals(mk_addr1(), price, qty, 0),
+            &mut bb,
+            &mut ob,
+        ).unwrap();
+    }
+    let mut market = BuyOrder::from_vals(mk_addr1(), 20, 1, 0);
+    market.set_order_type(OrderType::Market);
+    assert!(callauction::submit(market, &mut bb, &mut ob).is_err());
+
+    // 10 and 11 both clear 15 with an imbalance of 3; the lower price wins
+    assert_eq!(callauction::clearing_price(&ob), Some((10, 15)));
+    let fills = callauction::clear(&mut ob, &mut bb).unwrap();
+    assert!(fills.iter().all(|x| x.price == 10));
+    let qtys: Vec<u64> = fills.iter().map(|x| x.qty).collect();
+    assert_eq!(qtys, vec![6, 4, 5]);
+
+    // the 18 bid at 11 shares 15 pro rata, 5.8 and 9.2, the spare unit going to the older bid
+    let left: Vec<(u64, u64)> = ob
+        .buy_orders
+        .iter()
+        .map(|x| (x.get_price(), x.get_qty()))
+        .collect();
+    assert_eq!(left, vec![(11, 1), (11, 2), (8, 5)]);
+    assert!(ob.is_sell_empty());
+    assert_eq!(callauction::clearing_price(&ob), None);
+
+    let buyer = bb.get_by_addr(&mk_addr1()).unwrap();
+    assert_eq!((buyer.cash.get(), buyer.hold_cash.get()), (9_777, 73));
+    assert_eq!(buyer.assets(DEFAULT_INSTRUMENT), 1_015);
+    let seller = bb.get_by_addr(&mk_addr2()).unwrap();
+    assert_eq!(
+        (seller.cash.get(), seller.assets(DEFAULT_INSTRUMENT)),
+        (10_150, 985)
+    );
+    assert_eq!(seller.hold_assets(DEFAULT_INSTRUMENT), 0);
+}
+
+#[test]
+fn call_auction_never_pairs_an_owner_with_themselves() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    callauction::submit(SellOrder::from_vals(mk_addr1(), 9, 10, 0), &mut bb, &mut ob).unwrap();
+    callauction::submit(BuyOrder::from_vals(mk_addr1(), 10, 6, 0), &mut bb, &mut ob).unwrap();
+    callauction::submit(BuyOrder::from_vals(mk_addr2(), 10, 6, 0), &mut bb, &mut ob).unwrap();
+    let before = Snapshot::of(&bb, &[&ob]).unwrap();
+
+    // only the other owner's 6 can trade with the sell
+    assert_eq!(callauction::clearing_price(&ob), Some((9, 6)));
+    // each bid is allotted 3; the owner's own goes to what is left of the other's bid
+    let fills = callauction::clear(&mut ob, &mut bb).unwrap();
+    let traded: Vec<(Address, u64)> = fills.iter().map(|x| (x.buyer.clone(), x.qty)).collect();
+    assert_eq!(traded, vec![(mk_addr2(), 3), (mk_addr2(), 3)]);
+    let left: Vec<(Address, u64)> = ob
+        .buy_orders
+        .iter()
+        .map(|x| (x.get_addr(), x.get_qty()))
+        .collect();
+    assert_eq!(left, vec![(mk_addr1(), 6)]);
+    let left: Vec<u64> = ob.sell_orders.iter().map(|x| x.get_qty()).collect();
+    assert_eq!(left, vec![4]);
+    assert!(before.check(&Snapshot::of(&bb, &[&ob]).unwrap()).is_ok());
+
+    // the book crosses only the owner's own orders, so there is nothing left to auction
+    // and it can go back to trading continuously, where another owner trades with either
+    assert_eq!(callauction::clearing_price(&ob), None);
+    assert!(callauction::clear(&mut ob, &mut bb).unwrap().is_empty());
+    ob.mode = MarketMode::Continuous;
+    let fills = fill_sell(&mut ob, &mut bb, SellOrder::from_vals(mk_addr2(), 10, 6, 0)).unwrap();
+    assert_eq!((fills[0].buyer.clone(), fills[0].qty), (mk_addr1(), 6));
+}
+
+#[test]
+fn fees_go_to_the_treasury() {
+    let mut bb = mk_balancebook();
+    let treasury =
+        Address::from_string(String::from("3333333333333333333333333333333333333333")).unwrap();
+    bb.insert_seeded(
+        UserAccount::new_seeded(String::from("Treasury"), treasury.to_string(), 0, 0).unwrap(),
+    ).unwrap();
+    let mut ob = OrderBook::new();
+    let mut ledger = TradeLedger::new();
+
+    let mut fees = FeeSchedule::new();
+    fees.maker_bps = 10;
+    fees.taker_bps = 100;
+    fees.treasury = Some(treasury.clone());
+    fees.tiers.insert(
+        mk_addr2().to_string(),
+        FeeTier {
+            maker_bps: 0,
+            taker_bps: 50,
+        },
+    );
+    assert!(fees.check().is_ok());
+    ob.fees = fees.clone();
+    let before = Snapshot::of(&bb, &[&ob]).unwrap();
+
+    fill_sell(
+        &mut ob,
+        &mut bb,
+        SellOrder::from_vals(mk_addr2(), 20, 100, 0),
+    ).unwrap();
+    let fills = fill_buy(
+        &mut ob,
+        &mut bb,
+        BuyOrder::from_vals(mk_addr1(), 20, 100, 0),
+    ).unwrap();
+    // the buyer takes at 100 bps of the 2000 paid; the seller's tier makes no maker fee
+    assert_eq!((fills[0].buyer_fee, fills[0].seller_fee), (20, 0));
+    ledger.record_fills(&fills, 0);
+
+    fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 10, 50, 0)).unwrap();
+    let fills = fill_sell(
+        &mut ob,
+        &mut bb,
+        SellOrder::from_vals(mk_addr2(), 10, 50, 0),
+    ).unwrap();
+    // 10 and 50 bps of 500 are 0.5 and 2.5, both rounded up
+    assert_eq!((fills[0].buyer_fee, fills[0].seller_fee), (1, 3));
+    ledger.record_fills(&fills, 0);
+
+    // all of it in cash, none in assets
+    let t = bb.get_by_addr(&treasury).unwrap();
+    assert_eq!((t.cash.get(), t.assets(DEFAULT_INSTRUMENT)), (24, 0));
+    let buyer = bb.get_by_addr(&mk_addr1()).unwrap();
+    assert_eq!(
+        (buyer.cash.get(), buyer.assets(DEFAULT_INSTRUMENT)),
+        (7_479, 1_150)
+    );
+    assert_eq!(bb.get_by_addr(&mk_addr2()).unwrap().cash.get(), 12_497);
+    assert_eq!(ledger.fees_collected(), 24);
+    assert!(before.check(&Snapshot::of(&bb, &[&ob]).unwrap()).is_ok());
+
+    // a buy must cover its taker fee as well as what it pays
+    assert!(fill_buy(
+        &mut ob,
+        &mut bb,
+        BuyOrder::from_vals(mk_addr1(), 7_479, 1, 0)
+    )
+    .is_err());
+
+    fees.taker_bps = 10_001;
+    assert!(fees.check().is_err());
+}
+
+#[test]
+fn resting_buys_hold_their_fees() {
+    let treasury =
+        Address::from_string(String::from("3333333333333333333333333333333333333333")).unwrap();
+    let mut fees = FeeSchedule::new();
+    fees.maker_bps = 10;
+    fees.taker_bps = 100;
+    fees.treasury = Some(treasury.clone());
+
+    // continuously, and in a call auction clearing at the buy's own limit
+    for call in [false, true].iter() {
+        let mut bb = mk_balancebook();
+        bb.insert_seeded(
+            UserAccount::new_seeded(String::from("Treasury"), treasury.to_string(), 0, 0).unwrap(),
+        ).unwrap();
+        let mut ob = OrderBook::new();
+        ob.fees = fees.clone();
+        let before = Snapshot::of(&bb, &[&ob]).unwrap();
+
+        // 9900 and 1% of it, at the higher of the buyer's two rates
+        let buy = BuyOrder::from_vals(mk_addr1(), 100, 99, 0);
+        match *call {
+            false => assert!(fill_buy(&mut ob, &mut bb, buy).unwrap().is_empty()),
+            true => callauction::submit(buy, &mut bb, &mut ob).unwrap(),
+        }
+        assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get(), 9_999);
+        bb.debit_cash(&mk_addr1(), 1).unwrap();
+        let before = before.issue(-1, DEFAULT_INSTRUMENT, 0);
+
+        // the bidder has no liquid cash left, and still pays its maker fee
+        let sell = SellOrder::from_vals(mk_addr2(), 100, 99, 0);
+        let fills = match *call {
+            false => fill_sell(&mut ob, &mut bb, sell).unwrap(),
+            true => {
+                callauction::submit(sell, &mut bb, &mut ob).unwrap();
+                callauction::clear(&mut ob, &mut bb).unwrap()
+            }
+        };
+        assert_eq!(fills.len(), 1);
+        assert_eq!((fills[0].buyer_fee, fills[0].seller_fee), (10, 99));
+        let buyer = bb.get_by_addr(&mk_addr1()).unwrap();
+        assert_eq!((buyer.cash.get(), buyer.hold_cash.get()), (89, 0));
+        assert_eq!(bb.get_by_addr(&treasury).unwrap().cash.get(), 109);
+        assert!(before.check(&Snapshot::of(&bb, &[&ob]).unwrap()).is_ok());
+    }
+}
+
+#[test]
+fn trading_rules_reject_bad_orders() {
+    let rules = TradingRules {
+        tick_size: 5,
+        lot_size: 10,
+        min_qty: 20,
+        max_qty: Some(100),
+        max_notional: Some(2_000),
+    };
+    let order = |price, qty| BuyOrder::from_vals(mk_addr1(), price, qty, 0);
+
+    assert!(rules
+        .check_order(&order(15, 40), &Precision::default())
+        .is_ok());
+    match rules.check_order(&order(12, 40), &Precision::default()) {
+        Err(LibError::TickSizeError { price: 12, .. }) => (),
+        other => panic!("expected a tick size error, got {:?}", other),
+    }
+    match rules.check_order(&order(0, 40), &Precision::default()) {
+        Err(LibError::TickSizeError { price: 0, .. }) => (),
+        other => panic!("expected a tick size error, got {:?}", other),
+    }
+    match rules.check_order(&order(15, 0), &Precision::default()) {
+        Err(LibError::EmptyZeroError { .. }) => (),
+        other => panic!("expected a zero quantity error, got {:?}", other),
+    }
+    match rules.check_order(&order(15, 45), &Precision::default()) {
+        Err(LibError::LotSizeError { qty: 45, .. }) => (),
+        other => panic!("expected a lot size error, got {:?}", other),
+    }
+    match rules.check_order(&order(15, 10), &Precision::default()) {
+        Err(LibError::MinQtyError { qty: 10, .. }) => (),
+        other => panic!("expected a minimum quantity error, got {:?}", other),
+    }
+    match rules.check_order(&order(5, 110), &Precision::default()) {
+        Err(LibError::MaxQtyError { qty: 110, .. }) => (),
+        other => panic!("expected a maximum quantity error, got {:?}", other),
+    }
+    match rules.check_order(&order(25, 100), &Precision::default()) {
+        Err(LibError::NotionalError {
+            notional: 2_500, ..
+        }) => (),
+        other => panic!("expected a notional error, got {:?}", other),
+    }
+    // the limit is on cash at the instrument's precision, here a hundredth of the product
+    let cents = Precision {
+        price: 2,
+        qty: 0,
+        cash: 0,
+    };
+    assert!(rules.check_order(&order(25, 100), &cents).is_ok());
+    let mut iceberg = order(15, 40);
+    iceberg.set_peak(Some(15));
+    assert!(rules.check_order(&iceberg, &Precision::default()).is_err());
+
+    // without a notional limit, price * qty still has to fit in a u64
+    let unlimited = TradingRules::default();
+    match unlimited.check_order(&order(u64::max_value(), 2), &Precision::default()) {
+        Err(LibError::IntOverflowError { .. }) => (),
+        other => panic!("expected an overflow error, got {:?}", other),
+    }
+
+    // amendments are checked on what they change
+    let amend = Amend::from_vals(String::from("x"), Some(20), None);
+    assert!(rules
+        .check_amend(&amend, (15, 10), &Precision::default())
+        .is_ok());
+    let amend = Amend::from_vals(String::from("x"), None, Some(200));
+    assert!(rules
+        .check_amend(&amend, (15, 40), &Precision::default())
+        .is_err());
+
+    let mut registry = InstrumentRegistry::new();
+    let mut bad = rules;
+    bad.max_qty = Some(10);
+    assert!(registry.set_rules(DEFAULT_INSTRUMENT, bad).is_err());
+    assert!(registry.set_rules("NOPE", rules).is_err());
+    registry.set_rules(DEFAULT_INSTRUMENT, rules).unwrap();
+    assert_eq!(registry.get(DEFAULT_INSTRUMENT).unwrap().rules, rules);
+}
+
+#[test]
+fn price_bands_and_circuit_breaker_halts() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    let band = PriceBand {
+        reference: ReferencePrice::LastTrade,
+        width_bps: 1_000,
+    };
+
+    // no trades yet, so no reference and no band
+    assert!(band.check(1, ob.reference_price(band.reference)).is_ok());
+    fill_sell(
+        &mut ob,
+        &mut bb,
+        SellOrder::from_vals(mk_addr2(), 100, 5, 0),
+    ).unwrap();
+    let fills = fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 100, 5, 0)).unwrap();
+    assert_eq!(ob.reference_price(ReferencePrice::LastTrade), Some(100));
+    assert_eq!(ob.reference_price(ReferencePrice::PrevClose), None);
+    assert!(band.check(110, ob.last_price).is_ok());
+    match band.check(111, ob.last_price) {
+        Err(LibError::PriceBandError {
+            low: 90, high: 110, ..
+        }) => (),
+        other => panic!("expected a price band error, got {:?}", other),
+    }
+
+    let breaker = CircuitBreaker {
+        move_bps: 500,
+        window: 2,
+        halt_blocks: 3,
+    };
+    ob.record_prices(&fills, 0, breaker.window);
+    assert!(!breaker.is_tripped(&ob.recent_prices));
+    let fills = fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 104, 1, 0)).unwrap();
+    assert!(fills.is_empty());
+    fill_sell(
+        &mut ob,
+        &mut bb,
+        SellOrder::from_vals(mk_addr2(), 106, 1, 0),
+    ).unwrap();
+    let fills = fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 106, 1, 0)).unwrap();
+    // 100 to 106 is a 6% move within two blocks
+    ob.record_prices(&fills, 1, breaker.window);
+    assert!(breaker.is_tripped(&ob.recent_prices));
+    // two blocks later the 100 trade has left the window
+    ob.record_prices(&[], 2, breaker.window);
+    assert_eq!(ob.recent_prices, vec![(1, 106)]);
+    assert!(!breaker.is_tripped(&ob.recent_prices));
+    // a block keeps only its lowest and highest prices
+    let at = |price| {
+        let mut fill = fills[0].clone();
+        fill.price = price;
+        fill
+    };
+    ob.record_prices(&[at(105), at(103)], 2, breaker.window);
+    ob.record_prices(&[at(104), at(107)], 2, breaker.window);
+    assert_eq!(ob.recent_prices, vec![(1, 106), (2, 103), (2, 107)]);
+
+    ob.halt = Some(Halt {
+        instrument: default_instrument(),
+        price: 106,
+        block: 0,
+        resumes_at: 2,
+    });
+    assert!(ob.is_halted(1));
+    assert!(!ob.is_halted(2));
+
+    let mut registry = InstrumentRegistry::new();
+    let mut bad = breaker;
+    bad.window = 0;
+    assert!(registry.set_breaker(DEFAULT_INSTRUMENT, Some(bad)).is_err());
+    bad.window = MAX_BREAKER_WINDOW + 1;
+    assert!(registry.set_breaker(DEFAULT_INSTRUMENT, Some(bad)).is_err());
+    registry
+        .set_breaker(DEFAULT_INSTRUMENT, Some(breaker))
+        .unwrap();
+    registry.set_band(DEFAULT_INSTRUMENT, Some(band)).unwrap();
+    let listing = registry.get(DEFAULT_INSTRUMENT).unwrap();
+    assert_eq!((listing.band, listing.breaker), (Some(band), Some(breaker)));
+}
+
+#[test]
+fn self_trade_prevention_modes() {
+    let order = |addr: Address, id: &str, price, qty, stp| {
+        let mut buy = BuyOrder::from_vals(addr.clone(), price, qty, 0);
+        buy.set_id(String::from(id));
+        buy.set_stp(stp);
+        let mut sell = SellOrder::from_vals(addr, price, qty, 0);
+        sell.set_id(String::from(id));
+        sell.set_stp(stp);
+        (buy, sell)
+    };
+    let sells = |ob: &OrderBook| -> Vec<(String, u64)> {
+        ob.sell_orders
+            .iter()
+            .map(|x| (x.get_id().clone(), x.get_qty()))
+            .collect()
+    };
+    let newest = SelfTradePrevention::CancelNewest;
+
+    // cancel newest: the incoming buy goes, the resting sell stays
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    fill_sell(&mut ob, &mut bb, order(mk_addr1(), "s1", 10, 5, newest).1).unwrap();
+    let fills = fill_buy(&mut ob, &mut bb, order(mk_addr1(), "b1", 10, 3, newest).0).unwrap();
+    assert!(fills.is_empty());
+    assert!(ob.is_buy_empty());
+    assert_eq!(sells(&ob), vec![(String::from("s1"), 5)]);
+    assert_eq!(ob.take_self_trades(), vec![(String::from("b1"), 3)]);
+
+    // a fill-or-kill order that would meet its owner's order is killed
+    fill_sell(&mut ob, &mut bb, order(mk_addr2(), "s2", 11, 5, newest).1).unwrap();
+    let (mut fok, _) = order(mk_addr1(), "b2", 11, 4, newest);
+    fok.set_order_type(OrderType::FillOrKill);
+    assert!(fill_buy(&mut ob, &mut bb, fok).unwrap().is_empty());
+    assert!(ob.take_self_trades().is_empty());
+
+    // cancel oldest: the resting sell is cancelled and its hold released, then matching goes on
+    let (buy, _) = order(mk_addr1(), "b3", 11, 4, SelfTradePrevention::CancelOldest);
+    let fills = fill_buy(&mut ob, &mut bb, buy).unwrap();
+    assert_eq!(fills.len(), 1);
+    assert_eq!((fills[0].sell_id.as_str(), fills[0].qty), ("s2", 4));
+    assert_eq!(ob.take_self_trades(), vec![(String::from("s1"), 5)]);
+    let alex = bb.get_by_addr(&mk_addr1()).unwrap();
+    assert_eq!(alex.hold_assets(DEFAULT_INSTRUMENT), 0);
+    assert_eq!(alex.assets(DEFAULT_INSTRUMENT), 1_004);
+
+    // decrement: both orders lose the smaller quantity and the buy carries on
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    fill_sell(&mut ob, &mut bb, order(mk_addr1(), "s1", 10, 5, newest).1).unwrap();
+    fill_sell(&mut ob, &mut bb, order(mk_addr2(), "s2", 10, 5, newest).1).unwrap();
+    let (buy, _) = order(mk_addr1(), "b1", 10, 8, SelfTradePrevention::Decrement);
+    let fills = fill_buy(&mut ob, &mut bb, buy).unwrap();
+    assert_eq!(fills.iter().map(|x| x.qty).sum::<u64>(), 3);
+    assert_eq!(sells(&ob), vec![(String::from("s2"), 2)]);
+    let removed = ob.take_self_trades();
+    assert_eq!(
+        removed,
+        vec![(String::from("b1"), 5), (String::from("s1"), 5)]
+    );
+
+    let mut status = OrderStatusBook::new();
+    let mut s1 = SellOrder::from_vals(mk_addr1(), 10, 5, 0);
+    s1.set_id(String::from("s1"));
+    status.record_order(&s1, &Vec::new());
+    status.record_self_trades(&removed);
+    assert_eq!(
+        status.get(&String::from("s1")).unwrap().status,
+        OrderStatus::Cancelled
+    );
+
+    // cancel both: neither order is left
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    fill_sell(&mut ob, &mut bb, order(mk_addr1(), "s1", 10, 5, newest).1).unwrap();
+    let (buy, _) = order(mk_addr1(), "b1", 10, 2, SelfTradePrevention::CancelBoth);
+    assert!(fill_buy(&mut ob, &mut bb, buy).unwrap().is_empty());
+    assert!(ob.is_buy_empty() && ob.is_sell_empty());
+    assert_eq!(
+        ob.take_self_trades(),
+        vec![(String::from("s1"), 5), (String::from("b1"), 2)]
+    );
+}
+
+#[test]
+fn price_levels_keep_heap_priority() {
+    // orders come off in the same order as from a heap, including the front of a level
+    // being put back after a partial fill
+    let mut rng = XorShiftRng::from_seed([7; 16]);
+    let mut heap: BinaryHeap<BuyOrder> = BinaryHeap::new();
+    let mut levels: PriceLevels<BuyOrder> = PriceLevels::new();
+    let (mut from_heap, mut from_levels) = (Vec::new(), Vec::new());
+    for nonce in 0..2_000 {
+        let price = rng.gen_range(90, 110);
+        let mut order = BuyOrder::from_vals(mk_addr1(), price, rng.gen_range(1, 10), nonce);
+        order.set_id(format!("b{}", nonce));
+        heap.push(order.clone());
+        levels.push(order);
+        if rng.gen_range(0, 4) == 0 {
+            let (mut a, mut b) = (heap.pop().unwrap(), levels.pop().unwrap());
+            from_heap.push(a.get_id().clone());
+            from_levels.push(b.get_id().clone());
+            if a.get_qty() > 1 {
+                a.dec_qty_by(1).unwrap();
+                b.dec_qty_by(1).unwrap();
+                heap.push(a);
+                levels.push(b);
+            }
+        }
+    }
+    assert_eq!(from_heap, from_levels);
+    assert_eq!(heap.len(), levels.len());
+    let stored = serde_cbor::to_vec(&heap).unwrap();
+    let rest: Vec<BuyOrder> = heap.into_sorted_vec().into_iter().rev().collect();
+    assert_eq!(levels.iter().cloned().collect::<Vec<BuyOrder>>(), rest);
+    // a book stored as a heap reads back
+    let decoded: PriceLevels<BuyOrder> = serde_cbor::from_slice(&stored).unwrap();
+    assert_eq!(decoded, levels);
+
+    let mut sells: PriceLevels<SellOrder> = PriceLevels::new();
+    for (id, price, qty, nonce) in vec![
+        ("a", 11, 5, 0),
+        ("b", 10, 3, 1),
+        ("c", 11, 2, 2),
+        ("d", 10, 4, 3),
+    ] {
+        let mut order = SellOrder::from_vals(mk_addr2(), price, qty, nonce);
+        order.set_id(String::from(id));
+        sells.push(order);
+    }
+    assert_eq!(sells.depth(), vec![(10, 7, 2), (11, 7, 2)]);
+    assert_eq!(sells.qty_through(10), 7);
+    assert_eq!(sells.remove("b").map(|x| x.get_nonce()), Some(1));
+    assert!(sells.get("b").is_none());
+    assert_eq!(sells.peek().unwrap().get_id(), "d");
+    assert_eq!(sells.get("c").map(|x| x.get_qty()), Some(2));
+
+    // the same orders encode the same whatever order they arrived in
+    let reversed: PriceLevels<SellOrder> = sells.clone().into_vec().into_iter().rev().collect();
+    assert_eq!(
+        serde_cbor::to_vec(&reversed).unwrap(),
+        serde_cbor::to_vec(&sells).unwrap()
+    );
+}
+
+#[test]
+fn sharded_addresses_stay_in_their_namespaces() {
+    let hex = |x: &String| x.len() == 70 && x.chars().all(|c| c.is_digit(16));
+    let instrument = String::from("GOLD");
+    let addrs = vec![
+        (
+            addressing::account_addr(&mk_addr1()),
+            addressing::ACCOUNT_PREFIX,
+        ),
+        (
+            addressing::account_addr(&mk_addr2()),
+            addressing::ACCOUNT_PREFIX,
+        ),
+        (
+            addressing::level_addr(&instrument, Side::Buy, 100),
+            addressing::ORDERBOOK_PREFIX,
+        ),
+        (
+            addressing::level_addr(&instrument, Side::Sell, 100),
+            addressing::ORDERBOOK_PREFIX,
+        ),
+        (
+            addressing::level_addr(DEFAULT_INSTRUMENT, Side::Buy, u64::max_value()),
+            addressing::ORDERBOOK_PREFIX,
+        ),
+        (addressing::auction_addr(3), addressing::AUCTION_PREFIX),
+        (
+            addressing::sealed_auction_addr(3),
+            addressing::SEALED_AUCTION_PREFIX,
+        ),
+    ];
+    for (addr, prefix) in addrs.iter() {
+        assert!(hex(addr), "{}", addr);
+        assert!(addr.starts_with(prefix));
+    }
+    let unique: BTreeSet<&String> = addrs.iter().map(|x| &x.0).collect();
+    assert_eq!(unique.len(), addrs.len());
+
+    // a book's levels are found under its own prefix, and not under another book's
+    let level = addressing::level_addr(&instrument, Side::Sell, 100);
+    assert!(level.starts_with(&addressing::levels_prefix(&instrument)));
+    assert!(!level.starts_with(&addressing::levels_prefix(DEFAULT_INSTRUMENT)));
+    assert!(addressing::orderbook_addr(&instrument)
+        .starts_with(&addressing::levels_prefix(&instrument)));
+
+    // an order declares its book's levels and every account, and can write what it reads
+    let order = BuyOrder::from_vals(mk_addr1(), 100, 1, 0);
+    let req = Wrapper::Bo(order).to_cli_request().unwrap();
+    assert!(req
+        .output_addrs
+        .contains(&addressing::levels_prefix(DEFAULT_INSTRUMENT)));
+    assert!(req
+        .output_addrs
+        .contains(&String::from(addressing::ACCOUNT_PREFIX)));
+    for addr in req.output_addrs.iter() {
+        assert!(req.input_addrs.contains(addr));
+    }
+}
+
+#[test]
+fn reach_covers_what_a_match_touches() {
+    let addr = |x: char| Address::from_string(std::iter::repeat(x).take(40).collect()).unwrap();
+    let mut full = BalanceBook::new();
+    for x in "123456".chars() {
+        full.insert_seeded(
+            UserAccount::new_seeded(x.to_string(), addr(x).to_string(), 10_000, 1_000).unwrap(),
+        ).unwrap();
+    }
+    let mut ob = OrderBook::new();
+    for (x, price) in vec![('2', 10), ('3', 11), ('4', 13), ('6', 14)] {
+        let mut sell = SellOrder::from_vals(addr(x), price, 3, 0);
+        sell.set_id(format!("sell{}", x));
+        fill_sell(&mut ob, &mut full, sell).unwrap();
+    }
+    let mut stop_buy = BuyOrder::from_vals(addr('5'), 13, 3, 0);
+    stop_buy.set_id(String::from("stop5"));
+    ob.add_stop(StopOrder::Buy(11, stop_buy)).unwrap();
+    let mut stop_sell = SellOrder::from_vals(addr('6'), 1, 3, 0);
+    stop_sell.set_id(String::from("stop6"));
+    ob.add_stop(StopOrder::Sell(5, stop_sell)).unwrap();
+
+    // the buy takes 10 and 11, setting off the stop, which reaches on to 13
+    let order = BuyOrder::from_vals(addr('1'), 12, 5, 1);
+    let reach = reach::reach(&ob, &order, 0);
+    assert!(reach.buy_levels.is_empty());
+    assert_eq!(reach.sell_levels, vec![10, 11, 13]);
+    let owners: Vec<Address> = reach.owners().into_iter().collect();
+    assert_eq!(owners, vec![addr('2'), addr('3'), addr('4'), addr('5')]);
+
+    // the accounts it names are all the match needs
+    let mut bb = BalanceBook::new();
+    for x in owners.iter().chain(Some(&addr('1'))) {
+        bb.insert_seeded(full.get_by_addr(x).unwrap().clone())
+            .unwrap();
+    }
+    let mut fills = fill_buy(&mut ob, &mut bb, order).unwrap();
+    let fired = ob.trigger_stops(&mut bb, &|_, _| Ok(()));
+    assert_eq!(fired.len(), 1);
+    fills.extend(fired.into_iter().flat_map(|x| x.1.unwrap()));
+    let sellers: Vec<(Address, u64)> = fills.iter().map(|x| (x.seller.clone(), x.price)).collect();
+    assert_eq!(
+        sellers,
+        vec![
+            (addr('2'), 10),
+            (addr('3'), 11),
+            (addr('3'), 11),
+            (addr('4'), 13)
+        ]
+    );
+
+    // a match reaching for an account that isn't loaded fails
+    let mut ob = OrderBook::new();
+    fill_sell(
+        &mut ob,
+        &mut full,
+        SellOrder::from_vals(addr('2'), 10, 5, 0),
+    ).unwrap();
+    let mut bb = BalanceBook::new();
+    bb.insert_seeded(full.get_by_addr(&addr('1')).unwrap().clone())
+        .unwrap();
+    assert!(fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(addr('1'), 10, 5, 1)).is_err());
+}
+
+#[test]
+fn settlement_conserves_value_and_backs_holds() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    let mut iceberg = SellOrder::from_vals(mk_addr2(), 10, 20, 0);
+    iceberg.set_peak(Some(5));
+    fill_sell(&mut ob, &mut bb, iceberg).unwrap();
+    fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 8, 10, 0)).unwrap();
+    let before = Snapshot::of(&bb, &[&ob]).unwrap();
+
+    // an incoming buy pays from cash, taking the iceberg's peaks from its hold
+    let fills = fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 12, 12, 0)).unwrap();
+    let taken: Vec<(u64, u64)> = fills.iter().map(|x| (x.price, x.qty)).collect();
+    assert_eq!(taken, vec![(10, 5), (10, 5), (10, 2)]);
+    // an incoming sell delivers from assets, paid from the resting buy's hold
+    let fills = fill_sell(&mut ob, &mut bb, SellOrder::from_vals(mk_addr2(), 7, 4, 0)).unwrap();
+    assert_eq!(fills[0].price, 8);
+    {
+        let alex = bb.get_by_addr(&mk_addr1()).unwrap();
+        assert_eq!((alex.cash.get(), alex.hold_cash.get()), (9_800, 48));
+        assert_eq!(alex.assets(DEFAULT_INSTRUMENT), 1_016);
+        let bob = bb.get_by_addr(&mk_addr2()).unwrap();
+        assert_eq!((bob.cash.get(), bob.hold_cash.get()), (10_152, 0));
+        assert_eq!(bob.hold_assets(DEFAULT_INSTRUMENT), 8);
+        assert_eq!(bob.assets(DEFAULT_INSTRUMENT), 976);
+    }
+    assert!(before.check(&Snapshot::of(&bb, &[&ob]).unwrap()).is_ok());
+
+    // cash from nowhere is caught
+    bb.credit_cash(&mk_addr1(), 1).unwrap();
+    assert!(before.check(&Snapshot::of(&bb, &[&ob]).unwrap()).is_err());
+    // and so is a hold no resting order accounts for
+    bb.debit_cash(&mk_addr1(), 6).unwrap();
+    bb.credit_hold_cash(&mk_addr1(), 5).unwrap();
+    assert!(before.check(&Snapshot::of(&bb, &[&ob]).unwrap()).is_err());
+    bb.debit_hold_cash(&mk_addr1(), 5).unwrap();
+    bb.credit_cash(&mk_addr1(), 5).unwrap();
+    assert!(before.check(&Snapshot::of(&bb, &[&ob]).unwrap()).is_ok());
+}
+
+#[test]
+fn holds_are_checked_by_what_they_come_to() {
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 8, 10, 0)).unwrap();
+    fill_sell(&mut ob, &mut bb, SellOrder::from_vals(mk_addr2(), 9, 5, 0)).unwrap();
+    let snapshot = Snapshot::of(&bb, &[&ob]).unwrap();
+    assert!(snapshot.check_complete().is_ok());
+
+    // a hold already short of its order is caught though nothing changes it
+    bb.debit_hold_cash(&mk_addr1(), 5).unwrap();
+    bb.credit_cash(&mk_addr1(), 5).unwrap();
+    let short = Snapshot::of(&bb, &[&ob]).unwrap();
+    assert!(short.check(&short).is_err());
+    bb.debit_cash(&mk_addr1(), 5).unwrap();
+    bb.credit_hold_cash(&mk_addr1(), 5).unwrap();
+
+    // one holding more than its orders may be backing an order on a book not loaded, but
+    // not when every book is counted
+    bb.debit_assets(&mk_addr2(), DEFAULT_INSTRUMENT, 3).unwrap();
+    bb.credit_hold_assets(&mk_addr2(), DEFAULT_INSTRUMENT, 3)
+        .unwrap();
+    let over = Snapshot::of(&bb, &[&ob]).unwrap();
+    assert!(over.check(&over).is_ok());
+    assert!(over.check_complete().is_err());
+    bb.debit_hold_assets(&mk_addr2(), DEFAULT_INSTRUMENT, 3)
+        .unwrap();
+    bb.credit_assets(&mk_addr2(), DEFAULT_INSTRUMENT, 3)
+        .unwrap();
+
+    // clearing the book returns every hold
+    let before = Snapshot::of(&bb, &[&ob]).unwrap();
+    ob.refund_all(&mut bb).unwrap();
+    let after = Snapshot::of(&bb, &[&ob]).unwrap();
+    assert!(before.check(&after).is_ok());
+    assert!(after.check_complete().is_ok());
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get(), 0);
+}
+
+#[test]
+fn auction_bids_back_their_holds() {
+    let mut bb = mk_balancebook();
+    let mut alist = AuctionList::new();
+    alist.add_auction(Auction::new(
+        1,
+        String::from("test"),
+        true,
+        50,
+        10,
+        default_instrument(),
+    ));
+    alist
+        .place_bid(&mut bb, Bid::from_vals(mk_addr1(), 1, 100), 1)
+        .unwrap();
+    let before = Snapshot::of(&bb, &[]).unwrap().with_bids(&alist.held());
+
+    // outbidding moves the hold from one bidder to the other
+    alist
+        .place_bid(&mut bb, Bid::from_vals(mk_addr2(), 1, 150), 2)
+        .unwrap();
+    let after = Snapshot::of(&bb, &[]).unwrap().with_bids(&alist.held());
+    assert!(before.check(&after).is_ok());
+    // counted without the bids, the new hold is one nothing accounts for
+    assert!(before.check(&Snapshot::of(&bb, &[]).unwrap()).is_err());
+
+    // the winner's held bid pays for what is auctioned, which is issued to them
+    let result = alist.end_auction(&mut bb, 1).unwrap();
+    assert_eq!(result.price.get(), 150);
+    let ended = Snapshot::of(&bb, &[]).unwrap().with_bids(&alist.held());
+    assert!(after.check(&ended).is_err());
+    assert!(after
+        .clone()
+        .issue(-150, DEFAULT_INSTRUMENT, 50)
+        .check(&ended)
+        .is_ok());
+}
+
+#[test]
+fn money_arithmetic_overflows_into_errors() {
+    // products are taken wide, so only results too big for a u64 fail
+    assert_eq!(
+        Amount::new(u64::max_value())
+            .scale(10_000, 10_000, "test")
+            .unwrap(),
+        Amount::new(u64::max_value())
+    );
+    let units = Precision::default();
+    assert_eq!(
+        units.notional(1 << 32, (1 << 32) - 1).unwrap(),
+        u64::max_value() - (1 << 32) + 1
+    );
+    match units.notional(1 << 32, 1 << 32) {
+        Err(LibError::IntOverflowError { .. }) => (),
+        other => panic!("expected an overflow error, got {:?}", other),
+    }
+    match Quantity::new(1).sub(Quantity::new(2), "test") {
+        Err(LibError::IntUnderflowError { .. }) => (),
+        other => panic!("expected an underflow error, got {:?}", other),
+    }
+
+    // an order whose cash can't be counted is rejected rather than panicking
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    let err = fill_buy(
+        &mut ob,
+        &mut bb,
+        BuyOrder::from_vals(mk_addr1(), u64::max_value(), 2, 0),
+    )
+    .unwrap_err();
+    match err.downcast::<LibError>() {
+        Ok(LibError::IntOverflowError { .. }) => (),
+        other => panic!("expected an overflow error, got {:?}", other),
+    }
+    assert!(ob.is_buy_empty());
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().cash.get(), 10_000);
+}
+
+#[test]
+fn decimal_prices_settle_rounding_down_and_release_holds_exactly() {
+    assert_eq!(Decimal::parse("12.35", 2).unwrap(), Decimal::new(1235, 2));
+    assert_eq!(Decimal::parse("7", 2).unwrap().units, 700);
+    assert_eq!(Decimal::parse(".5", 1).unwrap().units, 5);
+    assert!(Decimal::parse("12.345", 2).is_err());
+    assert!(Decimal::parse("1.2.3", 2).is_err());
+    assert!(Decimal::parse("99999999999999999999", 0).is_err());
+    assert_eq!(Decimal::new(1235, 2).to_string(), "12.35");
+    assert_eq!(Decimal::new(5, 3).to_string(), "0.005");
+    assert_eq!(Decimal::new(42, 0).to_string(), "42");
+
+    // prices to the cent, quantities to a tenth, cash to the cent
+    let mut bb = mk_balancebook();
+    let mut ob = OrderBook::new();
+    ob.precision = Precision {
+        price: 2,
+        qty: 1,
+        cash: 2,
+    };
+    // 1.0 at 0.33 holds 0.33
+    fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 33, 10, 0)).unwrap();
+    assert_eq!(bb.get_by_addr(&mk_addr1()).unwrap().hold_cash.get(), 33);
+    assert!(ob.to_string().contains("Unit Price: 0.33\nQuantity: 1.0\n"));
+    let before = Snapshot::of(&bb, &[&ob]).unwrap();
+
+    // 0.3 at 0.33 is 0.099, paid as 0.09; the rest of what it frees from the hold is refunded
+    let mut paid = Vec::new();
+    for qty in [3, 3, 4].iter() {
+        let fills = fill_sell(
+            &mut ob,
+            &mut bb,
+            SellOrder::from_vals(mk_addr2(), 33, *qty, 0),
+        ).unwrap();
+        paid.extend(fills.iter().map(|x| x.cash));
+    }
+    assert_eq!(paid, vec![9, 9, 13]);
+    assert!(ob.is_buy_empty());
+    let alex = bb.get_by_addr(&mk_addr1()).unwrap();
+    assert_eq!((alex.cash.get(), alex.hold_cash.get()), (10_000 - 31, 0));
+    assert_eq!(bb.get_by_addr(&mk_addr2()).unwrap().cash.get(), 10_000 + 31);
+    assert!(before.check(&Snapshot::of(&bb, &[&ob]).unwrap()).is_ok());
+}
+
+#[test]
+fn matching_algorithms_share_the_best_level_differently() {
+    let maker = |x: &str| Address::from_string(x.repeat(40)).unwrap();
+    let (a, b, c) = (maker("3"), maker("4"), maker("5"));
+    assert_eq!(
+        matching::pro_rata(51, &[10, 30, 60]).unwrap(),
+        vec![6, 15, 30]
+    );
+
+    // the same order flow under each algorithm: sells of 10, 30 and 60 at 10, oldest first,
+    // then a buy of 51
+    let expected = vec![
+        (MatchingAlgorithm::Fifo, vec![10, 30, 11]),
+        (MatchingAlgorithm::ProRata, vec![6, 15, 30]),
+        // the oldest sell is filled first; the other two share the rest, 41 * 30 / 90 and
+        // 41 * 60 / 90 rounded down, with the unit left over going to the older
+        (MatchingAlgorithm::TopOrder, vec![10, 14, 27]),
+    ];
+    for (algorithm, qtys) in expected.into_iter() {
+        let mut bb = mk_balancebook();
+        for (i, addr) in [&a, &b, &c].iter().enumerate() {
+            bb.insert_seeded(
+                UserAccount::new_seeded(format!("Maker {}", i), Address::to_string(addr), 0, 100)
+                    .unwrap(),
+            ).unwrap();
+        }
+        let mut ob = OrderBook::new();
+        ob.matching = algorithm;
+        for (addr, qty) in [(&a, 10), (&b, 30), (&c, 60)].iter() {
+            fill_sell(
+                &mut ob,
+                &mut bb,
+                SellOrder::from_vals((*addr).clone(), 10, *qty, 0),
+            ).unwrap();
+        }
+        let before = Snapshot::of(&bb, &[&ob]).unwrap();
+        let fills = fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 10, 51, 0)).unwrap();
+        let taken: Vec<(Address, u64)> = fills.iter().map(|x| (x.seller.clone(), x.qty)).collect();
+        let want: Vec<(Address, u64)> = vec![a.clone(), b.clone(), c.clone()]
+            .into_iter()
+            .zip(qtys.into_iter())
+            .collect();
+        assert_eq!(taken, want, "{}", algorithm);
+        assert!(before.check(&Snapshot::of(&bb, &[&ob]).unwrap()).is_ok());
+        // what is left keeps its time priority
+        let left: Vec<u64> = ob.sell_orders.iter().map(|x| x.get_qty()).collect();
+        let expected_left: Vec<u64> = vec![10, 30, 60]
+            .into_iter()
+            .zip(want.iter())
+            .map(|(x, y)| x - y.1)
+            .filter(|x| *x > 0)
+            .collect();
+        assert_eq!(left, expected_left, "{}", algorithm);
+
+        // sharing the whole level means meeting the buyer's own sell anywhere in it
+        fill_sell(&mut ob, &mut bb, SellOrder::from_vals(mk_addr1(), 10, 5, 0)).unwrap();
+        let fills = fill_buy(&mut ob, &mut bb, BuyOrder::from_vals(mk_addr1(), 10, 5, 0)).unwrap();
+        match algorithm {
+            MatchingAlgorithm::Fifo => assert_eq!(fills.len(), 1),
+            _ => assert!(fills.is_empty()),
+        }
+    }
+}
@@ -8,6 +8,7 @@ use dmktp::address::Address;
 use dmktp::addressing;
 use dmktp::balancebook::BalanceBook;
 use dmktp::errors::LibError;
+use dmktp::instrument::default_instrument;
 use dmktp::sealedbid::{SealedAuction, SealedAuctionList, SealedBid, UnsealedBid};
 use dmktp::useracct::UserAccount;
 use failure::Error;
@@ -69,7 +70,7 @@ fn auction_t1() {
 
     let mut auctionlist = SealedAuctionList::new();
 
-    let auction1: SealedAuction = SealedAuction::new(1, format!("test one"), true, 1_000, 3999);
+    let auction1: SealedAuction = SealedAuction::new(1, format!("test one"), true, 1_000, 3999, default_instrument());
     auctionlist.add_sealed_auction(auction1);
 
     let mut bid1: UnsealedBid = UnsealedBid::from_vals(
@@ -95,9 +96,11 @@ fn auction_t1() {
     let mut sbid2 = bid2.to_sealed_bid();
     let mut sbid3 = bid3.to_sealed_bid();
 
-    auctionlist.submit_sealed_bid(sbid1).unwrap();
-    auctionlist.submit_sealed_bid(sbid2).unwrap();
-    auctionlist.submit_sealed_bid(sbid3).unwrap();
+    auctionlist.submit_sealed_bid(sbid1, 0).unwrap();
+    auctionlist.submit_sealed_bid(sbid2, 0).unwrap();
+    auctionlist.submit_sealed_bid(sbid3, 0).unwrap();
+    // bidding closes after the auction's end_date
+    assert!(auctionlist.submit_sealed_bid(bid1.to_sealed_bid(), 4_000).is_err());
 
     auctionlist.submit_unsealed_bid(bid1).unwrap();
     auctionlist.submit_unsealed_bid(bid2).unwrap();
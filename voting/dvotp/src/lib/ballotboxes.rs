@@ -34,8 +34,7 @@ impl CBallotBox {
     pub fn check_and_insert(&mut self, _voterlist: &VoterList, _pubkey: &Pubkey, _ballot: CBallot) -> Result<(), LibError> {
         match _voterlist.check_registration(&_pubkey) {
             true => {
-                self.tally += _ballot.contents;
-                self.cardinality += 1;
+                self.count(&_ballot)?;
                 self.contents.insert(_pubkey.to_string(), _ballot);
                 Ok(())
             }
@@ -45,14 +44,25 @@ impl CBallotBox {
     }
 
     pub fn just_insert(&mut self, _ballot: CBallot, _pubkey: &Pubkey) -> Result<(), LibError> {
-        self.tally += _ballot.contents;
-        self.cardinality += 1;
+        self.count(&_ballot)?;
         self.contents.insert(_pubkey.to_string(), _ballot);
         Ok(())
     } 
 
+    // An empty box averages to 0.
     pub fn get_result(&self) -> u64 {
-        self.tally / self.cardinality
+        self.tally.checked_div(self.cardinality).unwrap_or(0)
+    }
+
+    // Nothing is counted unless the whole ballot can be.
+    fn count(&mut self, _ballot: &CBallot) -> Result<(), LibError> {
+        let tally = match self.tally.checked_add(_ballot.contents) {
+            Some(v) => v,
+            None => return Err(LibError::IntOverflowError{ origin: String::from("cardinal ballot tally"), fst: self.tally as usize, snd: _ballot.contents as usize, intsize: String::from("u64") })
+        };
+        self.tally = tally;
+        self.cardinality += 1;
+        Ok(())
     }
 
 
@@ -69,10 +79,7 @@ impl RBallotBox {
     pub fn check_and_insert(&mut self, _voterlist: &VoterList, _pubkey: &Pubkey, _ballot: RBallot) -> Result<(), LibError> {
         match _voterlist.check_registration(&_pubkey) {
             true => {
-                self.tally = self.tally.iter()
-                                 .zip(_ballot.contents.iter())
-                                 .map(|x| x.0 + x.1)
-                                 .collect::<Vec<u32>>();
+                self.count(&_ballot)?;
                 self.contents.insert(_pubkey.to_string(), _ballot);
                 Ok(())
             }
@@ -81,7 +88,7 @@ impl RBallotBox {
     }
 
     pub fn just_insert(&mut self, _ballot: RBallot, _pubkey: &Pubkey) -> Result<(), LibError> {
-        self.tally = self.tally.iter().zip(_ballot.contents.iter()).map(|x| x.0 + x.1).collect::<Vec<u32>>();
+        self.count(&_ballot)?;
         self.contents.insert(_pubkey.to_string(), _ballot);
         Ok(())
     }
@@ -96,6 +103,18 @@ impl RBallotBox {
 
     }
 
+    fn count(&mut self, _ballot: &RBallot) -> Result<(), LibError> {
+        let mut tally = Vec::with_capacity(self.tally.len());
+        for (x, y) in self.tally.iter().zip(_ballot.contents.iter()) {
+            match x.checked_add(*y) {
+                Some(v) => tally.push(v),
+                None => return Err(LibError::IntOverflowError{ origin: String::from("ranked ballot tally"), fst: *x as usize, snd: *y as usize, intsize: String::from("u32") })
+            }
+        }
+        self.tally = tally;
+        Ok(())
+    }
+
 
 }
 
@@ -0,0 +1,46 @@
+use failure::Error;
+use serde_cbor;
+
+use voterid::Pubkey;
+
+pub const BALLOT_CAST: &'static str = "voting/ballot_cast";
+pub const ELECTION_CLOSED: &'static str = "voting/election_closed";
+
+// Attributes are what subscribers filter on; the data field carries the CBOR of the
+// ballot or result. `ballot` is "continuous" or "range" for both event types.
+pub struct VotingEvent {
+    pub event_type: String,
+    pub attributes: Vec<(String, String)>,
+    pub data: Vec<u8>,
+}
+
+impl VotingEvent {
+    pub fn ballot_cast<T: ::serde::Serialize>(
+        _kind: &str,
+        _voter: &Pubkey,
+        _ballot: &T,
+    ) -> Result<Self, Error> {
+        Ok(VotingEvent {
+            event_type: String::from(BALLOT_CAST),
+            attributes: vec![
+                (String::from("ballot"), String::from(_kind)),
+                (String::from("voter"), _voter.to_string()),
+            ],
+            data: serde_cbor::to_vec(_ballot)?,
+        })
+    }
+
+    pub fn election_closed<T: ::serde::Serialize + ::std::fmt::Debug>(
+        _kind: &str,
+        _result: &T,
+    ) -> Result<Self, Error> {
+        Ok(VotingEvent {
+            event_type: String::from(ELECTION_CLOSED),
+            attributes: vec![
+                (String::from("ballot"), String::from(_kind)),
+                (String::from("result"), format!("{:?}", _result)),
+            ],
+            data: serde_cbor::to_vec(_result)?,
+        })
+    }
+}
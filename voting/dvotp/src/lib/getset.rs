@@ -11,6 +11,7 @@ use voterlist::{ VoterList };
 use ballotboxes::{ CBallotBox, RBallotBox };
 use electionstatus::ElectionStatus;
 use voterid::VoterId;
+use events::VotingEvent;
 
 use errors::LibError;
 use addressing;
@@ -165,4 +166,11 @@ impl <'a> FamilyState<'a> {
 
     }
 
+    pub fn add_event(&mut self, _event: VotingEvent) -> Result<(), ApplyError> {
+        match self.context.add_event(_event.event_type, _event.attributes, &_event.data) {
+            Ok(v) => return Ok(()),
+            Err(e) => return Err(ApplyError::InternalError(format!("Error executing add_event in getset: {:?}\n", e)))
+        }
+    }
+
 }
@@ -27,6 +27,7 @@ pub mod voterlist;
 pub mod ballots;
 pub mod ballotboxes;
 pub mod electionstatus;
+pub mod events;
 pub mod routes;
 
 #[cfg(test)]
@@ -12,11 +12,19 @@ use wrapper::Wrapper;
 use voterlist::{ VoterList };
 use ballotboxes::{ CBallotBox, RBallotBox };
 use electionstatus::ElectionStatus;
+use events::VotingEvent;
 use getset::FamilyState;
 use addressing::ELECTIONSTATEBOOLS;
 
 use addressing;
 
+fn to_event(_event: Result<VotingEvent, Error>) -> Result<VotingEvent, ApplyError> {
+    match _event {
+        Ok(v) => Ok(v),
+        Err(e) => Err(ApplyError::InternalError(format!("Error building voting event in routes: {:?}\n", e)))
+    }
+}
+
 // FIXME: These should all use composition with and_or, or_else style combinators
 pub fn voterid_route(_familystate: &mut FamilyState, _id: VoterId) -> Result<(), ApplyError> {
     let mut fetched_voterlist: VoterList = _familystate.get_voterlist()?;
@@ -27,6 +35,7 @@ pub fn voterid_route(_familystate: &mut FamilyState, _id: VoterId) -> Result<(),
 pub fn cb_route(_familystate: &mut FamilyState, _cb: CBallot, _id: VoterId) -> Result<(), ApplyError> {
     let mut fetched_cballotbox: CBallotBox =  _familystate.get_cballotbox()?;
 //    fetched_cballotbox.just_insert(_cb, &_id.get_pubkey())?;
+    let event = to_event(VotingEvent::ballot_cast("continuous", &_id.get_pubkey(), &_cb))?;
     match fetched_cballotbox.just_insert(_cb, &_id.get_pubkey()) {
         Ok(v) => (),
         Err(e) => return Err(ApplyError::InternalError(format!("Error inserting cballot in route handler: {:?}\n", e)))
@@ -34,11 +43,13 @@ pub fn cb_route(_familystate: &mut FamilyState, _cb: CBallot, _id: VoterId) -> R
 
     info!("passed cbroute error match");
 
-    _familystate.set_cballotbox(fetched_cballotbox)
+    _familystate.set_cballotbox(fetched_cballotbox)?;
+    _familystate.add_event(event)
 }
 
 pub fn rb_route(_familystate: &mut FamilyState, _rb: RBallot, _id: VoterId) -> Result<(), ApplyError> {
     let mut fetched_rballotbox: RBallotBox =  _familystate.get_rballotbox()?;
+    let event = to_event(VotingEvent::ballot_cast("range", &_id.get_pubkey(), &_rb))?;
     match fetched_rballotbox.just_insert(_rb, &_id.get_pubkey()) {
          Ok(v) => (),
          Err(e) => return Err(ApplyError::InternalError(format!("Error inserting cballot in route handler: {:?}\n", e)))
@@ -46,7 +57,8 @@ pub fn rb_route(_familystate: &mut FamilyState, _rb: RBallot, _id: VoterId) -> R
 
     info!("passed rb_route error match");
 
-    _familystate.set_rballotbox(fetched_rballotbox)
+    _familystate.set_rballotbox(fetched_rballotbox)?;
+    _familystate.add_event(event)
 }
 
 pub fn cend_route(_familystate: &mut FamilyState) -> Result<(), ApplyError> {
@@ -55,7 +67,9 @@ pub fn cend_route(_familystate: &mut FamilyState) -> Result<(), ApplyError> {
 
     fetched_election_status.set_cstatus(false);
     let cresult = fetched_cballotbox.get_result();
-    _familystate.set_cresult(cresult)
+    let event = to_event(VotingEvent::election_closed("continuous", &cresult))?;
+    _familystate.set_cresult(cresult)?;
+    _familystate.add_event(event)
 }
 
 pub fn rend_route(_familystate: &mut FamilyState) -> Result<(), ApplyError> {
@@ -65,6 +79,8 @@ pub fn rend_route(_familystate: &mut FamilyState) -> Result<(), ApplyError> {
     fetched_election_status.set_rstatus(false);
     let rresults = fetched_rballotbox.get_result();
     info!("obtained rb result just before setting: {:?}\n", rresults);
-    _familystate.set_rresult(rresults)
+    let event = to_event(VotingEvent::election_closed("range", &rresults))?;
+    _familystate.set_rresult(rresults)?;
+    _familystate.add_event(event)
 }
 